@@ -0,0 +1,54 @@
+use aio_translator_interface::{
+    AsyncTranslator, Language, Translator, TranslatorMutTrait, TranslatorTrait, error::Error,
+};
+
+/// Translates `query` with either flavour of translator.
+///
+/// Blocking translators need a source language, async ones fall back to detection.
+pub async fn translate_batch<T: Translator + ?Sized>(
+    translator: &mut T,
+    query: &[String],
+    from: Option<Language>,
+    to: &Language,
+) -> anyhow::Result<Vec<String>> {
+    if query.is_empty() {
+        return Ok(vec![]);
+    }
+    let text = match translator.translator() {
+        TranslatorTrait::Async(t) => Some(t.translate_vec(query, None, from, to).await?.text),
+        TranslatorTrait::Blocking(_) => None,
+    };
+    let text = match text {
+        Some(text) => text,
+        None => match translator.translator_mut() {
+            TranslatorMutTrait::Blocking(t) => {
+                t.translate_vec(query, None, from.ok_or(Error::MissingSourceLanguage)?, to)?
+            }
+            TranslatorMutTrait::Async(_) => unreachable!("translator changed its flavour"),
+        },
+    };
+    if text.len() != query.len() {
+        Err(Error::ResultCountMismatch(query.len(), text.len()))?;
+    }
+    Ok(text)
+}
+
+/// Sends HTML fragments to a translator that reported [`AsyncTranslator::handles_html`].
+pub(crate) async fn translate_html_batch(
+    translator: &dyn AsyncTranslator,
+    query: &[String],
+    from: Option<Language>,
+    to: &Language,
+) -> anyhow::Result<Vec<String>> {
+    if query.is_empty() {
+        return Ok(vec![]);
+    }
+    let text = translator
+        .translate_html_vec(query, None, from, to)
+        .await?
+        .text;
+    if text.len() != query.len() {
+        Err(Error::ResultCountMismatch(query.len(), text.len()))?;
+    }
+    Ok(text)
+}
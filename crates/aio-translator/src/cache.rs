@@ -0,0 +1,316 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+};
+
+use aio_translator_interface::{
+    AsyncTranslator, BlockingTranslator, Language, TranslationListOutput, TranslationOutput,
+    Translator, TranslatorMutTrait, TranslatorTrait, error::Error, prompt::PromptBuilder,
+};
+use async_trait::async_trait;
+
+/// Whether the query is HTML, the languages and the query.
+type Key = (bool, Option<Language>, Language, String);
+/// Translation and the detected source language.
+type Entry = (String, Option<Language>);
+
+#[derive(Default)]
+struct Entries {
+    map: HashMap<Key, Entry>,
+    order: VecDeque<Key>,
+}
+
+/// Remembers up to `capacity` translations, the oldest ones are dropped first.
+///
+/// Requests with a context are passed through, the translation depends on the prompt.
+pub struct CachedTranslator<T: Translator> {
+    t: T,
+    capacity: usize,
+    entries: Mutex<Entries>,
+}
+
+impl<T: Translator> CachedTranslator<T> {
+    pub fn new(t: T, capacity: usize) -> Self {
+        Self {
+            t,
+            capacity,
+            entries: Default::default(),
+        }
+    }
+
+    fn lookup(
+        &self,
+        query: &[String],
+        from: Option<Language>,
+        to: Language,
+        html: bool,
+    ) -> Vec<Option<Entry>> {
+        let entries = self.entries.lock().unwrap();
+        query
+            .iter()
+            .map(|v| entries.map.get(&(html, from, to, v.clone())).cloned())
+            .collect()
+    }
+
+    /// Merges the translations of the misses into `cached` and remembers them.
+    fn fill(
+        &self,
+        query: &[String],
+        cached: Vec<Option<Entry>>,
+        from: Option<Language>,
+        to: Language,
+        html: bool,
+        translated: TranslationListOutput,
+    ) -> anyhow::Result<Vec<String>> {
+        let (translated, lang) = (translated.text, translated.lang);
+        let misses = cached.iter().filter(|v| v.is_none()).count();
+        if translated.len() != misses {
+            Err(Error::ResultCountMismatch(misses, translated.len()))?;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        let mut translated = translated.into_iter();
+        Ok(query
+            .iter()
+            .zip(cached)
+            .map(|(query, cached)| match cached {
+                Some((text, _)) => text,
+                None => {
+                    let text = translated.next().unwrap_or_default();
+                    if self.capacity > 0 {
+                        entries.insert(
+                            (html, from, to, query.clone()),
+                            (text.clone(), lang),
+                            self.capacity,
+                        );
+                    }
+                    text
+                }
+            })
+            .collect())
+    }
+}
+
+impl Entries {
+    fn insert(&mut self, key: Key, value: Entry, capacity: usize) {
+        if self.map.insert(key.clone(), value).is_none() {
+            self.order.push_back(key);
+        }
+        while self.order.len() > capacity {
+            if let Some(key) = self.order.pop_front() {
+                self.map.remove(&key);
+            }
+        }
+    }
+}
+
+fn misses(query: &[String], cached: &[Option<Entry>]) -> Vec<String> {
+    query
+        .iter()
+        .zip(cached)
+        .filter(|(_, cached)| cached.is_none())
+        .map(|(query, _)| query.clone())
+        .collect()
+}
+
+impl<T: Translator + Send + Sync> Translator for CachedTranslator<T> {
+    fn local(&self) -> bool {
+        self.t.local()
+    }
+
+    fn translator<'a>(&'a self) -> TranslatorTrait<'a> {
+        match self.t.translator() {
+            TranslatorTrait::Async(_) => TranslatorTrait::Async(self),
+            TranslatorTrait::Blocking(_) => TranslatorTrait::Blocking(self),
+        }
+    }
+
+    fn translator_mut<'a>(&'a mut self) -> TranslatorMutTrait<'a> {
+        match self.t.translator_mut() {
+            TranslatorMutTrait::Async(_) => TranslatorMutTrait::Async(self),
+            TranslatorMutTrait::Blocking(_) => TranslatorMutTrait::Blocking(self),
+        }
+    }
+}
+
+#[async_trait]
+impl<T: Translator + Send + Sync> AsyncTranslator for CachedTranslator<T> {
+    async fn translate(
+        &self,
+        query: &str,
+        context: Option<PromptBuilder>,
+        from: Option<Language>,
+        to: &Language,
+    ) -> anyhow::Result<TranslationOutput> {
+        let mut out = self
+            .translate_vec(&[query.to_owned()], context, from, to)
+            .await?;
+        Ok(TranslationOutput {
+            text: out.text.remove(0),
+            lang: out.lang,
+        })
+    }
+
+    async fn translate_vec(
+        &self,
+        query: &[String],
+        context: Option<PromptBuilder>,
+        from: Option<Language>,
+        to: &Language,
+    ) -> anyhow::Result<TranslationListOutput> {
+        self.cached_vec(query, context, from, to, false).await
+    }
+
+    fn handles_html(&self) -> bool {
+        matches!(self.t.translator(), TranslatorTrait::Async(t) if t.handles_html())
+    }
+
+    async fn translate_html_vec(
+        &self,
+        query: &[String],
+        context: Option<PromptBuilder>,
+        from: Option<Language>,
+        to: &Language,
+    ) -> anyhow::Result<TranslationListOutput> {
+        self.cached_vec(query, context, from, to, true).await
+    }
+}
+
+impl<T: Translator + Send + Sync> CachedTranslator<T> {
+    async fn cached_vec(
+        &self,
+        query: &[String],
+        context: Option<PromptBuilder>,
+        from: Option<Language>,
+        to: &Language,
+        html: bool,
+    ) -> anyhow::Result<TranslationListOutput> {
+        let t = self.t.translator();
+        let t = t.as_async().unwrap();
+        if context.is_some() {
+            return match html {
+                true => t.translate_html_vec(query, context, from, to).await,
+                false => t.translate_vec(query, context, from, to).await,
+            };
+        }
+        let cached = self.lookup(query, from, *to, html);
+        let misses = misses(query, &cached);
+        let translated = match (misses.is_empty(), html) {
+            (true, _) => TranslationListOutput {
+                text: vec![],
+                lang: from.or(cached.iter().flatten().find_map(|v| v.1)),
+            },
+            (false, true) => t.translate_html_vec(&misses, None, from, to).await?,
+            (false, false) => t.translate_vec(&misses, None, from, to).await?,
+        };
+        let lang = translated.lang;
+        Ok(TranslationListOutput {
+            text: self.fill(query, cached, from, *to, html, translated)?,
+            lang,
+        })
+    }
+}
+
+impl<T: Translator + Send + Sync> BlockingTranslator for CachedTranslator<T> {
+    fn translate(
+        &mut self,
+        query: &str,
+        context: Option<PromptBuilder>,
+        from: Language,
+        to: &Language,
+    ) -> anyhow::Result<String> {
+        Ok(self
+            .translate_vec(&[query.to_owned()], context, from, to)?
+            .remove(0))
+    }
+
+    fn translate_vec(
+        &mut self,
+        query: &[String],
+        context: Option<PromptBuilder>,
+        from: Language,
+        to: &Language,
+    ) -> anyhow::Result<Vec<String>> {
+        if context.is_some() {
+            return self
+                .t
+                .translator_mut()
+                .as_blocking()
+                .unwrap()
+                .translate_vec(query, context, from, to);
+        }
+        let cached = self.lookup(query, Some(from), *to, false);
+        let misses = misses(query, &cached);
+        let text = match misses.is_empty() {
+            true => vec![],
+            false => self
+                .t
+                .translator_mut()
+                .as_blocking()
+                .unwrap()
+                .translate_vec(&misses, None, from, to)?,
+        };
+        let translated = TranslationListOutput {
+            text,
+            lang: Some(from),
+        };
+        self.fill(query, cached, Some(from), *to, false, translated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aio_translator_fake::FakeTranslator;
+    use aio_translator_original::OriginalTranslator;
+
+    use super::*;
+    use crate::translate_batch;
+
+    #[tokio::test]
+    async fn only_misses_are_translated() {
+        let mut t = CachedTranslator::new(OriginalTranslator::new(), 2);
+        let query = ["a".to_owned(), "b".to_owned()];
+        let from = Some(Language::English);
+        translate_batch(&mut t, &query, from, &Language::German)
+            .await
+            .unwrap();
+        assert_eq!(
+            t.lookup(&query, from, Language::German, false),
+            [Some(("a".to_owned(), from)), Some(("b".to_owned(), from))]
+        );
+        assert_eq!(
+            t.lookup(&query, from, Language::French, false),
+            [None, None]
+        );
+
+        let query = ["b".to_owned(), "c".to_owned()];
+        let out = translate_batch(&mut t, &query, from, &Language::German)
+            .await
+            .unwrap();
+        assert_eq!(out, query);
+        // "a" was the oldest entry
+        let entries = t.entries.lock().unwrap();
+        assert_eq!(entries.map.len(), 2);
+        assert!(
+            !entries
+                .map
+                .contains_key(&(false, from, Language::German, "a".to_owned()))
+        );
+    }
+
+    #[tokio::test]
+    async fn remembers_detected_language() {
+        let t = CachedTranslator::new(FakeTranslator::new().detect(Language::French), 4);
+        let log = t.t.log();
+        let a = t.translator();
+        let a = a.as_async().unwrap();
+        let query = ["a".to_owned()];
+        for _ in 0..2 {
+            let out = a
+                .translate_vec(&query, None, None, &Language::German)
+                .await
+                .unwrap();
+            assert_eq!(out.lang, Some(Language::French));
+        }
+        assert_eq!(log.len(), 1);
+    }
+}
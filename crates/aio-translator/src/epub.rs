@@ -0,0 +1,427 @@
+//! EPUB book translation.
+//!
+//! Every XHTML document of the spine is translated with [`XhtmlDocument`], the language
+//! of the package document is updated and the book is packed again. Finished chapters
+//! are written to a checkpoint directory, so an interrupted run picks up where it stopped.
+
+use std::{
+    io::{Cursor, Read, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use aio_translator_interface::{Language, Translator};
+use anyhow::{Context, bail};
+use zip::{CompressionMethod, ZipArchive, ZipWriter, write::SimpleFileOptions};
+
+use crate::{
+    markup::escape_xml,
+    xhtml::XhtmlDocument,
+    xml::{Node, Token, element_name, indent_before, local_name, tokenize},
+};
+
+struct ZipEntry {
+    name: String,
+    data: Vec<u8>,
+    compression: CompressionMethod,
+}
+
+/// Counts of what [`EpubBook::translate`] did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EpubReport {
+    /// Chapters translated in this run
+    pub chapters: usize,
+    /// Chapters taken from the checkpoint
+    pub resumed: usize,
+    /// Text runs and attributes
+    pub translated: usize,
+    /// Runs whose inline markup could not be restored
+    pub needs_review: usize,
+}
+
+pub struct EpubBook {
+    entries: Vec<ZipEntry>,
+    /// path of the package document
+    opf: String,
+    spine: Vec<String>,
+}
+
+impl EpubBook {
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        let mut entries = vec![];
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i)?;
+            let mut data = vec![];
+            file.read_to_end(&mut data)?;
+            entries.push(ZipEntry {
+                name: file.name().to_owned(),
+                data,
+                compression: file.compression(),
+            });
+        }
+        let mut book = Self {
+            entries,
+            opf: String::new(),
+            spine: vec![],
+        };
+
+        let container = book.text("META-INF/container.xml")?;
+        let tokens = tokenize(&container)?;
+        book.opf = tokens
+            .iter()
+            .filter(|v| element_name(v) == Some("rootfile"))
+            .find_map(|v| v.attr("full-path"))
+            .map(|v| v.value.clone())
+            .context("container.xml without rootfile")?;
+
+        let opf = book.text(&book.opf)?;
+        let tokens = tokenize(&opf)?;
+        let manifest = tokens
+            .iter()
+            .filter(|v| element_name(v) == Some("item"))
+            .filter_map(|v| Some((v.attr("id")?.value.as_str(), v)))
+            .collect::<Vec<_>>();
+        for itemref in tokens.iter().filter(|v| element_name(v) == Some("itemref")) {
+            let Some(idref) = itemref.attr("idref") else {
+                continue;
+            };
+            let Some((_, item)) = manifest.iter().find(|v| v.0 == idref.value) else {
+                bail!("spine references unknown item {}", idref.value);
+            };
+            let xhtml = item
+                .attr("media-type")
+                .is_some_and(|v| v.value == "application/xhtml+xml");
+            if let (true, Some(href)) = (xhtml, item.attr("href")) {
+                book.spine.push(resolve(&book.opf, &href.value));
+            }
+        }
+        Ok(book)
+    }
+
+    fn entry(&self, name: &str) -> anyhow::Result<&ZipEntry> {
+        self.entries
+            .iter()
+            .find(|v| v.name == name)
+            .with_context(|| format!("{name} missing in archive"))
+    }
+
+    fn text(&self, name: &str) -> anyhow::Result<String> {
+        Ok(String::from_utf8(self.entry(name)?.data.clone())?)
+    }
+
+    fn set_text(&mut self, name: &str, text: String) {
+        if let Some(entry) = self.entries.iter_mut().find(|v| v.name == name) {
+            entry.data = text.into_bytes();
+        }
+    }
+
+    /// Paths of the XHTML documents in reading order.
+    pub fn spine(&self) -> &[String] {
+        &self.spine
+    }
+
+    /// First `dc:language` of the package document.
+    pub fn language(&self) -> Option<Language> {
+        let opf = self.text(&self.opf).ok()?;
+        let tokens = tokenize(&opf).ok()?;
+        let (_, content) = language_element(&tokens)?;
+        Language::from_tag(opf[content?].trim())
+    }
+
+    /// Translates every chapter of the spine into `to`.
+    ///
+    /// With `checkpoint`, each finished chapter is stored in that directory and chapters
+    /// found there are taken as they are.
+    pub async fn translate<T: Translator + ?Sized>(
+        &mut self,
+        translator: &mut T,
+        from: Option<Language>,
+        to: Language,
+        checkpoint: Option<&Path>,
+    ) -> anyhow::Result<EpubReport> {
+        let from = from.or_else(|| self.language());
+        let mut report = EpubReport::default();
+        for (i, chapter) in self.spine.clone().into_iter().enumerate() {
+            let part = checkpoint.map(|v| v.join(format!("{i:04}.xhtml")));
+            if let Some(part) = part.as_ref().filter(|v| v.exists()) {
+                self.set_text(&chapter, std::fs::read_to_string(part)?);
+                report.resumed += 1;
+                continue;
+            }
+            let mut doc = XhtmlDocument::parse(self.text(&chapter)?)?;
+            let chapter_report = doc.translate(translator, from, to).await?;
+            report.chapters += 1;
+            report.translated += chapter_report.translated;
+            report.needs_review += chapter_report.needs_review;
+            let text = doc.to_string();
+            if let Some(part) = part {
+                std::fs::write(part, &text)?;
+            }
+            self.set_text(&chapter, text);
+        }
+        self.set_language(&to)?;
+        Ok(report)
+    }
+
+    fn set_language(&mut self, to: &Language) -> anyhow::Result<()> {
+        let Some(tag) = to.to_tag() else {
+            return Ok(());
+        };
+        let opf = self.text(&self.opf)?;
+        let tokens = tokenize(&opf)?;
+        let (range, text) = match language_element(&tokens) {
+            Some((_, Some(content))) => (content, escape_xml(tag)),
+            Some((token, None)) => {
+                let Node::Start { name, .. } = &token.node else {
+                    unreachable!("language_element returns start tags");
+                };
+                // `<dc:language/>`
+                (token.range.clone(), format!("<{name}>{tag}</{name}>"))
+            }
+            None => {
+                let Some(end) = tokens.iter().find(
+                    |v| matches!(&v.node, Node::End { name } if local_name(name) == "metadata"),
+                ) else {
+                    bail!("package document without metadata");
+                };
+                let at = end.range.start;
+                let indent = indent_before(&opf, at);
+                let at = at - indent.len();
+                (
+                    at..at,
+                    format!("{indent}  <dc:language>{tag}</dc:language>\n"),
+                )
+            }
+        };
+        let mut opf = opf;
+        opf.replace_range(range, &text);
+        let opf_path = self.opf.clone();
+        self.set_text(&opf_path, opf);
+        Ok(())
+    }
+
+    /// Packs the book, with the uncompressed `mimetype` first as the spec demands.
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        let mut writer = ZipWriter::new(Cursor::new(vec![]));
+        let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.start_file("mimetype", stored)?;
+        match self.entries.iter().find(|v| v.name == "mimetype") {
+            Some(entry) => writer.write_all(&entry.data)?,
+            None => writer.write_all(b"application/epub+zip")?,
+        }
+        for entry in self.entries.iter().filter(|v| v.name != "mimetype") {
+            let compression = match entry.compression {
+                CompressionMethod::Stored => CompressionMethod::Stored,
+                _ => CompressionMethod::Deflated,
+            };
+            let options = SimpleFileOptions::default().compression_method(compression);
+            if entry.name.ends_with('/') {
+                writer.add_directory(entry.name.as_str(), options)?;
+                continue;
+            }
+            writer.start_file(entry.name.as_str(), options)?;
+            writer.write_all(&entry.data)?;
+        }
+        Ok(writer.finish()?.into_inner())
+    }
+
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        std::fs::write(path, self.to_bytes()?)?;
+        Ok(())
+    }
+}
+
+/// `dc:language` start tag and the range of its content, if it has one.
+fn language_element(tokens: &[Token]) -> Option<(&Token, Option<Range<usize>>)> {
+    let i = tokens
+        .iter()
+        .position(|v| element_name(v) == Some("language"))?;
+    let token = &tokens[i];
+    match token.node {
+        Node::Start { empty: true, .. } => Some((token, None)),
+        _ => {
+            let start = token.range.end;
+            let end = tokens
+                .get(i + 1..)?
+                .iter()
+                .find(|v| matches!(v.node, Node::End { .. }))?;
+            Some((token, Some(start..end.range.start)))
+        }
+    }
+}
+
+/// Resolves `href` relative to the directory of `base` inside the archive.
+fn resolve(base: &str, href: &str) -> String {
+    let href = percent_decode(href.split('#').next().unwrap_or_default());
+    let mut parts = base.split('/').collect::<Vec<_>>();
+    parts.pop();
+    for part in href.split('/') {
+        match part {
+            "." | "" => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = text
+            .get(i + 1..i + 3)
+            .and_then(|v| u8::from_str_radix(v, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Directory the chapters of `target` are checkpointed in.
+pub fn checkpoint_dir(target: &Path) -> PathBuf {
+    let mut name = target.as_os_str().to_owned();
+    name.push(".parts");
+    PathBuf::from(name)
+}
+
+/// Translates the book at `source` into `target`.
+///
+/// Chapters are checkpointed next to `target` and the checkpoint is removed once the book
+/// is written. A checkpoint of a different source or language is discarded.
+pub async fn translate_epub<T: Translator + ?Sized>(
+    translator: &mut T,
+    source: &Path,
+    target: &Path,
+    from: Option<Language>,
+    to: Language,
+) -> anyhow::Result<EpubReport> {
+    let bytes = std::fs::read(source)?;
+    let mut book = EpubBook::from_bytes(&bytes)?;
+
+    let checkpoint = checkpoint_dir(target);
+    let stamp = format!(
+        "{:x} {}",
+        md5::compute(&bytes),
+        to.to_tag().unwrap_or_default()
+    );
+    let stamp_path = checkpoint.join("source");
+    if std::fs::read_to_string(&stamp_path).ok().as_deref() != Some(stamp.as_str()) {
+        if checkpoint.exists() {
+            std::fs::remove_dir_all(&checkpoint)?;
+        }
+        std::fs::create_dir_all(&checkpoint)?;
+        std::fs::write(&stamp_path, &stamp)?;
+    }
+
+    let report = book
+        .translate(translator, from, to, Some(&checkpoint))
+        .await?;
+    book.write(target)?;
+    std::fs::remove_dir_all(&checkpoint)?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OriginalTranslator;
+
+    const OPF: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>Demo</dc:title>
+    <dc:language>en</dc:language>
+  </metadata>
+  <manifest>
+    <item id="c1" href="text/chapter%201.xhtml" media-type="application/xhtml+xml"/>
+    <item id="c2" href="text/chapter2.xhtml" media-type="application/xhtml+xml"/>
+  </manifest>
+  <spine>
+    <itemref idref="c1"/>
+    <itemref idref="c2"/>
+  </spine>
+</package>"#;
+
+    fn chapter(text: &str) -> String {
+        format!("<html xmlns=\"http://www.w3.org/1999/xhtml\"><body><p>{text}</p></body></html>")
+    }
+
+    fn book() -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(vec![]));
+        let options = SimpleFileOptions::default();
+        let files = [
+            ("META-INF/container.xml", r#"<container><rootfiles><rootfile full-path="OEBPS/content.opf"/></rootfiles></container>"#.to_owned()),
+            ("OEBPS/content.opf", OPF.to_owned()),
+            ("OEBPS/text/chapter 1.xhtml", chapter("One")),
+            ("OEBPS/text/chapter2.xhtml", chapter("Two")),
+        ];
+        writer
+            .start_file(
+                "mimetype",
+                options.compression_method(CompressionMethod::Stored),
+            )
+            .unwrap();
+        writer.write_all(b"application/epub+zip").unwrap();
+        for (name, text) in files {
+            writer.start_file(name, options).unwrap();
+            writer.write_all(text.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[tokio::test]
+    async fn resume() {
+        let mut book = EpubBook::from_bytes(&book()).unwrap();
+        assert_eq!(
+            book.spine(),
+            ["OEBPS/text/chapter 1.xhtml", "OEBPS/text/chapter2.xhtml"]
+        );
+        assert_eq!(book.language(), Some(Language::English));
+
+        let dir = std::env::temp_dir().join(format!("aio-epub-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("0000.xhtml"), chapter("Eins")).unwrap();
+        let report = book
+            .translate(
+                &mut OriginalTranslator::new(),
+                None,
+                Language::German,
+                Some(&dir),
+            )
+            .await
+            .unwrap();
+        assert_eq!(report.resumed, 1);
+        assert_eq!(report.chapters, 1);
+        assert!(dir.join("0001.xhtml").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let packed = EpubBook::from_bytes(&book.to_bytes().unwrap()).unwrap();
+        assert_eq!(packed.entries[0].name, "mimetype");
+        assert_eq!(packed.language(), Some(Language::German));
+        assert_eq!(
+            packed.text("OEBPS/text/chapter 1.xhtml").unwrap(),
+            chapter("Eins")
+        );
+        assert_eq!(
+            packed.text("OEBPS/text/chapter2.xhtml").unwrap(),
+            chapter("Two")
+        );
+    }
+}
@@ -0,0 +1,243 @@
+use aio_translator_interface::{
+    AsyncTranslator, BlockingTranslator, Language, TranslationListOutput, TranslationOutput,
+    Translator, TranslatorMutTrait, TranslatorTrait, error::Error, prompt::PromptBuilder,
+};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use tokio::sync::RwLock;
+
+/// Asks the translators in order until one succeeds.
+///
+/// Blocking if every translator is, async otherwise. Blocking translators are skipped in
+/// async mode when the source language is unknown. Handles HTML if every translator does.
+/// Concurrent requests share the async translators, blocking ones take one at a time.
+pub struct FallbackTranslator<T: Translator> {
+    translators: Vec<RwLock<T>>,
+    blocking: bool,
+    local: bool,
+    html: bool,
+}
+
+impl<T: Translator> FallbackTranslator<T> {
+    pub fn new(translators: Vec<T>) -> Self {
+        Self {
+            blocking: translators
+                .iter()
+                .all(|v| matches!(v.translator(), TranslatorTrait::Blocking(_))),
+            local: translators.iter().all(|v| v.local()),
+            html: translators
+                .iter()
+                .all(|v| matches!(v.translator(), TranslatorTrait::Async(t) if t.handles_html())),
+            translators: translators.into_iter().map(RwLock::new).collect(),
+        }
+    }
+}
+
+fn checked(query: &[String], text: &[String]) -> anyhow::Result<()> {
+    if text.len() != query.len() {
+        Err(Error::ResultCountMismatch(query.len(), text.len()))?;
+    }
+    Ok(())
+}
+
+fn exhausted(last: Option<anyhow::Error>, count: usize) -> anyhow::Error {
+    match last {
+        Some(e) => e.context(format!("all {count} translators failed")),
+        None => anyhow!("no translators to fall back to"),
+    }
+}
+
+impl<T: Translator + Send + Sync> Translator for FallbackTranslator<T> {
+    fn local(&self) -> bool {
+        self.local
+    }
+
+    fn translator<'a>(&'a self) -> TranslatorTrait<'a> {
+        match self.blocking {
+            true => TranslatorTrait::Blocking(self),
+            false => TranslatorTrait::Async(self),
+        }
+    }
+
+    fn translator_mut<'a>(&'a mut self) -> TranslatorMutTrait<'a> {
+        match self.blocking {
+            true => TranslatorMutTrait::Blocking(self),
+            false => TranslatorMutTrait::Async(self),
+        }
+    }
+}
+
+#[async_trait]
+impl<T: Translator + Send + Sync> AsyncTranslator for FallbackTranslator<T> {
+    async fn translate(
+        &self,
+        query: &str,
+        context: Option<PromptBuilder>,
+        from: Option<Language>,
+        to: &Language,
+    ) -> anyhow::Result<TranslationOutput> {
+        let mut out = self
+            .translate_vec(&[query.to_owned()], context, from, to)
+            .await?;
+        Ok(TranslationOutput {
+            text: out.text.remove(0),
+            lang: out.lang,
+        })
+    }
+
+    async fn translate_vec(
+        &self,
+        query: &[String],
+        context: Option<PromptBuilder>,
+        from: Option<Language>,
+        to: &Language,
+    ) -> anyhow::Result<TranslationListOutput> {
+        self.fallback_vec(query, context, from, to, false).await
+    }
+
+    fn handles_html(&self) -> bool {
+        self.html
+    }
+
+    async fn translate_html_vec(
+        &self,
+        query: &[String],
+        context: Option<PromptBuilder>,
+        from: Option<Language>,
+        to: &Language,
+    ) -> anyhow::Result<TranslationListOutput> {
+        self.fallback_vec(query, context, from, to, true).await
+    }
+}
+
+impl<T: Translator + Send + Sync> FallbackTranslator<T> {
+    async fn fallback_vec(
+        &self,
+        query: &[String],
+        context: Option<PromptBuilder>,
+        from: Option<Language>,
+        to: &Language,
+        html: bool,
+    ) -> anyhow::Result<TranslationListOutput> {
+        let mut last = None;
+        for t in &self.translators {
+            let shared = t.read().await;
+            let result = match shared.translator() {
+                TranslatorTrait::Async(t) if html => {
+                    Some(t.translate_html_vec(query, context.clone(), from, to).await)
+                }
+                TranslatorTrait::Async(t) => {
+                    Some(t.translate_vec(query, context.clone(), from, to).await)
+                }
+                TranslatorTrait::Blocking(_) => None,
+            };
+            drop(shared);
+            let result = match (result, from) {
+                (Some(result), _) => result,
+                // only blocking translators are locked for the request
+                (None, Some(from)) => t
+                    .write()
+                    .await
+                    .translator_mut()
+                    .as_blocking()
+                    .unwrap()
+                    .translate_vec(query, context.clone(), from, to)
+                    .map(|text| TranslationListOutput {
+                        text,
+                        lang: Some(from),
+                    }),
+                (None, None) => Err(Error::MissingSourceLanguage.into()),
+            };
+            match result.and_then(|out| checked(query, &out.text).map(|_| out)) {
+                Ok(out) => {
+                    return Ok(TranslationListOutput {
+                        text: out.text,
+                        lang: out.lang.or(from),
+                    });
+                }
+                Err(e) => last = Some(e),
+            }
+        }
+        Err(exhausted(last, self.translators.len()))
+    }
+}
+
+impl<T: Translator + Send + Sync> BlockingTranslator for FallbackTranslator<T> {
+    fn translate(
+        &mut self,
+        query: &str,
+        context: Option<PromptBuilder>,
+        from: Language,
+        to: &Language,
+    ) -> anyhow::Result<String> {
+        Ok(self
+            .translate_vec(&[query.to_owned()], context, from, to)?
+            .remove(0))
+    }
+
+    fn translate_vec(
+        &mut self,
+        query: &[String],
+        context: Option<PromptBuilder>,
+        from: Language,
+        to: &Language,
+    ) -> anyhow::Result<Vec<String>> {
+        let count = self.translators.len();
+        let mut last = None;
+        for t in self.translators.iter_mut() {
+            let TranslatorMutTrait::Blocking(t) = t.get_mut().translator_mut() else {
+                continue;
+            };
+            let result = t.translate_vec(query, context.clone(), from, to);
+            match result.and_then(|text| checked(query, &text).map(|_| text)) {
+                Ok(text) => return Ok(text),
+                Err(e) => last = Some(e),
+            }
+        }
+        Err(exhausted(last, count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aio_translator_fake::{Failure, FakeTranslator};
+    use aio_translator_original::OriginalTranslator;
+
+    use super::*;
+    use crate::translate_batch;
+
+    #[tokio::test]
+    async fn falls_back() {
+        let translators: Vec<Box<dyn Translator + Send + Sync>> = vec![
+            Box::new(FakeTranslator::blocking().fail_at(0, Failure::DropLast)),
+            Box::new(OriginalTranslator::new()),
+        ];
+        let mut t = FallbackTranslator::new(translators);
+        assert!(matches!(t.translator(), TranslatorTrait::Blocking(_)));
+        let query = ["Hello".to_owned()];
+        let out = translate_batch(&mut t, &query, Some(Language::English), &Language::German)
+            .await
+            .unwrap();
+        assert_eq!(out, query);
+
+        let mut t = FallbackTranslator::new(vec![
+            FakeTranslator::blocking().fail_at(0, Failure::Error("down".to_owned())),
+        ]);
+        let e = translate_batch(&mut t, &query, Some(Language::English), &Language::German)
+            .await
+            .unwrap_err();
+        assert_eq!(e.to_string(), "all 1 translators failed");
+    }
+
+    #[tokio::test]
+    async fn keeps_detected_language() {
+        let t = FallbackTranslator::new(vec![
+            FakeTranslator::new().fail_at(0, Failure::Error("down".to_owned())),
+            FakeTranslator::new().detect(Language::French),
+        ]);
+        let out = AsyncTranslator::translate(&t, "a", None, None, &Language::German)
+            .await
+            .unwrap();
+        assert_eq!(out.lang, Some(Language::French));
+    }
+}
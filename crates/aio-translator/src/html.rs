@@ -0,0 +1,492 @@
+//! HTML document translation.
+//!
+//! Text runs of block elements are translated as a whole with their inline markup
+//! protected, then parsed back into the tree. `code`, `pre`, `script` and similar elements
+//! are left alone. Of the attributes only `alt` and `title` are translated.
+
+use std::fmt;
+
+use aio_translator_interface::{Language, Translator, TranslatorTrait};
+use ego_tree::{NodeId, NodeRef};
+use scraper::{ElementRef, Html, Node, node::Element};
+
+use crate::{
+    batch::{translate_batch, translate_html_batch},
+    markup::{Protected, escape_xml, escape_xml_attr},
+};
+
+/// Elements whose content is never translated.
+pub(crate) const SKIPPED: &[&str] = &[
+    "code", "pre", "script", "style", "noscript", "template", "textarea", "svg", "math",
+];
+
+/// Elements that are part of a text run instead of ending it.
+pub(crate) const INLINE: &[&str] = &[
+    "a", "abbr", "b", "bdi", "bdo", "br", "cite", "code", "data", "del", "dfn", "em", "font", "i",
+    "img", "ins", "kbd", "label", "mark", "q", "rp", "rt", "ruby", "s", "samp", "small", "span",
+    "strong", "sub", "sup", "time", "u", "var", "wbr",
+];
+
+const VOID: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+pub(crate) const ATTRIBUTES: &[&str] = &["alt", "title"];
+
+enum Piece {
+    Text(String),
+    Open(String),
+    Close(String),
+    Standalone(String),
+}
+
+/// Consecutive text and inline nodes that are translated together.
+struct Run {
+    nodes: Vec<NodeId>,
+    /// markup protected, for plain text translators
+    text: Protected,
+    /// inline tags kept, for translators that handle HTML
+    html: Protected,
+    /// whitespace around the run, kept out of the translation
+    prefix: String,
+    suffix: String,
+}
+
+/// Counts of what [`HtmlDocument::translate`] did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HtmlReport {
+    /// Text runs and attributes
+    pub translated: usize,
+    /// Runs whose inline markup could not be restored
+    pub needs_review: usize,
+}
+
+pub struct HtmlDocument {
+    html: Html,
+    fragment: bool,
+}
+
+impl HtmlDocument {
+    /// Parses a whole document if `raw` starts with a doctype or `<html>`, a fragment
+    /// otherwise.
+    pub fn parse(raw: &str) -> Self {
+        let start = raw.trim_start().get(..9).unwrap_or_default().to_lowercase();
+        let fragment = !start.starts_with("<!doctype") && !start.starts_with("<html");
+        let html = match fragment {
+            true => Html::parse_fragment(raw),
+            false => Html::parse_document(raw),
+        };
+        Self { html, fragment }
+    }
+
+    /// Language from the `lang` attribute of `<html>`.
+    pub fn source_language(&self) -> Option<Language> {
+        match self.fragment {
+            true => None,
+            false => Language::from_tag(self.html.root_element().attr("lang")?),
+        }
+    }
+
+    /// Translates all text runs and attributes.
+    ///
+    /// Translators that handle HTML themselves get the runs with their inline tags,
+    /// everything else gets plain text with the tags replaced by placeholders.
+    pub async fn translate<T: Translator + ?Sized>(
+        &mut self,
+        translator: &mut T,
+        from: Option<Language>,
+        to: Language,
+    ) -> anyhow::Result<HtmlReport> {
+        let from = from.or_else(|| self.source_language());
+        let mut report = HtmlReport::default();
+
+        // attributes first, so tags inside the runs already carry the translations
+        let mut attributes = vec![];
+        collect_attributes(self.html.tree.root(), &mut attributes);
+        let query = attributes.iter().map(|v| v.2.clone()).collect::<Vec<_>>();
+        let translated = translate_batch(translator, &query, from, &to).await?;
+        report.translated += translated.len();
+        for ((id, name, _), value) in attributes.into_iter().zip(translated) {
+            self.set_attr(id, name, &value);
+        }
+        if let (false, Some(tag)) = (self.fragment, to.to_tag()) {
+            let root = self.html.root_element().id();
+            self.set_attr(root, "lang", tag);
+        }
+
+        let mut groups = vec![];
+        collect_runs(self.html.tree.root(), &mut groups);
+        let runs = groups
+            .into_iter()
+            .filter_map(|v| self.run(v))
+            .collect::<Vec<_>>();
+
+        let html = matches!(translator.translator(), TranslatorTrait::Async(t) if t.handles_html());
+        let translated = match html {
+            true => {
+                let TranslatorTrait::Async(t) = translator.translator() else {
+                    unreachable!("translator changed its flavour");
+                };
+                let query = runs
+                    .iter()
+                    .map(|v| v.html.text().to_owned())
+                    .collect::<Vec<_>>();
+                translate_html_batch(t, &query, from, &to).await?
+            }
+            false => {
+                let query = runs
+                    .iter()
+                    .map(|v| v.text.text().to_owned())
+                    .collect::<Vec<_>>();
+                translate_batch(translator, &query, from, &to).await?
+            }
+        };
+
+        for (run, translation) in runs.iter().zip(translated) {
+            let (text, valid) = match html {
+                true => run.html.restore(&translation, |v| v.to_owned()),
+                false => run.text.restore(&translation, escape_xml),
+            };
+            match valid {
+                true => report.translated += 1,
+                false => report.needs_review += 1,
+            }
+            self.replace(&run.nodes, &format!("{}{text}{}", run.prefix, run.suffix));
+        }
+        Ok(report)
+    }
+
+    fn run(&self, nodes: Vec<NodeId>) -> Option<Run> {
+        let mut pieces = vec![];
+        for id in &nodes {
+            flatten(self.html.tree.get(*id)?, &mut pieces);
+        }
+        let mut prefix = String::new();
+        let mut suffix = String::new();
+        if let Some(Piece::Text(text)) = pieces.first_mut() {
+            prefix = text[..text.len() - text.trim_start().len()].to_owned();
+            *text = text[prefix.len()..].to_owned();
+        }
+        if let Some(Piece::Text(text)) = pieces.last_mut() {
+            suffix = text[text.trim_end().len()..].to_owned();
+            text.truncate(text.len() - suffix.len());
+        }
+
+        let mut text = Protected::new();
+        let mut html = Protected::new();
+        for piece in &pieces {
+            match piece {
+                Piece::Text(v) => {
+                    text.push_text(v);
+                    html.push_text(&escape_xml(v));
+                }
+                Piece::Open(v) => {
+                    text.push_open(v);
+                    html.push_text(v);
+                }
+                Piece::Close(v) => {
+                    text.push_close(v);
+                    html.push_text(v);
+                }
+                Piece::Standalone(v) => {
+                    text.push_standalone(v);
+                    html.push_standalone(v);
+                }
+            }
+        }
+        (!text.is_blank()).then_some(Run {
+            nodes,
+            text,
+            html,
+            prefix,
+            suffix,
+        })
+    }
+
+    fn set_attr(&mut self, id: NodeId, attr: &str, value: &str) {
+        let Some(mut node) = self.html.tree.get_mut(id) else {
+            return;
+        };
+        if let Node::Element(element) = node.value() {
+            for (name, current) in element.attrs.iter_mut() {
+                if &*name.local == attr {
+                    *current = value.into();
+                }
+            }
+        }
+    }
+
+    /// Replaces the run `nodes` with the nodes parsed from `html`.
+    fn replace(&mut self, nodes: &[NodeId], html: &str) {
+        let fragment = Html::parse_fragment(html);
+        let root = self.html.tree.extend_tree(fragment.tree).id();
+        // fragment root > <html> > parsed nodes
+        let children = self
+            .html
+            .tree
+            .get(root)
+            .and_then(|v| v.first_child())
+            .map(|v| v.children().map(|v| v.id()).collect::<Vec<_>>())
+            .unwrap_or_default();
+        let Some(mut anchor) = self.html.tree.get_mut(nodes[0]) else {
+            return;
+        };
+        for child in children {
+            anchor.insert_id_before(child);
+        }
+        for id in nodes {
+            if let Some(mut node) = self.html.tree.get_mut(*id) {
+                node.detach();
+            }
+        }
+    }
+}
+
+impl fmt::Display for HtmlDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.fragment {
+            true => f.write_str(&self.html.root_element().inner_html()),
+            false => f.write_str(&self.html.html()),
+        }
+    }
+}
+
+fn is_skipped(element: &Element) -> bool {
+    SKIPPED.contains(&element.name())
+        || element.attr("translate") == Some("no")
+        || element.classes().any(|v| v == "notranslate")
+}
+
+/// True for nodes that can be part of a text run.
+fn is_phrasing(node: NodeRef<Node>) -> bool {
+    match node.value() {
+        Node::Text(_) | Node::Comment(_) => true,
+        Node::Element(element) => {
+            INLINE.contains(&element.name())
+                && (is_skipped(element) || node.children().all(is_phrasing))
+        }
+        _ => false,
+    }
+}
+
+fn collect_runs(node: NodeRef<Node>, runs: &mut Vec<Vec<NodeId>>) {
+    let mut run = vec![];
+    for child in node.children() {
+        if is_phrasing(child) {
+            run.push(child.id());
+            continue;
+        }
+        runs.push(std::mem::take(&mut run));
+        match child.value() {
+            Node::Element(element) if is_skipped(element) => {}
+            _ => collect_runs(child, runs),
+        }
+    }
+    runs.push(run);
+}
+
+fn collect_attributes(node: NodeRef<Node>, out: &mut Vec<(NodeId, &'static str, String)>) {
+    if let Node::Element(element) = node.value() {
+        if is_skipped(element) {
+            return;
+        }
+        for name in ATTRIBUTES {
+            match element.attr(name) {
+                Some(value) if crate::is_valuable_text(value) => {
+                    out.push((node.id(), name, value.to_owned()))
+                }
+                _ => {}
+            }
+        }
+    }
+    for child in node.children() {
+        collect_attributes(child, out);
+    }
+}
+
+fn flatten(node: NodeRef<Node>, out: &mut Vec<Piece>) {
+    match node.value() {
+        Node::Text(text) => out.push(Piece::Text(text.to_string())),
+        Node::Comment(comment) => out.push(Piece::Standalone(format!("<!--{}-->", &**comment))),
+        Node::Element(element) if is_skipped(element) => {
+            let html = ElementRef::wrap(node).map(|v| v.html()).unwrap_or_default();
+            out.push(Piece::Standalone(html));
+        }
+        Node::Element(element) if VOID.contains(&element.name()) => {
+            out.push(Piece::Standalone(start_tag(element)));
+        }
+        Node::Element(element) => {
+            out.push(Piece::Open(start_tag(element)));
+            for child in node.children() {
+                flatten(child, out);
+            }
+            out.push(Piece::Close(format!("</{}>", element.name())));
+        }
+        _ => {}
+    }
+}
+
+fn start_tag(element: &Element) -> String {
+    let mut tag = format!("<{}", element.name());
+    for (name, value) in element.attrs.iter() {
+        tag.push(' ');
+        if let Some(prefix) = &name.prefix {
+            tag.push_str(prefix);
+            tag.push(':');
+        }
+        tag.push_str(&name.local);
+        tag.push_str("=\"");
+        tag.push_str(&escape_xml_attr(value));
+        tag.push('"');
+    }
+    tag.push('>');
+    tag
+}
+
+#[cfg(test)]
+mod tests {
+    use aio_translator_interface::{
+        AsyncTranslator, TranslationListOutput, TranslationOutput, TranslatorMutTrait,
+        prompt::PromptBuilder,
+    };
+
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        CachedTranslator, FallbackTranslator, OriginalTranslator, RetryTranslator, StyleTransfer,
+    };
+
+    /// Echoes plain text, translates `world` only on the HTML path.
+    struct Echo;
+
+    impl Translator for Echo {
+        fn local(&self) -> bool {
+            true
+        }
+
+        fn translator<'a>(&'a self) -> TranslatorTrait<'a> {
+            TranslatorTrait::Async(self)
+        }
+
+        fn translator_mut<'a>(&'a mut self) -> TranslatorMutTrait<'a> {
+            TranslatorMutTrait::Async(self)
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl AsyncTranslator for Echo {
+        async fn translate(
+            &self,
+            query: &str,
+            _: Option<PromptBuilder>,
+            from: Option<Language>,
+            _: &Language,
+        ) -> anyhow::Result<TranslationOutput> {
+            Ok(TranslationOutput {
+                text: query.to_owned(),
+                lang: from,
+            })
+        }
+
+        async fn translate_vec(
+            &self,
+            query: &[String],
+            _: Option<PromptBuilder>,
+            from: Option<Language>,
+            _: &Language,
+        ) -> anyhow::Result<TranslationListOutput> {
+            Ok(TranslationListOutput {
+                text: query.to_vec(),
+                lang: from,
+            })
+        }
+
+        fn handles_html(&self) -> bool {
+            true
+        }
+
+        async fn translate_html_vec(
+            &self,
+            query: &[String],
+            _: Option<PromptBuilder>,
+            from: Option<Language>,
+            _: &Language,
+        ) -> anyhow::Result<TranslationListOutput> {
+            Ok(TranslationListOutput {
+                text: query.iter().map(|v| v.replace("world", "Welt")).collect(),
+                lang: from,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn fragment() {
+        let raw = r#"<h1 title="Greeting">Hello <b>world</b></h1>
+<p>Run <code>cargo build</code> first &amp; wait.<img alt="A cat" src="cat.png"></p>
+<pre>untouched</pre><script>let a = 1 < 2;</script>"#;
+        let mut doc = HtmlDocument::parse(raw);
+        let report = doc
+            .translate(
+                &mut OriginalTranslator::new(),
+                Some(Language::English),
+                Language::German,
+            )
+            .await
+            .unwrap();
+        assert_eq!(report.translated, 4);
+        assert_eq!(report.needs_review, 0);
+        assert_eq!(doc.to_string(), raw);
+    }
+
+    #[tokio::test]
+    async fn html_translator() {
+        let mut doc = HtmlDocument::parse(
+            "<!DOCTYPE html><html lang=\"en\"><head></head><body><p>Hello <b>world</b> <code>world</code></p></body></html>",
+        );
+        assert_eq!(doc.source_language(), Some(Language::English));
+        doc.translate(&mut Echo, None, Language::German)
+            .await
+            .unwrap();
+        assert_eq!(
+            doc.to_string(),
+            "<!DOCTYPE html><html lang=\"de\"><head></head><body><p>Hello <b>Welt</b> <code>world</code></p></body></html>"
+        );
+    }
+
+    #[tokio::test]
+    async fn wrapped_html_translator() {
+        let mut translator = FallbackTranslator::new(vec![RetryTranslator::new(
+            CachedTranslator::new(StyleTransfer::new(Echo), 10),
+            2,
+            Duration::ZERO,
+        )]);
+        let mut doc = HtmlDocument::parse("<p>Hello <b>world</b></p>");
+        doc.translate(&mut translator, Some(Language::English), Language::German)
+            .await
+            .unwrap();
+        assert_eq!(doc.to_string(), "<p>Hello <b>Welt</b></p>");
+
+        let plain = StyleTransfer::new(OriginalTranslator::new());
+        assert!(!matches!(plain.translator(), TranslatorTrait::Async(t) if t.handles_html()));
+    }
+
+    #[test]
+    fn runs() {
+        let doc = HtmlDocument::parse(
+            "<div>\n  Intro <a href=\"/x\">link</a>\n  <p>Para <code>x</code></p>\n</div>",
+        );
+        let mut groups = vec![];
+        collect_runs(doc.html.tree.root(), &mut groups);
+        let runs = groups
+            .into_iter()
+            .filter_map(|v| doc.run(v))
+            .collect::<Vec<_>>();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].text.text(), "Intro {0}link{1}");
+        assert_eq!(runs[0].html.text(), "Intro <a href=\"/x\">link</a>");
+        assert_eq!(runs[0].prefix, "\n  ");
+        assert_eq!(runs[1].text.text(), "Para {0}");
+    }
+}
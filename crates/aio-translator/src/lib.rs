@@ -1,34 +1,76 @@
+mod batch;
+mod cache;
+pub mod epub;
+mod fallback;
+pub mod html;
+pub mod markdown;
+mod markup;
+pub mod registry;
+pub mod resource;
+mod retry;
 mod style_transfer;
+pub mod table;
+pub mod xhtml;
+pub mod xliff;
+mod xml;
 
 pub use aio_translator_interface::{
     AsyncTranslator, BlockingTranslator, Detector, Language, Model, TranslationListOutput,
-    TranslationOutput, Translator, TranslatorTrait, error::ApiError, error::Error,
-    prompt::PromptBuilder,
+    TranslationOutput, Translator, TranslatorMutTrait, TranslatorTrait, error::ApiError,
+    error::Error, files::ModelFiles, http::HttpOptions, manager::ModelManager, pool::PoolOptions,
+    pool::SharedModel, pool::SharedTranslator, prompt::PromptBuilder,
 };
 
+#[cfg(feature = "baidu")]
 pub use aio_translator_baidu::BaiduTranslator;
+#[cfg(feature = "caiyun")]
 pub use aio_translator_caiyun::CaiyunTranslator;
+#[cfg(feature = "deepl")]
 pub use aio_translator_deepl::DeeplTranslator;
+#[cfg(feature = "fake")]
+pub use aio_translator_fake::{Failure, FakeTranslator, Pseudo};
+#[cfg(feature = "google")]
 pub use aio_translator_google::GoogleTranslator;
+#[cfg(feature = "ct2")]
+pub use aio_translator_interface::ct2::{Score, Threads};
+#[cfg(feature = "ct2")]
+pub use aio_translator_interface::decode::{Alternative, DecodeOptions};
+#[cfg(feature = "jparacrawl")]
 pub use aio_translator_jparacrawl::JParaCrawlTranslator;
+#[cfg(feature = "jparacrawl")]
 pub use aio_translator_jparacrawl::Size as JParaCrawlSize;
 pub use aio_translator_langid::LangIdDetector;
 #[cfg(feature = "lingua")]
 pub use aio_translator_lingua::LinguaDetector;
+#[cfg(feature = "m2m100")]
 pub use aio_translator_m2m100::M2M100Translator;
+#[cfg(feature = "m2m100")]
 pub use aio_translator_m2m100::Size as M2M100Size;
+#[cfg(feature = "mbart50")]
 pub use aio_translator_mbart50::MBart50Translator;
+#[cfg(feature = "mymemory")]
 pub use aio_translator_mymemory::MyMemoryTranslator;
+#[cfg(feature = "nllb")]
 pub use aio_translator_nllb::NLLBTranslator;
+#[cfg(feature = "nllb")]
 pub use aio_translator_nllb::Size as NLLBSize;
 pub use aio_translator_none::NoneTranslator;
 pub use aio_translator_original::OriginalTranslator;
+#[cfg(feature = "papago")]
 pub use aio_translator_papago::PapagoTranslator;
+#[cfg(feature = "sugoi")]
 pub use aio_translator_sugoi::SugoiTranslator;
 #[cfg(feature = "whatlang")]
 pub use aio_translator_whatlang::WhatLangDetector;
+#[cfg(feature = "youdao")]
 pub use aio_translator_youdao::YoudaoTranslator;
-pub use ct2rs::ComputeType;
+pub use batch::translate_batch;
+pub use cache::CachedTranslator;
+#[cfg(feature = "ct2")]
+pub use ct2rs::{BatchType, ComputeType};
+pub use fallback::FallbackTranslator;
+pub use registry::Registry;
+pub use retry::RetryTranslator;
 pub use style_transfer::StyleTransfer;
 pub use style_transfer::is_valuable_text;
 
@@ -36,6 +78,7 @@ pub use style_transfer::is_valuable_text;
 mod tests {
 
     #[test]
+    #[cfg(feature = "sugoi")]
     fn test_style_transfer() {
         pub use crate::Translator;
         let cuda = true;
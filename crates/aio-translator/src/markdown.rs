@@ -0,0 +1,291 @@
+//! Markdown document translation.
+//!
+//! Like XLIFF, the document is never re-serialized. Paragraphs, headings, list items and
+//! table cells are spliced into the original text with their inline syntax protected,
+//! code blocks, HTML blocks and front matter stay as they are.
+
+use std::{fmt, ops::Range};
+
+use aio_translator_interface::{Language, Translator};
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+
+use crate::{batch::translate_batch, markup::Protected};
+
+/// A paragraph-like run of inline content.
+struct Run {
+    span: Range<usize>,
+    text: Protected,
+}
+
+/// Counts of what [`MarkdownDocument::translate`] did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MarkdownReport {
+    pub translated: usize,
+    /// Runs whose inline syntax could not be restored
+    pub needs_review: usize,
+}
+
+pub struct MarkdownDocument {
+    raw: String,
+}
+
+/// How the source between two inline events is protected.
+#[derive(Clone, Copy)]
+enum Boundary {
+    Open,
+    Break,
+    Standalone,
+}
+
+/// Builds a [`Run`] from the offsets of inline events.
+struct RunBuilder<'a> {
+    raw: &'a str,
+    start: Option<usize>,
+    cursor: usize,
+    boundary: Option<Boundary>,
+    text: Protected,
+}
+
+impl<'a> RunBuilder<'a> {
+    fn new(raw: &'a str) -> Self {
+        Self {
+            raw,
+            start: None,
+            cursor: 0,
+            boundary: None,
+            text: Protected::new(),
+        }
+    }
+
+    /// Protects the source between the last event and `to`.
+    fn gap(&mut self, to: usize) {
+        if self.start.is_none() {
+            self.start = Some(to);
+            self.cursor = to;
+        }
+        let gap = &self.raw[self.cursor..to];
+        match self.boundary.take() {
+            Some(Boundary::Open) => self.text.push_open(gap),
+            // soft breaks are joined unless the next line carries a prefix like `>`
+            Some(Boundary::Break) if gap.trim().is_empty() => self.text.push_text(" "),
+            Some(_) => self.text.push_standalone(gap),
+            None if gap.is_empty() => {}
+            // escapes and entities
+            None if !gap.contains('\n') => self.text.push_text(gap),
+            None => self.text.push_standalone(gap),
+        }
+        self.cursor = to;
+    }
+
+    fn event(&mut self, event: &Event, range: Range<usize>) {
+        match event {
+            Event::Text(_) => {
+                self.gap(range.start);
+                self.text.push_text(&self.raw[range.clone()]);
+                self.cursor = range.end;
+            }
+            Event::Start(_) => {
+                self.gap(range.start);
+                self.boundary = Some(Boundary::Open);
+            }
+            Event::End(_) => {
+                // the end range covers the whole element, its closing syntax is the rest
+                let end = range.end.max(self.cursor);
+                self.gap(self.cursor);
+                self.text.push_close(&self.raw[self.cursor..end]);
+                self.cursor = end;
+            }
+            Event::SoftBreak => {
+                self.gap(range.start);
+                self.boundary = Some(Boundary::Break);
+            }
+            Event::HardBreak => {
+                self.gap(range.start);
+                self.boundary = Some(Boundary::Standalone);
+            }
+            _ => {
+                self.gap(range.start);
+                self.text.push_standalone(&self.raw[range.clone()]);
+                self.cursor = range.end;
+            }
+        }
+    }
+
+    fn finish(&mut self, runs: &mut Vec<Run>) {
+        let text = std::mem::take(&mut self.text);
+        self.boundary = None;
+        if let Some(start) = self.start.take()
+            && !text.is_blank()
+        {
+            runs.push(Run {
+                span: start..self.cursor,
+                text,
+            });
+        }
+    }
+}
+
+fn is_inline(tag: &Tag) -> bool {
+    matches!(
+        tag,
+        Tag::Emphasis
+            | Tag::Strong
+            | Tag::Strikethrough
+            | Tag::Superscript
+            | Tag::Subscript
+            | Tag::Link { .. }
+            | Tag::Image { .. }
+    )
+}
+
+fn is_inline_end(tag: &TagEnd) -> bool {
+    matches!(
+        tag,
+        TagEnd::Emphasis
+            | TagEnd::Strong
+            | TagEnd::Strikethrough
+            | TagEnd::Superscript
+            | TagEnd::Subscript
+            | TagEnd::Link
+            | TagEnd::Image
+    )
+}
+
+impl MarkdownDocument {
+    pub fn parse(raw: impl Into<String>) -> Self {
+        Self { raw: raw.into() }
+    }
+
+    fn runs(&self) -> Vec<Run> {
+        let options = Options::ENABLE_TABLES
+            | Options::ENABLE_FOOTNOTES
+            | Options::ENABLE_STRIKETHROUGH
+            | Options::ENABLE_TASKLISTS
+            | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS
+            | Options::ENABLE_PLUSES_DELIMITED_METADATA_BLOCKS;
+        let mut runs = vec![];
+        let mut builder = RunBuilder::new(&self.raw);
+        // code blocks, html blocks and metadata
+        let mut verbatim = 0;
+        for (event, range) in Parser::new_ext(&self.raw, options).into_offset_iter() {
+            let inline = match &event {
+                Event::Start(tag) if !is_inline(tag) => {
+                    if matches!(
+                        tag,
+                        Tag::CodeBlock(_) | Tag::HtmlBlock | Tag::MetadataBlock(_)
+                    ) {
+                        verbatim += 1;
+                    }
+                    false
+                }
+                Event::End(tag) if !is_inline_end(tag) => {
+                    if matches!(
+                        tag,
+                        TagEnd::CodeBlock | TagEnd::HtmlBlock | TagEnd::MetadataBlock(_)
+                    ) {
+                        verbatim -= 1;
+                    }
+                    false
+                }
+                Event::Html(_) | Event::Rule | Event::DisplayMath(_) | Event::TaskListMarker(_) => {
+                    false
+                }
+                _ => verbatim == 0,
+            };
+            match inline {
+                true => builder.event(&event, range),
+                false => builder.finish(&mut runs),
+            }
+        }
+        builder.finish(&mut runs);
+        runs
+    }
+
+    /// Text of every run as it is sent to the translator.
+    pub fn segments(&self) -> Vec<String> {
+        self.runs()
+            .into_iter()
+            .map(|v| v.text.text().to_owned())
+            .collect()
+    }
+
+    pub async fn translate<T: Translator + ?Sized>(
+        &mut self,
+        translator: &mut T,
+        from: Option<Language>,
+        to: Language,
+    ) -> anyhow::Result<MarkdownReport> {
+        let runs = self.runs();
+        let query = runs
+            .iter()
+            .map(|v| v.text.text().to_owned())
+            .collect::<Vec<_>>();
+        let translated = translate_batch(translator, &query, from, &to).await?;
+
+        let mut report = MarkdownReport::default();
+        let mut raw = String::with_capacity(self.raw.len());
+        let mut last = 0;
+        for (run, translation) in runs.iter().zip(translated) {
+            let (text, valid) = run.text.restore(&translation, |v| v.to_owned());
+            match valid {
+                true => report.translated += 1,
+                false => report.needs_review += 1,
+            }
+            raw.push_str(&self.raw[last..run.span.start]);
+            raw.push_str(&text);
+            last = run.span.end;
+        }
+        raw.push_str(&self.raw[last..]);
+        self.raw = raw;
+        Ok(report)
+    }
+}
+
+impl fmt::Display for MarkdownDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OriginalTranslator;
+
+    const DOC: &str = "---\ntitle: Demo\n---\n# Hello *world*\n\nSee [the docs](https://example.com \"Docs\") and run `cargo test`.\nSecond line \\* escaped.\n\n> quoted\n> text\n\n```rust\nfn main() {}\n```\n\n- one\n- **two**\n\n| a | b |\n|---|---|\n| c | d |\n";
+
+    #[test]
+    fn segments() {
+        let doc = MarkdownDocument::parse(DOC);
+        assert_eq!(
+            doc.segments(),
+            [
+                "Hello {0}world{1}",
+                "See {0}the docs{1} and run {2}. Second line \\* escaped.",
+                "quoted{0}text",
+                "one",
+                "{0}two{1}",
+                "a",
+                "b",
+                "c",
+                "d",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        let mut doc = MarkdownDocument::parse(DOC);
+        let report = doc
+            .translate(
+                &mut OriginalTranslator::new(),
+                Some(Language::English),
+                Language::German,
+            )
+            .await
+            .unwrap();
+        assert_eq!(report.needs_review, 0);
+        // the soft break gets joined, everything else stays as it is
+        assert_eq!(doc.to_string(), DOC.replace("`.\nSecond", "`. Second"));
+    }
+}
@@ -0,0 +1,368 @@
+//! Inline markup protection shared by the document formats.
+//!
+//! Tags and other untranslatable spans are swapped for `{n}` placeholders before a
+//! segment is sent to a translator and swapped back afterwards.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SpanKind {
+    Standalone,
+    Open(usize),
+    Close(usize),
+}
+
+#[derive(Debug, Clone)]
+struct Span {
+    raw: String,
+    kind: SpanKind,
+}
+
+/// Segment text with its markup replaced by placeholders.
+#[derive(Debug, Clone, Default)]
+pub struct Protected {
+    text: String,
+    spans: Vec<Span>,
+    open: Vec<usize>,
+    pairs: usize,
+    /// spans in front of the first text, used as prefix when restoring fails
+    leading: Option<usize>,
+}
+
+impl Protected {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Text that should be sent to the translator.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// True if there is nothing to translate besides markup.
+    pub fn is_blank(&self) -> bool {
+        let mut text = self.text.clone();
+        for i in 0..self.spans.len() {
+            text = text.replace(&placeholder(i), "");
+        }
+        !crate::is_valuable_text(&text)
+    }
+
+    /// Appends plain text. Literal placeholder lookalikes are protected as well.
+    pub fn push_text(&mut self, text: &str) {
+        let mut last = 0;
+        for (start, end, _) in find_placeholders(text) {
+            self.append_text(&text[last..start]);
+            self.push_standalone(&text[start..end]);
+            last = end;
+        }
+        self.append_text(&text[last..]);
+    }
+
+    /// Appends text, protecting format placeholders like `{name}`, `{{count}}`,
+    /// `{ $var }` and `%1$s` the way message catalogs use them.
+    pub fn push_formatted(&mut self, text: &str) {
+        let mut last = 0;
+        let mut i = 0;
+        while let Some(ch) = text[i..].chars().next() {
+            let len = match ch {
+                '{' => balanced_braces(&text[i..]),
+                '%' => printf_len(&text[i..]),
+                _ => None,
+            };
+            match len {
+                Some(len) => {
+                    self.push_text(&text[last..i]);
+                    self.push_standalone(&text[i..i + len]);
+                    i += len;
+                    last = i;
+                }
+                None => i += ch.len_utf8(),
+            }
+        }
+        self.push_text(&text[last..]);
+    }
+
+    fn append_text(&mut self, text: &str) {
+        if self.leading.is_none() && !text.is_empty() {
+            self.leading = Some(self.spans.len());
+        }
+        self.text.push_str(text);
+    }
+
+    /// Appends markup that has no closing counterpart, like `<x/>` or `<br>`.
+    pub fn push_standalone(&mut self, raw: &str) {
+        self.push(raw, SpanKind::Standalone);
+    }
+
+    /// Appends an opening tag that will be closed by [`Protected::push_close`].
+    pub fn push_open(&mut self, raw: &str) {
+        self.open.push(self.pairs);
+        self.push(raw, SpanKind::Open(self.pairs));
+        self.pairs += 1;
+    }
+
+    /// Appends the closing tag of the last opened tag.
+    pub fn push_close(&mut self, raw: &str) {
+        match self.open.pop() {
+            Some(pair) => self.push(raw, SpanKind::Close(pair)),
+            None => self.push_standalone(raw),
+        }
+    }
+
+    fn push(&mut self, raw: &str, kind: SpanKind) {
+        self.text.push_str(&placeholder(self.spans.len()));
+        self.spans.push(Span {
+            raw: raw.to_owned(),
+            kind,
+        });
+    }
+
+    /// Puts the markup back into `translation`, escaping the text with `escape`.
+    ///
+    /// Returns `false` as second value if placeholders went missing, got duplicated or
+    /// would produce invalid nesting. In that case the translated text is kept and the
+    /// markup is placed around it in its original order.
+    pub fn restore(&self, translation: &str, escape: impl Fn(&str) -> String) -> (String, bool) {
+        if self.spans.is_empty() {
+            return (escape(translation), true);
+        }
+        let found = find_placeholders(translation)
+            .into_iter()
+            .filter(|v| v.2 < self.spans.len())
+            .collect::<Vec<_>>();
+
+        let mut seen = vec![false; self.spans.len()];
+        let mut stack = vec![];
+        let mut valid = found.len() == self.spans.len();
+        for (_, _, index) in &found {
+            if std::mem::replace(&mut seen[*index], true) {
+                valid = false;
+            }
+            match self.spans[*index].kind {
+                SpanKind::Standalone => {}
+                SpanKind::Open(pair) => stack.push(pair),
+                SpanKind::Close(pair) => {
+                    if stack.pop() != Some(pair) {
+                        valid = false;
+                    }
+                }
+            }
+        }
+        valid &= stack.is_empty();
+
+        let mut out = String::new();
+        let mut last = 0;
+        if valid {
+            for (start, end, index) in found {
+                out.push_str(&escape(&translation[last..start]));
+                out.push_str(&self.spans[index].raw);
+                last = end;
+            }
+            out.push_str(&escape(&translation[last..]));
+            return (out, true);
+        }
+
+        let mut text = String::new();
+        for (start, end, _) in found {
+            text.push_str(&translation[last..start]);
+            last = end;
+        }
+        text.push_str(&translation[last..]);
+        let leading = self.leading.unwrap_or(self.spans.len());
+        for span in &self.spans[..leading] {
+            out.push_str(&span.raw);
+        }
+        out.push_str(&escape(text.trim()));
+        for span in &self.spans[leading..] {
+            out.push_str(&span.raw);
+        }
+        (out, false)
+    }
+}
+
+fn placeholder(index: usize) -> String {
+    format!("{{{index}}}")
+}
+
+/// Finds `{n}` placeholders, tolerating whitespace that translators like to insert.
+fn find_placeholders(text: &str) -> Vec<(usize, usize, usize)> {
+    let mut found = vec![];
+    let mut rest = 0;
+    while let Some(start) = text[rest..].find('{').map(|v| v + rest) {
+        rest = start + 1;
+        let Some(end) = text[rest..].find('}').map(|v| v + rest) else {
+            break;
+        };
+        if let Ok(index) = text[start + 1..end].trim().parse::<usize>() {
+            found.push((start, end + 1, index));
+            rest = end + 1;
+        }
+    }
+    found
+}
+
+fn balanced_braces(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, ch) in text.char_indices() {
+        match ch {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Length of a printf style conversion like `%d`, `%1$s`, `%.2f` or `%@`.
+fn printf_len(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    if bytes.get(1) == Some(&b'%') {
+        return Some(2);
+    }
+    let digits = |mut i: usize| {
+        while bytes.get(i).is_some_and(|v| v.is_ascii_digit()) {
+            i += 1;
+        }
+        i
+    };
+    let mut i = digits(1);
+    if i > 1 && bytes.get(i) == Some(&b'$') {
+        i += 1;
+    } else {
+        i = 1;
+    }
+    while bytes
+        .get(i)
+        .is_some_and(|v| matches!(v, b'-' | b'+' | b'0' | b'#' | b'\''))
+    {
+        i += 1;
+    }
+    i = digits(i);
+    if bytes.get(i) == Some(&b'.') {
+        i = digits(i + 1);
+    }
+    for len in ["hh", "ll", "h", "l", "L", "z", "j", "t", "q"] {
+        if text[i..].starts_with(len) {
+            i += len.len();
+            break;
+        }
+    }
+    match bytes.get(i) {
+        Some(b'@' | b'd' | b'D' | b'i' | b'u' | b'U' | b'x' | b'X' | b'o' | b'O' | b'f' | b'F')
+        | Some(b'e' | b'E' | b'g' | b'G' | b'c' | b'C' | b's' | b'S' | b'p' | b'a' | b'A') => {
+            Some(i + 1)
+        }
+        _ => None,
+    }
+}
+
+/// Escapes text content for XML and HTML.
+pub fn escape_xml(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+/// Escapes an attribute value for XML and HTML.
+pub fn escape_xml_attr(text: &str) -> String {
+    escape_xml(text).replace('"', "&quot;")
+}
+
+/// Resolves the predefined XML entities and character references.
+pub fn unescape_xml(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';').filter(|v| *v <= 12) else {
+            out.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let entity = &rest[1..end];
+        let ch = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some('\u{a0}'),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .and_then(|v| u32::from_str_radix(v, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|v| v.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match ch {
+            Some(ch) => {
+                out.push(ch);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restore_reordered() {
+        let mut p = Protected::new();
+        p.push_text("Hello ");
+        p.push_open("<g id=\"1\">");
+        p.push_text("world");
+        p.push_close("</g>");
+        p.push_standalone("<x id=\"2\"/>");
+        assert_eq!(p.text(), "Hello {0}world{1}{2}");
+
+        let (out, valid) = p.restore("{2}Hallo { 0 }Welt{1} & mehr", escape_xml);
+        assert!(valid);
+        assert_eq!(out, "<x id=\"2\"/>Hallo <g id=\"1\">Welt</g> &amp; mehr");
+    }
+
+    #[test]
+    fn format_placeholders() {
+        let mut p = Protected::new();
+        p.push_formatted("Hi {name}, {{count}} of %1$s at 100% or %.2f%% { $var }");
+        assert_eq!(p.text(), "Hi {0}, {1} of {2} at 100% or {3}{4} {5}");
+        let (out, valid) = p.restore(p.text(), |v| v.to_owned());
+        assert!(valid);
+        assert_eq!(
+            out,
+            "Hi {name}, {{count}} of %1$s at 100% or %.2f%% { $var }"
+        );
+    }
+
+    #[test]
+    fn restore_broken_nesting() {
+        let mut p = Protected::new();
+        p.push_open("<b>");
+        p.push_text("bold");
+        p.push_close("</b>");
+        p.push_text(" text {7}");
+
+        let (out, valid) = p.restore("{1}fett{0} Text", escape_xml);
+        assert!(!valid);
+        assert_eq!(out, "<b>fett Text</b>{7}");
+    }
+}
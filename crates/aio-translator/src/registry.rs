@@ -0,0 +1,660 @@
+//! Builds translators and detectors from a TOML or JSON config.
+//!
+//! ```toml
+//! model_memory_mb = 8192
+//!
+//! [translators.ja-en]
+//! backend = "sugoi"
+//! cuda = true
+//! compute_type = "int8"
+//! model_dir = "/srv/models/sugoi"
+//! decode = { beam_size = 1, sampling_topk = 10, sampling_temperature = 0.8 }
+//! style_transfer = true
+//! cache = 10000
+//! fallback = ["deepl"]
+//!
+//! [translators.deepl]
+//! backend = "deepl"
+//! retry = { attempts = 3, delay_ms = 500 }
+//! env = { api_key = "MY_DEEPL_KEY" }
+//! http = { base_url = "https://api.deepl.com", timeout_ms = 10000 }
+//!
+//! [detectors.default]
+//! backend = "langid"
+//! ```
+//!
+//! Credentials are read from environment variables, `env` maps a credential to the variable
+//! that holds it. Without entry the variables of [`Credential::var`] are used.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context, anyhow, bail};
+use serde::Deserialize;
+
+#[cfg(feature = "ct2")]
+use crate::{BatchType, ComputeType, DecodeOptions, ModelFiles};
+use crate::{
+    CachedTranslator, Detector, FallbackTranslator, HttpOptions, LangIdDetector, ModelManager,
+    NoneTranslator, OriginalTranslator, RetryTranslator, StyleTransfer, Translator,
+};
+
+pub type DynTranslator = Box<dyn Translator + Send + Sync>;
+pub type DynDetector = Box<dyn Detector + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    Sugoi,
+    JParaCrawl,
+    M2M100,
+    MBart50,
+    Nllb,
+    Deepl,
+    Google,
+    MyMemory,
+    Baidu,
+    Caiyun,
+    Youdao,
+    Papago,
+    /// Returns empty strings
+    None,
+    /// Returns the input
+    Original,
+}
+
+impl Backend {
+    /// Name in configs, also the cargo feature that enables the backend.
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Sugoi => "sugoi",
+            Backend::JParaCrawl => "jparacrawl",
+            Backend::M2M100 => "m2m100",
+            Backend::MBart50 => "mbart50",
+            Backend::Nllb => "nllb",
+            Backend::Deepl => "deepl",
+            Backend::Google => "google",
+            Backend::MyMemory => "mymemory",
+            Backend::Baidu => "baidu",
+            Backend::Caiyun => "caiyun",
+            Backend::Youdao => "youdao",
+            Backend::Papago => "papago",
+            Backend::None => "none",
+            Backend::Original => "original",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Size {
+    Small,
+    Base,
+    Large,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Compute {
+    #[default]
+    Default,
+    Auto,
+    Float32,
+    Float16,
+    Int16,
+    Int8,
+    Int8Float16,
+}
+
+#[cfg(feature = "ct2")]
+impl From<Compute> for ComputeType {
+    fn from(value: Compute) -> Self {
+        match value {
+            Compute::Default => ComputeType::DEFAULT,
+            Compute::Auto => ComputeType::AUTO,
+            Compute::Float32 => ComputeType::FLOAT32,
+            Compute::Float16 => ComputeType::FLOAT16,
+            Compute::Int16 => ComputeType::INT16,
+            Compute::Int8 => ComputeType::INT8,
+            Compute::Int8Float16 => ComputeType::INT8_FLOAT16,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Credential {
+    ApiKey,
+    AppId,
+    Key,
+    Token,
+    RequestId,
+    AppKey,
+    AppSecret,
+}
+
+impl Credential {
+    /// Name of the credential in `env`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Credential::ApiKey => "api_key",
+            Credential::AppId => "app_id",
+            Credential::Key => "key",
+            Credential::Token => "token",
+            Credential::RequestId => "request_id",
+            Credential::AppKey => "app_key",
+            Credential::AppSecret => "app_secret",
+        }
+    }
+
+    /// Default environment variable of the credential for a backend.
+    pub fn var(self, backend: Backend) -> String {
+        let prefix = match backend {
+            Backend::Deepl => "DEEPL",
+            Backend::Google => "GOOGLE",
+            Backend::Baidu => "BAIDU",
+            Backend::Caiyun => "CAIYUN",
+            Backend::Youdao => "YOUDAO",
+            _ => "AIO_TRANSLATOR",
+        };
+        format!("{prefix}_{}", self.name().to_uppercase())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RetryConfig {
+    /// Requests including the first one
+    pub attempts: usize,
+    /// Delay before the first retry, doubled after every failure
+    #[serde(default)]
+    pub delay_ms: u64,
+}
+
+/// Connection settings of the online and scrape backends
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HttpConfig {
+    pub base_url: Option<String>,
+    pub proxy: Option<String>,
+    pub user_agent: Option<String>,
+    /// Time limit of a single request
+    pub timeout_ms: Option<u64>,
+}
+
+impl From<&HttpConfig> for HttpOptions {
+    fn from(config: &HttpConfig) -> Self {
+        let mut options = HttpOptions::new();
+        if let Some(base_url) = &config.base_url {
+            options = options.base_url(base_url);
+        }
+        if let Some(proxy) = &config.proxy {
+            options = options.proxy(proxy);
+        }
+        if let Some(user_agent) = &config.user_agent {
+            options = options.user_agent(user_agent);
+        }
+        if let Some(timeout) = config.timeout_ms {
+            options = options.timeout(Duration::from_millis(timeout));
+        }
+        options
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Batch {
+    Examples,
+    Tokens,
+}
+
+/// Decoding of the offline backends, unset fields keep the defaults of `DecodeOptions`
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DecodeConfig {
+    pub beam_size: Option<usize>,
+    pub length_penalty: Option<f32>,
+    pub repetition_penalty: Option<f32>,
+    pub max_decoding_length: Option<usize>,
+    pub sampling_topk: Option<usize>,
+    pub sampling_temperature: Option<f32>,
+    pub no_repeat_ngram_size: Option<usize>,
+    pub max_batch_size: Option<usize>,
+    pub batch_type: Option<Batch>,
+}
+
+#[cfg(feature = "ct2")]
+impl From<&DecodeConfig> for DecodeOptions {
+    fn from(config: &DecodeConfig) -> Self {
+        let default = DecodeOptions::default();
+        DecodeOptions {
+            beam_size: config.beam_size.unwrap_or(default.beam_size),
+            length_penalty: config.length_penalty.unwrap_or(default.length_penalty),
+            repetition_penalty: config
+                .repetition_penalty
+                .unwrap_or(default.repetition_penalty),
+            max_decoding_length: config
+                .max_decoding_length
+                .unwrap_or(default.max_decoding_length),
+            sampling_topk: config.sampling_topk.unwrap_or(default.sampling_topk),
+            sampling_temperature: config
+                .sampling_temperature
+                .unwrap_or(default.sampling_temperature),
+            no_repeat_ngram_size: config
+                .no_repeat_ngram_size
+                .unwrap_or(default.no_repeat_ngram_size),
+            max_batch_size: config.max_batch_size.unwrap_or(default.max_batch_size),
+            batch_type: match config.batch_type {
+                Some(Batch::Examples) => BatchType::Examples,
+                Some(Batch::Tokens) => BatchType::Tokens,
+                None => default.batch_type,
+            },
+            num_hypotheses: default.num_hypotheses,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TranslatorConfig {
+    pub backend: Backend,
+    /// Credential name to environment variable
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub cuda: bool,
+    #[serde(default)]
+    pub compute_type: Compute,
+    /// Model size of jparacrawl, m2m100 and nllb
+    pub size: Option<Size>,
+    #[serde(default)]
+    pub decode: DecodeConfig,
+    /// Directory with the model archives and files of an offline backend instead of downloads
+    pub model_dir: Option<PathBuf>,
+    /// Model key to the URL it is downloaded from instead
+    #[serde(default)]
+    pub mirrors: HashMap<String, String>,
+    /// Honorific speech for papago
+    #[serde(default)]
+    pub honorific: bool,
+    #[serde(default)]
+    pub style_transfer: bool,
+    /// Translations to remember
+    pub cache: Option<usize>,
+    pub retry: Option<RetryConfig>,
+    #[serde(default)]
+    pub http: HttpConfig,
+    /// Translators asked in order when this one fails
+    #[serde(default)]
+    pub fallback: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DetectorBackend {
+    LangId,
+    #[cfg(feature = "whatlang")]
+    WhatLang,
+    #[cfg(feature = "lingua")]
+    Lingua,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DetectorConfig {
+    pub backend: DetectorBackend,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Registry {
+    /// Memory the offline models may take together, the least recently used ones are unloaded
+    /// above it. Applied to the [`ModelManager::global`] when a translator is created.
+    pub model_memory_mb: Option<u64>,
+    #[serde(default)]
+    pub translators: HashMap<String, TranslatorConfig>,
+    #[serde(default)]
+    pub detectors: HashMap<String, DetectorConfig>,
+}
+
+impl Registry {
+    pub fn from_toml(config: &str) -> anyhow::Result<Self> {
+        Ok(toml::from_str(config)?)
+    }
+
+    pub fn from_json(config: &str) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(config)?)
+    }
+
+    /// Parses `.json` files as JSON and everything else as TOML.
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let config = std::fs::read_to_string(path)
+            .with_context(|| format!("could not read {}", path.display()))?;
+        let registry = match path.extension().is_some_and(|v| v == "json") {
+            true => Self::from_json(&config),
+            false => Self::from_toml(&config),
+        };
+        registry.with_context(|| format!("invalid config {}", path.display()))
+    }
+
+    pub fn translator_config(&self, name: &str) -> anyhow::Result<&TranslatorConfig> {
+        self.translators
+            .get(name)
+            .ok_or_else(|| anyhow!("unknown translator {name}"))
+    }
+
+    /// Creates a new instance of the named translator with its wrappers and fallbacks.
+    pub async fn translator(&self, name: &str) -> anyhow::Result<DynTranslator> {
+        if let Some(mb) = self.model_memory_mb {
+            ModelManager::global().set_budget(Some(mb * 1024 * 1024));
+        }
+        self.build(name, &mut vec![]).await
+    }
+
+    pub fn detector(&self, name: &str) -> anyhow::Result<DynDetector> {
+        let config = self
+            .detectors
+            .get(name)
+            .ok_or_else(|| anyhow!("unknown detector {name}"))?;
+        Ok(match config.backend {
+            DetectorBackend::LangId => Box::new(LangIdDetector::new()?),
+            #[cfg(feature = "whatlang")]
+            DetectorBackend::WhatLang => Box::new(crate::WhatLangDetector::new()),
+            #[cfg(feature = "lingua")]
+            DetectorBackend::Lingua => Box::new(crate::LinguaDetector::new()),
+        })
+    }
+
+    /// `path` holds the translators being built to reject cyclic fallbacks.
+    async fn build(&self, name: &str, path: &mut Vec<String>) -> anyhow::Result<DynTranslator> {
+        if path.iter().any(|v| v == name) {
+            bail!("cyclic fallback {} -> {name}", path.join(" -> "));
+        }
+        let config = self.translator_config(name)?;
+        let mut translator = config
+            .backend()
+            .await
+            .with_context(|| format!("could not create translator {name}"))?;
+        if config.style_transfer {
+            translator = Box::new(StyleTransfer::new(translator));
+        }
+        if let Some(retry) = &config.retry {
+            let delay = Duration::from_millis(retry.delay_ms);
+            translator = Box::new(RetryTranslator::new(translator, retry.attempts, delay));
+        }
+        if let Some(capacity) = config.cache {
+            translator = Box::new(CachedTranslator::new(translator, capacity));
+        }
+        if config.fallback.is_empty() {
+            return Ok(translator);
+        }
+        path.push(name.to_owned());
+        let mut translators = vec![translator];
+        for fallback in &config.fallback {
+            translators.push(Box::pin(self.build(fallback, path)).await?);
+        }
+        path.pop();
+        Ok(Box::new(FallbackTranslator::new(translators)))
+    }
+}
+
+impl TranslatorConfig {
+    #[cfg(any(
+        feature = "deepl",
+        feature = "google",
+        feature = "baidu",
+        feature = "caiyun",
+        feature = "youdao"
+    ))]
+    fn credential(&self, credential: Credential) -> anyhow::Result<String> {
+        let var = match self.env.get(credential.name()) {
+            Some(var) => var.clone(),
+            None => credential.var(self.backend),
+        };
+        std::env::var(&var).with_context(|| format!("{var} not set"))
+    }
+
+    #[cfg(feature = "ct2")]
+    fn compute_type(&self) -> ComputeType {
+        self.compute_type.into()
+    }
+
+    #[cfg(feature = "ct2")]
+    fn model_files(&self) -> ModelFiles {
+        let files = match &self.model_dir {
+            Some(dir) => ModelFiles::local(dir),
+            None => ModelFiles::new(),
+        };
+        self.mirrors
+            .iter()
+            .fold(files, |files, (key, url)| files.mirror(key, url))
+    }
+
+    #[cfg(feature = "ct2")]
+    fn decode_options(&self) -> DecodeOptions {
+        (&self.decode).into()
+    }
+
+    async fn backend(&self) -> anyhow::Result<DynTranslator> {
+        Ok(match self.backend {
+            #[cfg(feature = "sugoi")]
+            Backend::Sugoi => Box::new(
+                crate::SugoiTranslator::new(self.cuda, self.compute_type())
+                    .with_decode_options(self.decode_options())
+                    .with_model_files(self.model_files()),
+            ),
+            #[cfg(feature = "jparacrawl")]
+            Backend::JParaCrawl => {
+                use crate::JParaCrawlSize;
+                let size = match self.size.unwrap_or(Size::Base) {
+                    Size::Small => JParaCrawlSize::Small,
+                    Size::Base => JParaCrawlSize::Base,
+                    Size::Large => JParaCrawlSize::Large,
+                };
+                Box::new(
+                    crate::JParaCrawlTranslator::new(true, self.cuda, self.compute_type(), size)
+                        .with_decode_options(self.decode_options())
+                        .with_model_files(self.model_files()),
+                )
+            }
+            #[cfg(feature = "m2m100")]
+            Backend::M2M100 => {
+                use crate::M2M100Size;
+                let size = match self.size.unwrap_or(Size::Small) {
+                    Size::Small => M2M100Size::Small,
+                    Size::Large => M2M100Size::Large,
+                    Size::Base => bail!("m2m100 comes in small and large"),
+                };
+                Box::new(
+                    crate::M2M100Translator::new(self.cuda, self.compute_type(), size)
+                        .with_decode_options(self.decode_options())
+                        .with_model_files(self.model_files()),
+                )
+            }
+            #[cfg(feature = "mbart50")]
+            Backend::MBart50 => Box::new(
+                crate::MBart50Translator::new(self.cuda, self.compute_type())
+                    .with_decode_options(self.decode_options())
+                    .with_model_files(self.model_files()),
+            ),
+            #[cfg(feature = "nllb")]
+            Backend::Nllb => {
+                use crate::NLLBSize;
+                let size = match self.size.unwrap_or(Size::Small) {
+                    Size::Small => NLLBSize::SmallDistilled,
+                    Size::Base => NLLBSize::Base,
+                    Size::Large => NLLBSize::Large,
+                };
+                Box::new(
+                    crate::NLLBTranslator::new(self.cuda, self.compute_type(), size)
+                        .with_decode_options(self.decode_options())
+                        .with_model_files(self.model_files()),
+                )
+            }
+            #[cfg(feature = "deepl")]
+            Backend::Deepl => Box::new(crate::DeeplTranslator::with_options(
+                self.credential(Credential::ApiKey)?,
+                (&self.http).into(),
+            )?),
+            #[cfg(feature = "google")]
+            Backend::Google => Box::new(crate::GoogleTranslator::with_options(
+                self.credential(Credential::ApiKey)?,
+                (&self.http).into(),
+            )?),
+            #[cfg(feature = "mymemory")]
+            Backend::MyMemory => Box::new(crate::MyMemoryTranslator::with_options(
+                (&self.http).into(),
+            )?),
+            #[cfg(feature = "baidu")]
+            Backend::Baidu => Box::new(crate::BaiduTranslator::with_options(
+                &self.credential(Credential::AppId)?,
+                &self.credential(Credential::Key)?,
+                (&self.http).into(),
+            )?),
+            #[cfg(feature = "caiyun")]
+            Backend::Caiyun => Box::new(crate::CaiyunTranslator::with_options(
+                self.credential(Credential::Token)?,
+                self.credential(Credential::RequestId)
+                    .unwrap_or_else(|_| "demo".to_owned()),
+                (&self.http).into(),
+            )?),
+            #[cfg(feature = "youdao")]
+            Backend::Youdao => Box::new(crate::YoudaoTranslator::with_options(
+                self.credential(Credential::AppKey)?,
+                self.credential(Credential::AppSecret)?,
+                (&self.http).into(),
+            )?),
+            #[cfg(feature = "papago")]
+            Backend::Papago => Box::new(
+                crate::PapagoTranslator::with_options(self.honorific, (&self.http).into()).await?,
+            ),
+            Backend::None => Box::new(NoneTranslator::new()),
+            Backend::Original => Box::new(OriginalTranslator::new()),
+            #[allow(unreachable_patterns)]
+            backend => bail!(
+                "{0} is not enabled, build aio-translator with the {0} feature",
+                backend.name()
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Language, TranslatorTrait, translate_batch};
+
+    const CONFIG: &str = r#"
+        model_memory_mb = 8192
+
+        [translators.main]
+        backend = "none"
+        retry = { attempts = 2 }
+        fallback = ["echo"]
+
+        [translators.echo]
+        backend = "original"
+        style_transfer = true
+        cache = 100
+        decode = { beam_size = 1, sampling_temperature = 0.5, max_batch_size = 4096, batch_type = "tokens" }
+
+        [translators.deepl]
+        backend = "deepl"
+        env = { api_key = "AIO_TRANSLATOR_TEST_MISSING_KEY" }
+        http = { base_url = "http://127.0.0.1:1", timeout_ms = 100 }
+
+        [translators.cycle]
+        backend = "original"
+        fallback = ["back"]
+
+        [translators.back]
+        backend = "original"
+        fallback = ["cycle"]
+
+        [detectors.default]
+        backend = "langid"
+    "#;
+
+    #[test]
+    fn parse() {
+        let registry = Registry::from_toml(CONFIG).unwrap();
+        assert_eq!(registry.model_memory_mb, Some(8192));
+        let main = registry.translator_config("main").unwrap();
+        assert_eq!(main.backend, Backend::None);
+        assert_eq!(main.fallback, ["echo"]);
+        assert_eq!(main.compute_type, Compute::Default);
+        assert_eq!(main.http, HttpConfig::default());
+        assert_eq!(main.decode, DecodeConfig::default());
+        let echo = registry.translator_config("echo").unwrap();
+        assert_eq!(echo.decode.beam_size, Some(1));
+        assert_eq!(echo.decode.sampling_temperature, Some(0.5));
+        assert_eq!(echo.decode.max_batch_size, Some(4096));
+        assert_eq!(echo.decode.batch_type, Some(Batch::Tokens));
+        assert!(
+            Registry::from_toml("[translators.x]\nbackend = \"nllb\"\ndecode = { beam = 1 }")
+                .is_err()
+        );
+        let deepl = registry.translator_config("deepl").unwrap();
+        assert_eq!(deepl.http.base_url.as_deref(), Some("http://127.0.0.1:1"));
+        assert_eq!(deepl.http.timeout_ms, Some(100));
+
+        let json = Registry::from_json(
+            r#"{ "translators": { "nllb": { "backend": "nllb", "size": "large", "cuda": true, "compute_type": "int8_float16", "model_dir": "/srv/models", "mirrors": { "spm": "https://mirror.internal/spm.model" } } } }"#,
+        )
+        .unwrap();
+        let nllb = json.translator_config("nllb").unwrap();
+        assert_eq!(nllb.size, Some(Size::Large));
+        assert_eq!(nllb.compute_type, Compute::Int8Float16);
+        assert!(nllb.cuda);
+        assert_eq!(nllb.model_dir, Some(PathBuf::from("/srv/models")));
+        assert_eq!(nllb.mirrors["spm"], "https://mirror.internal/spm.model");
+        assert!(main.mirrors.is_empty());
+
+        assert!(Registry::from_toml("[translators.x]\nbackend = \"klingon\"").is_err());
+        assert!(Registry::from_toml("[translators.x]\nbackend = \"none\"\ncolor = 1").is_err());
+        assert_eq!(Credential::ApiKey.var(Backend::Deepl), "DEEPL_API_KEY");
+    }
+
+    #[tokio::test]
+    async fn build() {
+        let registry = Registry::from_toml(CONFIG).unwrap();
+        let mut main = registry.translator("main").await.unwrap();
+        assert!(matches!(main.translator(), TranslatorTrait::Blocking(_)));
+        let query = ["Hello,world".to_owned()];
+        let out = translate_batch(
+            &mut main,
+            &query,
+            Some(Language::English),
+            &Language::German,
+        )
+        .await
+        .unwrap();
+        // none answers with empty texts, so the fallback is not asked
+        assert_eq!(out, [""]);
+        let mut echo = registry.translator("echo").await.unwrap();
+        let out = translate_batch(
+            &mut echo,
+            &query,
+            Some(Language::English),
+            &Language::German,
+        )
+        .await
+        .unwrap();
+        assert_eq!(out, ["Hello, world"]);
+
+        let e = registry.translator("deepl").await.err().unwrap();
+        assert_eq!(e.to_string(), "could not create translator deepl");
+        let cause = match cfg!(feature = "deepl") {
+            true => "AIO_TRANSLATOR_TEST_MISSING_KEY not set",
+            false => "deepl is not enabled, build aio-translator with the deepl feature",
+        };
+        assert!(format!("{e:#}").contains(cause), "{e:#}");
+        let e = registry.translator("cycle").await.err().unwrap();
+        assert_eq!(e.to_string(), "cyclic fallback cycle -> back -> cycle");
+        let e = registry.translator("missing").await.err().unwrap();
+        assert_eq!(e.to_string(), "unknown translator missing");
+    }
+}
@@ -0,0 +1,437 @@
+//! Translation of app localization files.
+//!
+//! Only values are translated. Keys, ordering and comments are kept by splicing the
+//! translations into the source file, which serves as template for the target locale.
+
+mod android;
+mod apple;
+mod fluent;
+mod json;
+mod yaml;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use aio_translator_interface::{Language, Translator, error::Error};
+use anyhow::bail;
+
+use crate::{batch::translate_batch, markup::Protected};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceFormat {
+    /// Nested JSON objects, as used by i18next, vue-i18n, ngx-translate, ...
+    Json,
+    /// Nested YAML mappings, as used by rails-i18n and friends
+    Yaml,
+    /// Project Fluent `.ftl`
+    Fluent,
+    /// Android `res/values/strings.xml`
+    AndroidStrings,
+    /// Apple `.strings`
+    AppleStrings,
+    /// Apple string catalog `.xcstrings`, which holds every locale in one file
+    XcStrings,
+}
+
+impl ResourceFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        Some(match path.extension()?.to_str()? {
+            "json" => Self::Json,
+            "yaml" | "yml" => Self::Yaml,
+            "ftl" => Self::Fluent,
+            "xml" => Self::AndroidStrings,
+            "strings" => Self::AppleStrings,
+            "xcstrings" => Self::XcStrings,
+            _ => return None,
+        })
+    }
+}
+
+/// How a value is written back into the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Codec {
+    Json,
+    YamlPlain,
+    YamlQuoted,
+    Fluent,
+    Android,
+    AppleStrings,
+    Plain,
+}
+
+/// A translatable value.
+#[derive(Debug)]
+pub struct Entry {
+    /// Key path, nested keys are joined with `.`
+    pub key: String,
+    text: Protected,
+    span: Range<usize>,
+    codec: Codec,
+    /// location inside a string catalog
+    path: Vec<String>,
+}
+
+impl Entry {
+    fn new(key: String, text: &str, span: Range<usize>, codec: Codec) -> Self {
+        let mut protected = Protected::new();
+        protected.push_formatted(text);
+        Self::protected(key, protected, span, codec)
+    }
+
+    fn protected(key: String, text: Protected, span: Range<usize>, codec: Codec) -> Self {
+        Self {
+            key,
+            text,
+            span,
+            codec,
+            path: vec![],
+        }
+    }
+
+    /// Source text with placeholders protected, as sent to the translator.
+    pub fn text(&self) -> &str {
+        self.text.text()
+    }
+
+    fn encode(&self, translation: &str) -> (String, bool) {
+        if self.codec == Codec::Android {
+            return self.text.restore(translation, android::escape);
+        }
+        let (text, valid) = self.text.restore(translation, |v| v.to_owned());
+        let text = match self.codec {
+            Codec::Json | Codec::YamlQuoted => json::encode(&text),
+            Codec::YamlPlain => yaml::encode_plain(&text),
+            Codec::Fluent => fluent::encode(&text),
+            Codec::AppleStrings => apple::encode(&text),
+            Codec::Android | Codec::Plain => text,
+        };
+        (text, valid)
+    }
+}
+
+/// Translated value of an entry, already encoded for the file.
+struct Rendered {
+    raw: String,
+    review: bool,
+}
+
+/// Source values of the last run, used by the incremental mode to find changed keys.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResourceLock {
+    sources: BTreeMap<String, String>,
+}
+
+impl ResourceLock {
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let sources = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        Ok(Self { sources })
+    }
+
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(&self.sources)?)?;
+        Ok(())
+    }
+
+    /// Lock file used for `target`: `<target>.<lang>.lock`.
+    pub fn path_for(target: &Path, to: &Language) -> PathBuf {
+        let mut name = target.as_os_str().to_owned();
+        name.push(format!(".{}.lock", to.to_tag().unwrap_or("target")));
+        PathBuf::from(name)
+    }
+
+    fn changed(&self, entry: &Entry) -> bool {
+        // keys without a record are adopted as they are
+        self.sources
+            .get(&entry.key)
+            .is_some_and(|v| v != entry.text())
+    }
+}
+
+/// Counts of what [`ResourceFile::translate`] did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResourceReport {
+    pub translated: usize,
+    /// Translations that lost placeholders
+    pub needs_review: usize,
+    /// Existing translations that were kept by the incremental mode
+    pub reused: usize,
+    /// Values without translatable text
+    pub skipped: usize,
+}
+
+pub struct ResourceOutput {
+    /// Content of the target locale file
+    pub text: String,
+    pub lock: ResourceLock,
+    pub report: ResourceReport,
+}
+
+pub struct ResourceFile {
+    format: ResourceFormat,
+    raw: String,
+    entries: Vec<Entry>,
+    /// root key that names the locale, like `en:` in rails yaml files
+    locale: Option<Range<usize>>,
+    /// elements that must not show up in translations
+    removed: Vec<Range<usize>>,
+    catalog: Option<serde_json::Value>,
+}
+
+impl ResourceFile {
+    pub fn parse(format: ResourceFormat, raw: impl Into<String>) -> anyhow::Result<Self> {
+        let raw = raw.into();
+        let mut file = Self {
+            format,
+            raw: String::new(),
+            entries: vec![],
+            locale: None,
+            removed: vec![],
+            catalog: None,
+        };
+        match format {
+            ResourceFormat::Json => file.entries = json::parse(&raw)?,
+            ResourceFormat::Yaml => (file.entries, file.locale) = yaml::parse(&raw)?,
+            ResourceFormat::Fluent => file.entries = fluent::parse(&raw),
+            ResourceFormat::AndroidStrings => (file.entries, file.removed) = android::parse(&raw)?,
+            ResourceFormat::AppleStrings => file.entries = apple::parse(&raw)?,
+            ResourceFormat::XcStrings => {
+                let catalog = serde_json::from_str(&raw)?;
+                file.entries = apple::catalog_entries(&catalog)?;
+                file.catalog = Some(catalog);
+            }
+        }
+        file.raw = raw;
+        Ok(file)
+    }
+
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let Some(format) = ResourceFormat::from_path(path) else {
+            bail!("unknown resource format {}", path.display());
+        };
+        Self::parse(format, std::fs::read_to_string(path)?)
+    }
+
+    pub fn format(&self) -> ResourceFormat {
+        self.format
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Language declared by the file itself, if the format has a place for it.
+    pub fn source_language(&self) -> Option<Language> {
+        match (&self.locale, &self.catalog) {
+            (Some(locale), _) => {
+                Language::from_tag(self.raw[locale.clone()].trim_matches(['"', '\'']))
+            }
+            (_, Some(catalog)) => Language::from_tag(catalog["sourceLanguage"].as_str()?),
+            _ => None,
+        }
+    }
+
+    /// Translates the file into `to`.
+    ///
+    /// With `previous`, the existing translation of the target locale, values that are
+    /// present there and whose source didn't change according to `lock` are kept. For
+    /// string catalogs `previous` is the catalog itself.
+    pub async fn translate<T: Translator + ?Sized>(
+        &self,
+        translator: &mut T,
+        from: Option<Language>,
+        to: Language,
+        previous: Option<&ResourceFile>,
+        lock: Option<&ResourceLock>,
+    ) -> anyhow::Result<ResourceOutput> {
+        let from = from.or_else(|| self.source_language());
+        let existing = previous
+            .map(|v| v.existing(&to))
+            .transpose()?
+            .unwrap_or_default();
+
+        let mut report = ResourceReport::default();
+        let mut values = HashMap::new();
+        let mut pending = vec![];
+        for entry in &self.entries {
+            if entry.text.is_blank() {
+                report.skipped += 1;
+                continue;
+            }
+            match existing.get(&entry.key) {
+                Some(raw) if !lock.is_some_and(|v| v.changed(entry)) => {
+                    report.reused += 1;
+                    values.insert(
+                        entry.key.clone(),
+                        Rendered {
+                            raw: raw.clone(),
+                            review: false,
+                        },
+                    );
+                }
+                _ => pending.push(entry),
+            }
+        }
+
+        let query = pending
+            .iter()
+            .map(|v| v.text().to_owned())
+            .collect::<Vec<_>>();
+        let translated = translate_batch(translator, &query, from, &to).await?;
+        for (entry, translation) in pending.into_iter().zip(translated) {
+            let (raw, valid) = entry.encode(&translation);
+            match valid {
+                true => report.translated += 1,
+                false => report.needs_review += 1,
+            }
+            values.insert(
+                entry.key.clone(),
+                Rendered {
+                    raw,
+                    review: !valid,
+                },
+            );
+        }
+
+        let lock = ResourceLock {
+            sources: self
+                .entries
+                .iter()
+                .filter(|v| values.contains_key(&v.key))
+                .map(|v| (v.key.clone(), v.text().to_owned()))
+                .collect(),
+        };
+        Ok(ResourceOutput {
+            text: self.render(&values, &to)?,
+            lock,
+            report,
+        })
+    }
+
+    /// Raw values of the target locale keyed by entry key.
+    fn existing(&self, to: &Language) -> anyhow::Result<HashMap<String, String>> {
+        if let Some(catalog) = &self.catalog {
+            let tag = to.to_tag().ok_or(Error::UnknownLanguage(*to))?;
+            return Ok(apple::catalog_values(catalog, &self.entries, tag));
+        }
+        Ok(self
+            .entries
+            .iter()
+            .map(|v| (v.key.clone(), self.raw[v.span.clone()].to_owned()))
+            .collect())
+    }
+
+    fn render(&self, values: &HashMap<String, Rendered>, to: &Language) -> anyhow::Result<String> {
+        if let Some(catalog) = &self.catalog {
+            let tag = to.to_tag().ok_or(Error::UnknownLanguage(*to))?;
+            let mut catalog = catalog.clone();
+            for entry in &self.entries {
+                if let Some(value) = values.get(&entry.key) {
+                    apple::set_catalog_value(
+                        &mut catalog,
+                        &entry.path,
+                        tag,
+                        &value.raw,
+                        value.review,
+                    );
+                }
+            }
+            return Ok(apple::to_xcode_json(&catalog));
+        }
+
+        let mut edits = self
+            .entries
+            .iter()
+            .filter_map(|v| Some((v.span.clone(), values.get(&v.key)?.raw.clone())))
+            .chain(self.removed.iter().map(|v| (v.clone(), String::new())))
+            .collect::<Vec<_>>();
+        if let (Some(locale), Some(tag)) = (&self.locale, to.to_tag()) {
+            edits.push((locale.clone(), tag.to_owned()));
+        }
+        edits.sort_by_key(|v| v.0.start);
+
+        let mut out = String::with_capacity(self.raw.len());
+        let mut last = 0;
+        for (range, text) in edits {
+            out.push_str(&self.raw[last..range.start]);
+            out.push_str(&text);
+            last = range.end;
+        }
+        out.push_str(&self.raw[last..]);
+        Ok(out)
+    }
+}
+
+/// Translates `source` into the locale file `target`.
+///
+/// In incremental mode the existing `target` and the lock file next to it are used to
+/// translate only keys that are missing or whose source changed since the last run.
+pub async fn translate_resource_file<T: Translator + ?Sized>(
+    translator: &mut T,
+    source: &Path,
+    target: &Path,
+    from: Option<Language>,
+    to: Language,
+    incremental: bool,
+) -> anyhow::Result<ResourceReport> {
+    let file = ResourceFile::read(source)?;
+    let lock_path = ResourceLock::path_for(target, &to);
+    let previous = match incremental && target.exists() {
+        true => Some(ResourceFile::read(target)?),
+        false => None,
+    };
+    let lock = match incremental && lock_path.exists() {
+        true => Some(ResourceLock::read(&lock_path)?),
+        false => None,
+    };
+    let output = file
+        .translate(translator, from, to, previous.as_ref(), lock.as_ref())
+        .await?;
+    std::fs::write(target, output.text)?;
+    if incremental {
+        output.lock.write(&lock_path)?;
+    }
+    Ok(output.report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OriginalTranslator;
+
+    #[tokio::test]
+    async fn incremental() {
+        let source = ResourceFile::parse(
+            ResourceFormat::Json,
+            r#"{"a": "Hello", "b": {"c": "World {name}"}, "n": 1}"#,
+        )
+        .unwrap();
+        let previous = ResourceFile::parse(
+            ResourceFormat::Json,
+            r#"{"a": "Hallo", "b": {"c": "Welt {name}"}}"#,
+        )
+        .unwrap();
+        let lock = ResourceLock {
+            sources: [("b.c".to_owned(), "Old {0}".to_owned())].into(),
+        };
+        let out = source
+            .translate(
+                &mut OriginalTranslator::new(),
+                Some(Language::English),
+                Language::German,
+                Some(&previous),
+                Some(&lock),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            out.text,
+            r#"{"a": "Hallo", "b": {"c": "World {name}"}, "n": 1}"#
+        );
+        assert_eq!(out.report.reused, 1);
+        assert_eq!(out.report.translated, 1);
+        assert_eq!(out.lock.sources["b.c"], "World {0}");
+    }
+}
@@ -0,0 +1,181 @@
+//! Android `strings.xml` with `<string>`, `<string-array>` and `<plurals>` resources.
+
+use std::ops::Range;
+
+use super::{Codec, Entry};
+use crate::{
+    markup::{Protected, escape_xml, unescape_xml},
+    xml::{Node, Token, element_end, element_name, local_name, tokenize},
+};
+
+pub(super) fn parse(raw: &str) -> anyhow::Result<(Vec<Entry>, Vec<Range<usize>>)> {
+    let tokens = tokenize(raw)?;
+    let mut entries = vec![];
+    let mut removed = vec![];
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = &tokens[i];
+        let (Some(kind @ ("string" | "string-array" | "plurals")), Some(name)) =
+            (element_name(token), token.attr("name"))
+        else {
+            i += 1;
+            continue;
+        };
+        let end = element_end(&tokens, i);
+        if token
+            .attr("translatable")
+            .is_some_and(|v| v.value == "false")
+        {
+            removed.push(line_range(raw, token.range.start..tokens[end].range.end));
+            i = end + 1;
+            continue;
+        }
+        match kind {
+            "string" => entries.push(entry(raw, &tokens, i, end, name.value.clone())),
+            _ => {
+                let mut j = i + 1;
+                let mut index = 0;
+                while j < end {
+                    if element_name(&tokens[j]) != Some("item") {
+                        j += 1;
+                        continue;
+                    }
+                    let item_end = element_end(&tokens, j);
+                    let key = match tokens[j].attr("quantity") {
+                        Some(quantity) => format!("{}:{}", name.value, quantity.value),
+                        None => format!("{}[{index}]", name.value),
+                    };
+                    entries.push(entry(raw, &tokens, j, item_end, key));
+                    index += 1;
+                    j = item_end + 1;
+                }
+            }
+        }
+        i = end + 1;
+    }
+    Ok((entries, removed))
+}
+
+/// Extends `range` to whole lines when nothing else is on them.
+fn line_range(raw: &str, range: Range<usize>) -> Range<usize> {
+    let before = raw[..range.start].rfind('\n').map(|v| v + 1).unwrap_or(0);
+    let after = raw[range.end..]
+        .find('\n')
+        .map(|v| range.end + v + 1)
+        .unwrap_or(raw.len());
+    match raw[before..range.start].trim().is_empty() && raw[range.end..after].trim().is_empty() {
+        true => before..after,
+        false => range,
+    }
+}
+
+fn entry(raw: &str, tokens: &[Token], start: usize, end: usize, key: String) -> Entry {
+    if start == end {
+        // `<string name="empty"/>`
+        let at = tokens[start].range.end;
+        return Entry::protected(key, Protected::new(), at..at, Codec::Android);
+    }
+    let mut protected = Protected::new();
+    let mut i = start + 1;
+    while i < end {
+        let token = &tokens[i];
+        let text = &raw[token.range.clone()];
+        match &token.node {
+            Node::Text => protected.push_formatted(&unescape(&unescape_xml(text))),
+            Node::CData => protected.push_formatted(&text[9..text.len() - 3]),
+            Node::Start {
+                name, empty: false, ..
+            } if local_name(name) == "g" => {
+                // `<xliff:g>` marks content that must not be translated
+                let g_end = element_end(tokens, i);
+                protected.push_standalone(&raw[token.range.start..tokens[g_end].range.end]);
+                i = g_end;
+            }
+            Node::Start { empty: true, .. } | Node::Other => protected.push_standalone(text),
+            Node::Start { .. } => protected.push_open(text),
+            Node::End { .. } => protected.push_close(text),
+        }
+        i += 1;
+    }
+    let span = tokens[start].range.end..tokens[end].range.start;
+    Entry::protected(key, protected, span, Codec::Android)
+}
+
+/// Resolves the backslash escapes of Android resources and drops unescaped quotes.
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('u') => {
+                    let code = chars.by_ref().take(4).collect::<String>();
+                    if let Some(ch) = u32::from_str_radix(&code, 16).ok().and_then(char::from_u32) {
+                        out.push(ch);
+                    }
+                }
+                Some(ch) => out.push(ch),
+                None => {}
+            },
+            '"' => {}
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+/// Escapes text for the content of a string resource.
+pub(super) fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    if text.starts_with(['@', '?']) {
+        out.push('\\');
+    }
+    for ch in escape_xml(text).chars() {
+        match ch {
+            '\\' => out.push_str("\\\\"),
+            '\'' => out.push_str("\\'"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resources() {
+        let raw = r#"<resources xmlns:xliff="urn:oasis:names:tc:xliff:document:1.2">
+    <string name="app_name" translatable="false">Demo</string>
+    <string name="hello">Hello <b>%1$s</b>, it\'s <xliff:g id="n">%2$d</xliff:g> o\'clock</string>
+    <string-array name="days">
+        <item>Monday</item>
+        <item>Tuesday</item>
+    </string-array>
+    <plurals name="songs">
+        <item quantity="one">%d song</item>
+        <item quantity="other">%d songs</item>
+    </plurals>
+</resources>"#;
+        let (entries, removed) = parse(raw).unwrap();
+        let keys = entries.iter().map(|v| v.key.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            ["hello", "days[0]", "days[1]", "songs:one", "songs:other"]
+        );
+        assert_eq!(entries[0].text(), "Hello {0}{1}{2}, it's {3} o'clock");
+        assert!(raw[removed[0].clone()].contains("app_name"));
+        let (text, valid) = entries[0].encode("Hallo {0}{1}{2}, es ist {3} Uhr & \"so\"");
+        assert!(valid);
+        assert_eq!(
+            text,
+            r#"Hallo <b>%1$s</b>, es ist <xliff:g id="n">%2$d</xliff:g> Uhr &amp; \"so\""#
+        );
+    }
+}
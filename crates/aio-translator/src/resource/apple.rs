@@ -0,0 +1,292 @@
+//! Apple `.strings` files and `.xcstrings` string catalogs.
+
+use std::collections::HashMap;
+
+use anyhow::bail;
+use serde_json::{Map, Value};
+
+use super::{Codec, Entry, json};
+
+pub(super) fn parse(raw: &str) -> anyhow::Result<Vec<Entry>> {
+    let mut entries = vec![];
+    let mut i = 0;
+    loop {
+        i = skip_trivia(raw, i)?;
+        if i >= raw.len() {
+            return Ok(entries);
+        }
+        let (key, end) = match raw[i..].starts_with('"') {
+            true => quoted(raw, i)?,
+            false => {
+                let len = raw[i..]
+                    .find(|v: char| v.is_whitespace() || v == '=')
+                    .unwrap_or(raw.len() - i);
+                (raw[i..i + len].to_owned(), i + len)
+            }
+        };
+        i = skip_trivia(raw, end)?;
+        if !raw[i..].starts_with('=') {
+            bail!("expected `=` at byte {i}");
+        }
+        i = skip_trivia(raw, i + 1)?;
+        if !raw[i..].starts_with('"') {
+            bail!("expected string at byte {i}");
+        }
+        let (value, end) = quoted(raw, i)?;
+        entries.push(Entry::new(key, &value, i..end, Codec::AppleStrings));
+        i = skip_trivia(raw, end)?;
+        if !raw[i..].starts_with(';') {
+            bail!("expected `;` at byte {i}");
+        }
+        i += 1;
+    }
+}
+
+fn skip_trivia(raw: &str, mut i: usize) -> anyhow::Result<usize> {
+    loop {
+        let rest = &raw[i..];
+        let trimmed = rest.trim_start();
+        i += rest.len() - trimmed.len();
+        if trimmed.starts_with("/*") {
+            match trimmed.find("*/") {
+                Some(end) => i += end + 2,
+                None => bail!("unterminated comment at byte {i}"),
+            }
+        } else if trimmed.starts_with("//") {
+            i += trimmed.find('\n').unwrap_or(trimmed.len());
+        } else {
+            return Ok(i);
+        }
+    }
+}
+
+/// Reads the string literal starting at `start`, returning its value and end.
+fn quoted(raw: &str, start: usize) -> anyhow::Result<(String, usize)> {
+    let mut out = String::new();
+    let mut chars = raw[start + 1..].char_indices();
+    while let Some((i, ch)) = chars.next() {
+        match ch {
+            '"' => return Ok((out, start + 1 + i + 1)),
+            '\\' => match chars.next().map(|v| v.1) {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('r') => out.push('\r'),
+                Some('U' | 'u') => {
+                    let code = chars.by_ref().take(4).map(|v| v.1).collect::<String>();
+                    if let Some(ch) = u32::from_str_radix(&code, 16).ok().and_then(char::from_u32) {
+                        out.push(ch);
+                    }
+                }
+                Some(ch) => out.push(ch),
+                None => {}
+            },
+            _ => out.push(ch),
+        }
+    }
+    bail!("unterminated string at byte {start}")
+}
+
+pub(super) fn encode(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for ch in text.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            _ => out.push(ch),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Entries of the source language of a string catalog.
+///
+/// Plural variations get the key `key:category`.
+pub(super) fn catalog_entries(catalog: &Value) -> anyhow::Result<Vec<Entry>> {
+    let Some(source) = catalog["sourceLanguage"].as_str() else {
+        bail!("string catalog without sourceLanguage");
+    };
+    let Some(strings) = catalog["strings"].as_object() else {
+        bail!("string catalog without strings");
+    };
+    let mut entries = vec![];
+    for (key, value) in strings {
+        if value["shouldTranslate"] == false {
+            continue;
+        }
+        let localization = &value["localizations"][source];
+        match localization["variations"]["plural"].as_object() {
+            Some(plural) => {
+                for (category, variation) in plural {
+                    let Some(text) = variation["stringUnit"]["value"].as_str() else {
+                        continue;
+                    };
+                    let mut entry =
+                        Entry::new(format!("{key}:{category}"), text, 0..0, Codec::Plain);
+                    entry.path = vec![key.clone(), "plural".to_owned(), category.clone()];
+                    entries.push(entry);
+                }
+            }
+            None => {
+                // without a localization the key itself is the source text
+                let text = localization["stringUnit"]["value"].as_str().unwrap_or(key);
+                let mut entry = Entry::new(key.clone(), text, 0..0, Codec::Plain);
+                entry.path = vec![key.clone()];
+                entries.push(entry);
+            }
+        }
+    }
+    Ok(entries)
+}
+
+fn unit<'a>(catalog: &'a Value, path: &[String], tag: &str) -> &'a Value {
+    let localization = &catalog["strings"][&path[0]]["localizations"][tag];
+    match path {
+        [_, kind, category] => &localization["variations"][kind][category]["stringUnit"],
+        _ => &localization["stringUnit"],
+    }
+}
+
+/// Existing values of the locale `tag` keyed by entry key.
+pub(super) fn catalog_values(
+    catalog: &Value,
+    entries: &[Entry],
+    tag: &str,
+) -> HashMap<String, String> {
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let value = unit(catalog, &entry.path, tag)["value"].as_str()?;
+            Some((entry.key.clone(), value.to_owned()))
+        })
+        .collect()
+}
+
+fn object<'a>(value: &'a mut Value, key: &str) -> &'a mut Value {
+    if !value.is_object() {
+        *value = Value::Object(Map::new());
+    }
+    value
+        .as_object_mut()
+        .expect("just made an object")
+        .entry(key)
+        .or_insert_with(|| Value::Object(Map::new()))
+}
+
+pub(super) fn set_catalog_value(
+    catalog: &mut Value,
+    path: &[String],
+    tag: &str,
+    value: &str,
+    review: bool,
+) {
+    let strings = object(catalog, "strings");
+    let localization = object(object(object(strings, &path[0]), "localizations"), tag);
+    let target = match path {
+        [_, kind, category] => object(object(object(localization, "variations"), kind), category),
+        _ => localization,
+    };
+    let state = match review {
+        true => "needs_review",
+        false => "translated",
+    };
+    *object(target, "stringUnit") = serde_json::json!({ "state": state, "value": value });
+}
+
+/// Serializes like Xcode does: two space indent and `" : "` between key and value.
+pub(super) fn to_xcode_json(value: &Value) -> String {
+    let mut out = String::new();
+    write_value(&mut out, value, 0);
+    out
+}
+
+fn write_value(out: &mut String, value: &Value, depth: usize) {
+    let indent = "  ".repeat(depth + 1);
+    match value {
+        Value::Object(map) if map.is_empty() => {
+            out.push_str("{\n\n");
+            out.push_str(&indent[2..]);
+            out.push('}');
+        }
+        Value::Object(map) => {
+            out.push_str("{\n");
+            for (i, (key, value)) in map.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(",\n");
+                }
+                out.push_str(&indent);
+                out.push_str(&json::encode(key));
+                out.push_str(" : ");
+                write_value(out, value, depth + 1);
+            }
+            out.push('\n');
+            out.push_str(&indent[2..]);
+            out.push('}');
+        }
+        Value::Array(list) if list.is_empty() => {
+            out.push_str("[\n\n");
+            out.push_str(&indent[2..]);
+            out.push(']');
+        }
+        Value::Array(list) => {
+            out.push_str("[\n");
+            for (i, value) in list.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(",\n");
+                }
+                out.push_str(&indent);
+                write_value(out, value, depth + 1);
+            }
+            out.push('\n');
+            out.push_str(&indent[2..]);
+            out.push(']');
+        }
+        _ => out.push_str(&value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strings() {
+        let raw =
+            "/* Greeting */\n\"hello\" = \"Hello \\\"%@\\\"\";\n// bare key\nbye = \"Bye\";\n";
+        let entries = parse(raw).unwrap();
+        let keys = entries.iter().map(|v| v.key.as_str()).collect::<Vec<_>>();
+        assert_eq!(keys, ["hello", "bye"]);
+        assert_eq!(entries[0].text(), "Hello \"{0}\"");
+        assert_eq!(entries[0].encode("Hallo \"{0}\"").0, "\"Hallo \\\"%@\\\"\"");
+    }
+
+    #[test]
+    fn catalog() {
+        let mut catalog = serde_json::json!({
+            "sourceLanguage": "en",
+            "strings": {
+                "Done": {},
+                "internal": { "shouldTranslate": false },
+                "%lld files": {
+                    "localizations": { "en": { "variations": { "plural": {
+                        "one": { "stringUnit": { "state": "translated", "value": "%lld file" } },
+                        "other": { "stringUnit": { "state": "translated", "value": "%lld files" } }
+                    } } } }
+                }
+            },
+            "version": "1.0"
+        });
+        let entries = catalog_entries(&catalog).unwrap();
+        let keys = entries.iter().map(|v| v.key.as_str()).collect::<Vec<_>>();
+        assert_eq!(keys, ["Done", "%lld files:one", "%lld files:other"]);
+        set_catalog_value(&mut catalog, &entries[1].path, "de", "%lld Datei", false);
+        let values = catalog_values(&catalog, &entries, "de");
+        assert_eq!(values.len(), 1);
+        assert_eq!(values["%lld files:one"], "%lld Datei");
+        assert!(to_xcode_json(&catalog).contains("\"Done\" : {\n\n    }"));
+    }
+}
@@ -0,0 +1,134 @@
+//! Project Fluent messages, terms and attributes.
+//!
+//! Placeables are protected as a whole, so the variants of select expressions stay in the
+//! source language.
+
+use super::{Codec, Entry};
+
+struct Pending {
+    key: String,
+    start: usize,
+    end: usize,
+    lines: Vec<String>,
+}
+
+impl Pending {
+    fn finish(self, entries: &mut Vec<Entry>) {
+        let text = self.lines.join("\n");
+        entries.push(Entry::new(
+            self.key,
+            &text,
+            self.start..self.end,
+            Codec::Fluent,
+        ));
+    }
+}
+
+pub(super) fn parse(raw: &str) -> Vec<Entry> {
+    let mut entries = vec![];
+    let mut message: Option<String> = None;
+    let mut current: Option<Pending> = None;
+    let mut offset = 0;
+    for line in raw.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        let content = line.trim_end_matches(['\n', '\r']);
+        let trimmed = content.trim_start();
+        let indented = trimmed.len() != content.len();
+
+        // the closing brace of a select expression may sit at the line start
+        let closing = trimmed.starts_with('}') && current.is_some();
+        if (indented || closing) && !trimmed.is_empty() {
+            if let Some(attr) = trimmed.strip_prefix('.') {
+                if let (Some(message), Some((name, value_start))) = (&message, split_assign(attr)) {
+                    if let Some(pending) = current.take() {
+                        pending.finish(&mut entries);
+                    }
+                    let at = start + content.len() - attr.len() + value_start;
+                    current = Some(Pending {
+                        key: format!("{message}.{name}"),
+                        start: at,
+                        end: start + content.len(),
+                        lines: first_line(&raw[at..start + content.len()]),
+                    });
+                }
+            } else if let Some(pending) = current.as_mut() {
+                pending.end = start + content.len();
+                pending.lines.push(trimmed.to_owned());
+            }
+            continue;
+        }
+
+        if let Some(pending) = current.take() {
+            pending.finish(&mut entries);
+        }
+        message = None;
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if let Some((name, value_start)) = split_assign(content) {
+            let at = start + value_start;
+            message = Some(name.to_owned());
+            current = Some(Pending {
+                key: name.to_owned(),
+                start: at,
+                end: start + content.len(),
+                lines: first_line(&raw[at..start + content.len()]),
+            });
+        }
+    }
+    if let Some(pending) = current.take() {
+        pending.finish(&mut entries);
+    }
+    entries
+}
+
+fn first_line(value: &str) -> Vec<String> {
+    match value.trim() {
+        "" => vec![],
+        v => vec![v.to_owned()],
+    }
+}
+
+/// Splits `identifier = value`, returning the identifier and the offset after `=`.
+fn split_assign(line: &str) -> Option<(&str, usize)> {
+    let eq = line.find('=')?;
+    let name = line[..eq].trim_end();
+    let ident = name.strip_prefix('-').unwrap_or(name);
+    let valid = ident.starts_with(|v: char| v.is_ascii_alphabetic())
+        && ident
+            .chars()
+            .all(|v| v.is_ascii_alphanumeric() || v == '_' || v == '-');
+    valid.then_some((name, eq + 1))
+}
+
+/// Encodes a pattern as it follows the `=`, continuation lines get indented.
+pub(super) fn encode(text: &str) -> String {
+    let mut out = String::new();
+    for (i, line) in text.lines().enumerate() {
+        match i {
+            0 => out.push(' '),
+            _ => out.push_str("\n    "),
+        }
+        out.push_str(line.trim());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages() {
+        let raw = "# comment\n-brand = Firefox\nhello = Hello { $name }!\nlong =\n    First line\n    second line\n    .title = Tooltip\n\nemails = { $n ->\n    [one] One email\n   *[other] { $n } emails\n}\n";
+        let entries = parse(raw);
+        let keys = entries.iter().map(|v| v.key.as_str()).collect::<Vec<_>>();
+        assert_eq!(keys, ["-brand", "hello", "long", "long.title", "emails"]);
+        assert_eq!(entries[1].text(), "Hello {0}!");
+        assert_eq!(entries[2].text(), "First line\nsecond line");
+        assert_eq!(&raw[entries[3].span.clone()], " Tooltip");
+        assert!(entries[4].text.is_blank());
+        assert_eq!(encode("a\nb"), " a\n    b");
+    }
+}
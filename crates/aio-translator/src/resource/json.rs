@@ -0,0 +1,157 @@
+use anyhow::bail;
+
+use super::{Codec, Entry};
+
+/// Collects every string leaf of a JSON document together with its position.
+pub(super) fn parse(raw: &str) -> anyhow::Result<Vec<Entry>> {
+    let mut parser = Parser {
+        raw,
+        pos: 0,
+        entries: vec![],
+    };
+    parser.value(&mut vec![])?;
+    parser.whitespace();
+    if parser.pos != raw.len() {
+        bail!("trailing data at byte {}", parser.pos);
+    }
+    Ok(parser.entries)
+}
+
+pub(super) fn encode(text: &str) -> String {
+    serde_json::to_string(text).expect("strings always serialize")
+}
+
+struct Parser<'a> {
+    raw: &'a str,
+    pos: usize,
+    entries: Vec<Entry>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.raw.as_bytes().get(self.pos).copied()
+    }
+
+    fn whitespace(&mut self) {
+        while self.peek().is_some_and(|v| v.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, ch: u8) -> anyhow::Result<()> {
+        self.whitespace();
+        if self.peek() != Some(ch) {
+            bail!("expected `{}` at byte {}", ch as char, self.pos);
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn value(&mut self, path: &mut Vec<String>) -> anyhow::Result<()> {
+        self.whitespace();
+        match self.peek() {
+            Some(b'{') => {
+                self.pos += 1;
+                self.whitespace();
+                if self.peek() == Some(b'}') {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                loop {
+                    self.whitespace();
+                    let key = self.string()?;
+                    self.expect(b':')?;
+                    path.push(key);
+                    self.value(path)?;
+                    path.pop();
+                    self.whitespace();
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b'}') => {
+                            self.pos += 1;
+                            return Ok(());
+                        }
+                        _ => bail!("expected `,` or `}}` at byte {}", self.pos),
+                    }
+                }
+            }
+            Some(b'[') => {
+                self.pos += 1;
+                self.whitespace();
+                if self.peek() == Some(b']') {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                for i in 0.. {
+                    path.push(i.to_string());
+                    self.value(path)?;
+                    path.pop();
+                    self.whitespace();
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b']') => break,
+                        _ => bail!("expected `,` or `]` at byte {}", self.pos),
+                    }
+                }
+                self.pos += 1;
+                Ok(())
+            }
+            Some(b'"') => {
+                let start = self.pos;
+                let text = self.string()?;
+                self.entries.push(Entry::new(
+                    path.join("."),
+                    &text,
+                    start..self.pos,
+                    Codec::Json,
+                ));
+                Ok(())
+            }
+            Some(_) => {
+                let start = self.pos;
+                while self
+                    .peek()
+                    .is_some_and(|v| !matches!(v, b',' | b']' | b'}') && !v.is_ascii_whitespace())
+                {
+                    self.pos += 1;
+                }
+                serde_json::from_str::<serde_json::Value>(&self.raw[start..self.pos])?;
+                Ok(())
+            }
+            None => bail!("unexpected end of document"),
+        }
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        let start = self.pos;
+        if self.peek() != Some(b'"') {
+            bail!("expected string at byte {start}");
+        }
+        self.pos += 1;
+        loop {
+            match self.peek() {
+                Some(b'\\') => self.pos += 2,
+                Some(b'"') => break,
+                Some(_) => self.pos += 1,
+                None => bail!("unterminated string at byte {start}"),
+            }
+        }
+        self.pos += 1;
+        Ok(serde_json::from_str(&self.raw[start..self.pos])?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested() {
+        let entries =
+            parse(r#"{"a": {"b": "x\"y", "c": [1, "two", {"d": "three"}]}, "e": null}"#).unwrap();
+        let keys = entries.iter().map(|v| v.key.as_str()).collect::<Vec<_>>();
+        assert_eq!(keys, ["a.b", "a.c.1", "a.c.2.d"]);
+        assert_eq!(entries[0].text(), "x\"y");
+        assert_eq!(entries[0].encode("z\"").0, r#""z\"""#);
+    }
+}
@@ -0,0 +1,219 @@
+//! Line based reader for the YAML subset locale files use: nested block mappings and
+//! sequences with single line scalars. Block scalars and flow collections are left as
+//! they are.
+
+use std::{collections::HashMap, ops::Range};
+
+use aio_translator_interface::Language;
+
+use super::{Codec, Entry, json};
+
+pub(super) fn parse(raw: &str) -> anyhow::Result<(Vec<Entry>, Option<Range<usize>>)> {
+    let mut entries = vec![];
+    let mut stack: Vec<(usize, String)> = vec![];
+    let mut counters: HashMap<String, usize> = HashMap::new();
+    let mut roots = vec![];
+    let mut block = None;
+    let mut offset = 0;
+    for line in raw.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        let content = line.trim_end_matches(['\n', '\r']);
+        let trimmed = content.trim_start();
+        let mut indent = content.len() - trimmed.len();
+        if let Some(block_indent) = block {
+            if trimmed.is_empty() || indent > block_indent {
+                continue;
+            }
+            block = None;
+        }
+        if trimmed.is_empty()
+            || trimmed.starts_with('#')
+            || trimmed.starts_with("---")
+            || trimmed.starts_with("...")
+            || trimmed.starts_with('%')
+        {
+            continue;
+        }
+        while stack.last().is_some_and(|v| v.0 >= indent) {
+            stack.pop();
+        }
+
+        let mut rest = trimmed;
+        let mut pos = start + indent;
+        let mut item = false;
+        while rest == "-" || rest.starts_with("- ") {
+            let counter = counters.entry(join(&stack)).or_default();
+            stack.push((indent, counter.to_string()));
+            *counter += 1;
+            let skip = rest.len() - rest[1..].trim_start().len();
+            rest = &rest[skip..];
+            pos += skip;
+            indent += skip;
+            item = true;
+        }
+        if rest.is_empty() {
+            continue;
+        }
+
+        let Some((key, key_range, after)) = split_key(rest) else {
+            if let (true, Some((text, span, codec))) = (item, scalar(rest, pos)) {
+                entries.push(Entry::new(join(&stack), &text, span, codec));
+            }
+            continue;
+        };
+        if stack.is_empty() {
+            roots.push((pos + key_range.start..pos + key_range.end, key.clone()));
+        }
+        let value = after.trim_start();
+        let value_pos = pos + rest.len() - value.len();
+        if value.is_empty() || value.starts_with('#') {
+            stack.push((indent, key));
+            continue;
+        }
+        if value.starts_with('|') || value.starts_with('>') {
+            block = Some(indent);
+            continue;
+        }
+        if let Some((text, span, codec)) = scalar(value, value_pos) {
+            let mut path = join(&stack);
+            if !path.is_empty() {
+                path.push('.');
+            }
+            path.push_str(&key);
+            entries.push(Entry::new(path, &text, span, codec));
+        }
+    }
+
+    // rails style files nest everything below the locale
+    let mut locale = None;
+    if let [(range, key)] = roots.as_slice() {
+        let prefix = format!("{key}.");
+        if Language::from_tag(key).is_some() && entries.iter().all(|v| v.key.starts_with(&prefix)) {
+            for entry in &mut entries {
+                entry.key = entry.key[prefix.len()..].to_owned();
+            }
+            locale = Some(range.clone());
+        }
+    }
+    Ok((entries, locale))
+}
+
+fn join(stack: &[(usize, String)]) -> String {
+    stack
+        .iter()
+        .map(|v| v.1.as_str())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Splits `key: value`, returning the unquoted key, its range and the rest of the line.
+fn split_key(line: &str) -> Option<(String, Range<usize>, &str)> {
+    let (key, range, after) = match line.chars().next()? {
+        quote @ ('"' | '\'') => {
+            let end = line[1..].find(quote)? + 1;
+            (
+                line[1..end].to_owned(),
+                1..end,
+                line[end + 1..].trim_start(),
+            )
+        }
+        '[' | '{' | '&' | '*' | '!' | '|' | '>' | '#' => return None,
+        _ => {
+            let end = line
+                .match_indices(':')
+                .map(|v| v.0)
+                .find(|v| line[v + 1..].is_empty() || line[v + 1..].starts_with([' ', '\t']))?;
+            let key = line[..end].trim_end();
+            (key.to_owned(), 0..key.len(), &line[end..])
+        }
+    };
+    Some((key, range, after.strip_prefix(':')?))
+}
+
+/// Reads a single line scalar, returning its text, absolute range and codec.
+fn scalar(value: &str, pos: usize) -> Option<(String, Range<usize>, Codec)> {
+    match value.chars().next()? {
+        '"' => {
+            let mut escaped = false;
+            let end = value.char_indices().skip(1).find(|(_, ch)| {
+                let end = *ch == '"' && !escaped;
+                escaped = *ch == '\\' && !escaped;
+                end
+            })?;
+            let literal = &value[..end.0 + 1];
+            let text = serde_json::from_str(literal).ok()?;
+            Some((text, pos..pos + literal.len(), Codec::YamlQuoted))
+        }
+        '\'' => {
+            let mut i = 1;
+            loop {
+                let next = value[i..].find('\'')? + i;
+                if value[next + 1..].starts_with('\'') {
+                    i = next + 2;
+                    continue;
+                }
+                let text = value[1..next].replace("''", "'");
+                return Some((text, pos..pos + next + 1, Codec::YamlQuoted));
+            }
+        }
+        '[' | '{' | '&' | '*' | '!' | '|' | '>' | '@' | '`' => None,
+        _ => {
+            let end = value.find(" #").unwrap_or(value.len());
+            let text = value[..end].trim_end();
+            if is_reserved(text) {
+                return None;
+            }
+            Some((text.to_owned(), pos..pos + text.len(), Codec::YamlPlain))
+        }
+    }
+}
+
+fn is_reserved(text: &str) -> bool {
+    matches!(
+        text.to_lowercase().as_str(),
+        "~" | "null" | "true" | "false" | "yes" | "no" | "on" | "off"
+    ) || text.parse::<f64>().is_ok()
+}
+
+/// Keeps a value unquoted when YAML would read it back as the same string.
+pub(super) fn encode_plain(text: &str) -> String {
+    let plain = !text.is_empty()
+        && text.trim() == text
+        && !text.contains(['\n', '\t'])
+        && !text.contains(": ")
+        && !text.contains(" #")
+        && !text.ends_with(':')
+        && !text.starts_with([
+            '-', '?', ':', ',', '[', ']', '{', '}', '#', '&', '*', '!', '|', '>', '\'', '"', '%',
+            '@', '`',
+        ])
+        && !is_reserved(text);
+    match plain {
+        true => text.to_owned(),
+        false => json::encode(text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rails() {
+        let raw = "# comment\nen:\n  hello: Hello world # trailing\n  quoted: 'it''s'\n  count: 3\n  nested:\n    title: \"Title: {name}\"\n    body: |\n      block\n  list:\n    - one\n    - two\n";
+        let (entries, locale) = parse(raw).unwrap();
+        let keys = entries.iter().map(|v| v.key.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            ["hello", "quoted", "nested.title", "list.0", "list.1"]
+        );
+        assert_eq!(&raw[locale.unwrap()], "en");
+        assert_eq!(entries[0].text(), "Hello world");
+        assert_eq!(&raw[entries[0].span.clone()], "Hello world");
+        assert_eq!(entries[1].text(), "it's");
+        assert_eq!(entries[2].text(), "Title: {0}");
+        assert_eq!(encode_plain("Hallo: Welt"), "\"Hallo: Welt\"");
+        assert_eq!(encode_plain("Hallo Welt"), "Hallo Welt");
+    }
+}
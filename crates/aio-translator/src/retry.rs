@@ -0,0 +1,271 @@
+use std::time::Duration;
+
+use aio_translator_interface::{
+    AsyncTranslator, BlockingTranslator, Language, TranslationListOutput, TranslationOutput,
+    Translator, TranslatorMutTrait, TranslatorTrait, error::is_transient, prompt::PromptBuilder,
+};
+use async_trait::async_trait;
+
+/// Repeats requests that failed with a transient error, see [`is_transient`], up to `attempts`
+/// times in total. The delay doubles after every failure, other errors are returned at once.
+pub struct RetryTranslator<T: Translator> {
+    t: T,
+    attempts: usize,
+    delay: Duration,
+}
+
+impl<T: Translator> RetryTranslator<T> {
+    pub fn new(t: T, attempts: usize, delay: Duration) -> Self {
+        Self {
+            t,
+            attempts: attempts.max(1),
+            delay,
+        }
+    }
+
+    fn delay(&self, attempt: usize) -> Duration {
+        self.delay.saturating_mul(1 << attempt.min(16))
+    }
+}
+
+impl<T: Translator + Send + Sync> Translator for RetryTranslator<T> {
+    fn local(&self) -> bool {
+        self.t.local()
+    }
+
+    fn translator<'a>(&'a self) -> TranslatorTrait<'a> {
+        match self.t.translator() {
+            TranslatorTrait::Async(_) => TranslatorTrait::Async(self),
+            TranslatorTrait::Blocking(_) => TranslatorTrait::Blocking(self),
+        }
+    }
+
+    fn translator_mut<'a>(&'a mut self) -> TranslatorMutTrait<'a> {
+        match self.t.translator_mut() {
+            TranslatorMutTrait::Async(_) => TranslatorMutTrait::Async(self),
+            TranslatorMutTrait::Blocking(_) => TranslatorMutTrait::Blocking(self),
+        }
+    }
+}
+
+#[async_trait]
+impl<T: Translator + Send + Sync> AsyncTranslator for RetryTranslator<T> {
+    async fn translate(
+        &self,
+        query: &str,
+        context: Option<PromptBuilder>,
+        from: Option<Language>,
+        to: &Language,
+    ) -> anyhow::Result<TranslationOutput> {
+        let t = self.t.translator();
+        let t = t.as_async().unwrap();
+        let mut attempt = 0;
+        loop {
+            match t.translate(query, context.clone(), from, to).await {
+                Err(e) if attempt + 1 < self.attempts && is_transient(&e) => {
+                    tokio::time::sleep(self.delay(attempt)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn translate_vec(
+        &self,
+        query: &[String],
+        context: Option<PromptBuilder>,
+        from: Option<Language>,
+        to: &Language,
+    ) -> anyhow::Result<TranslationListOutput> {
+        self.retry_vec(query, context, from, to, false).await
+    }
+
+    fn handles_html(&self) -> bool {
+        matches!(self.t.translator(), TranslatorTrait::Async(t) if t.handles_html())
+    }
+
+    async fn translate_html_vec(
+        &self,
+        query: &[String],
+        context: Option<PromptBuilder>,
+        from: Option<Language>,
+        to: &Language,
+    ) -> anyhow::Result<TranslationListOutput> {
+        self.retry_vec(query, context, from, to, true).await
+    }
+}
+
+impl<T: Translator + Send + Sync> RetryTranslator<T> {
+    async fn retry_vec(
+        &self,
+        query: &[String],
+        context: Option<PromptBuilder>,
+        from: Option<Language>,
+        to: &Language,
+        html: bool,
+    ) -> anyhow::Result<TranslationListOutput> {
+        let t = self.t.translator();
+        let t = t.as_async().unwrap();
+        let mut attempt = 0;
+        loop {
+            let result = match html {
+                true => t.translate_html_vec(query, context.clone(), from, to).await,
+                false => t.translate_vec(query, context.clone(), from, to).await,
+            };
+            match result {
+                Err(e) if attempt + 1 < self.attempts && is_transient(&e) => {
+                    tokio::time::sleep(self.delay(attempt)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl<T: Translator + Send + Sync> BlockingTranslator for RetryTranslator<T> {
+    fn translate(
+        &mut self,
+        query: &str,
+        context: Option<PromptBuilder>,
+        from: Language,
+        to: &Language,
+    ) -> anyhow::Result<String> {
+        let mut attempt = 0;
+        loop {
+            let result = self.t.translator_mut().as_blocking().unwrap().translate(
+                query,
+                context.clone(),
+                from,
+                to,
+            );
+            match result {
+                Err(e) if attempt + 1 < self.attempts && is_transient(&e) => {
+                    std::thread::sleep(self.delay(attempt));
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn translate_vec(
+        &mut self,
+        query: &[String],
+        context: Option<PromptBuilder>,
+        from: Language,
+        to: &Language,
+    ) -> anyhow::Result<Vec<String>> {
+        let mut attempt = 0;
+        loop {
+            let result = self
+                .t
+                .translator_mut()
+                .as_blocking()
+                .unwrap()
+                .translate_vec(query, context.clone(), from, to);
+            match result {
+                Err(e) if attempt + 1 < self.attempts && is_transient(&e) => {
+                    std::thread::sleep(self.delay(attempt));
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aio_translator_interface::error::Error;
+
+    use super::*;
+
+    /// Fails with `status` until it was called `failures` times.
+    struct Flaky {
+        failures: usize,
+        status: u16,
+        calls: usize,
+    }
+
+    impl Translator for Flaky {
+        fn local(&self) -> bool {
+            true
+        }
+
+        fn translator<'a>(&'a self) -> TranslatorTrait<'a> {
+            TranslatorTrait::Blocking(self)
+        }
+
+        fn translator_mut<'a>(&'a mut self) -> TranslatorMutTrait<'a> {
+            TranslatorMutTrait::Blocking(self)
+        }
+    }
+
+    impl BlockingTranslator for Flaky {
+        fn translate(
+            &mut self,
+            query: &str,
+            _: Option<PromptBuilder>,
+            _: Language,
+            _: &Language,
+        ) -> anyhow::Result<String> {
+            self.calls += 1;
+            if self.calls <= self.failures {
+                Err(Error::RequestFailed(self.status))?;
+            }
+            Ok(query.to_owned())
+        }
+
+        fn translate_vec(
+            &mut self,
+            query: &[String],
+            _: Option<PromptBuilder>,
+            from: Language,
+            to: &Language,
+        ) -> anyhow::Result<Vec<String>> {
+            query
+                .iter()
+                .map(|v| self.translate(v, None, from, to))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn retries() {
+        let flaky = Flaky {
+            failures: 2,
+            status: 503,
+            calls: 0,
+        };
+        let mut t = RetryTranslator::new(flaky, 3, Duration::from_millis(1));
+        let out =
+            BlockingTranslator::translate(&mut t, "a", None, Language::English, &Language::German);
+        assert_eq!(out.unwrap(), "a");
+        assert_eq!(t.t.calls, 3);
+
+        t.t.failures = 10;
+        let out =
+            BlockingTranslator::translate(&mut t, "a", None, Language::English, &Language::German);
+        assert!(out.is_err());
+        assert_eq!(t.t.calls, 6);
+    }
+
+    #[test]
+    fn returns_permanent_errors() {
+        let flaky = Flaky {
+            failures: 1,
+            status: 403,
+            calls: 0,
+        };
+        let mut t = RetryTranslator::new(flaky, 3, Duration::from_millis(1));
+        let out =
+            BlockingTranslator::translate(&mut t, "a", None, Language::English, &Language::German);
+        assert!(matches!(
+            out.unwrap_err().downcast_ref::<Error>(),
+            Some(Error::RequestFailed(403))
+        ));
+        assert_eq!(t.t.calls, 1);
+    }
+}
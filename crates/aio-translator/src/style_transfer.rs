@@ -1,6 +1,6 @@
 use aio_translator_interface::{
     AsyncTranslator, BlockingTranslator, Language, TranslationListOutput, TranslationOutput,
-    Translator, TranslatorMutTrait, TranslatorTrait, error::Error, prompt::PromptBuilder,
+    Translator, TranslatorMutTrait, TranslatorTrait, prompt::PromptBuilder,
 };
 use async_trait::async_trait;
 use fancy_regex::Regex;
@@ -10,6 +10,12 @@ pub struct StyleTransfer<T: Translator> {
     t: T,
 }
 
+impl<T: Translator> StyleTransfer<T> {
+    pub fn new(t: T) -> Self {
+        Self { t }
+    }
+}
+
 impl<T: Translator + Send + Sync> Translator for StyleTransfer<T> {
     fn local(&self) -> bool {
         self.t.local()
@@ -87,6 +93,32 @@ impl<T: Translator + Send + Sync> AsyncTranslator for StyleTransfer<T> {
             .collect();
         Ok(trans)
     }
+
+    fn handles_html(&self) -> bool {
+        matches!(self.t.translator(), TranslatorTrait::Async(t) if t.handles_html())
+    }
+
+    /// Markup is passed through, the cleanup only applies to plain text.
+    async fn translate_html_vec(
+        &self,
+        query: &[String],
+        context: Option<PromptBuilder>,
+        from: Option<Language>,
+        to: &Language,
+    ) -> anyhow::Result<TranslationListOutput> {
+        if from == Some(*to) {
+            return Ok(TranslationListOutput {
+                text: query.to_owned(),
+                lang: from,
+            });
+        }
+        self.t
+            .translator()
+            .as_async()
+            .unwrap()
+            .translate_html_vec(query, context, from, to)
+            .await
+    }
 }
 
 impl<T: Translator + Send + Sync> BlockingTranslator for StyleTransfer<T> {
@@ -248,7 +280,20 @@ pub fn is_valuable_text(text: &str) -> bool {
 
 #[cfg(test)]
 mod tests {
+    use aio_translator_original::OriginalTranslator;
+
     use super::*;
+    use crate::translate_batch;
+
+    #[tokio::test]
+    async fn wraps_translator() {
+        let mut t = StyleTransfer::new(OriginalTranslator::new());
+        let query = ["Hello,world".to_owned(), "...".to_owned()];
+        let out = translate_batch(&mut t, &query, Some(Language::English), &Language::German)
+            .await
+            .unwrap();
+        assert_eq!(out, ["Hello, world", "..."]);
+    }
 
     #[test]
     fn test_clean_translation_output_basic() {
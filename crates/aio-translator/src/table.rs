@@ -0,0 +1,329 @@
+//! Bulk translation of CSV and TSV files.
+//!
+//! Rows are streamed in batches, every selected column gets one new column per target
+//! language. Rows are written as soon as their batch is done, which is what resuming
+//! builds on.
+
+use std::{
+    collections::HashMap,
+    fs::OpenOptions,
+    io::{Read, Write},
+    path::Path,
+};
+
+use aio_translator_interface::{Language, Translator};
+use anyhow::bail;
+use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+
+use crate::batch::translate_batch;
+
+pub struct TableOptions {
+    /// `b','` for CSV, `b'\t'` for TSV
+    pub delimiter: u8,
+    /// Header names of the columns to translate
+    pub columns: Vec<String>,
+    /// Column holding the source language of each row, as language tag or ISO 639-3
+    pub language_column: Option<String>,
+    /// Source language of rows without hint
+    pub from: Option<Language>,
+    pub targets: Vec<Language>,
+    /// Rows per batch
+    pub batch_size: usize,
+}
+
+impl TableOptions {
+    pub fn new(columns: Vec<String>, targets: Vec<Language>) -> Self {
+        Self {
+            delimiter: b',',
+            columns,
+            language_column: None,
+            from: None,
+            targets,
+            batch_size: 64,
+        }
+    }
+
+    /// Picks the delimiter from the file extension, tab for `.tsv` and `.tab`.
+    pub fn with_delimiter_for(mut self, path: &Path) -> Self {
+        self.delimiter = match path.extension().and_then(|v| v.to_str()) {
+            Some("tsv" | "tab") => b'\t',
+            _ => b',',
+        };
+        self
+    }
+
+    /// Name of the column holding `column` translated to `to`.
+    pub fn output_column(column: &str, to: &Language) -> String {
+        format!("{column}_{}", to.to_tag().unwrap_or("target"))
+    }
+}
+
+/// Counts of what [`translate_table`] did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TableReport {
+    /// Rows written in this run
+    pub rows: usize,
+    /// Rows that were already done
+    pub resumed: usize,
+    /// Translated cells
+    pub translated: usize,
+}
+
+struct Layout {
+    columns: Vec<usize>,
+    language: Option<usize>,
+}
+
+impl Layout {
+    fn new(header: &StringRecord, options: &TableOptions) -> anyhow::Result<Self> {
+        let find = |name: &str| match header.iter().position(|v| v == name) {
+            Some(i) => Ok(i),
+            None => bail!("column {name} missing"),
+        };
+        Ok(Self {
+            columns: options
+                .columns
+                .iter()
+                .map(|v| find(v))
+                .collect::<anyhow::Result<_>>()?,
+            language: options.language_column.as_deref().map(find).transpose()?,
+        })
+    }
+}
+
+fn output_header(header: &StringRecord, options: &TableOptions) -> StringRecord {
+    let mut out = header.clone();
+    for to in &options.targets {
+        for column in &options.columns {
+            out.push_field(&TableOptions::output_column(column, to));
+        }
+    }
+    out
+}
+
+/// Translates all rows of `input` into `output`, header included.
+pub async fn translate_table<T: Translator + ?Sized, R: Read, W: Write>(
+    translator: &mut T,
+    input: R,
+    output: W,
+    options: &TableOptions,
+) -> anyhow::Result<TableReport> {
+    translate_rows(translator, input, output, options, 0, true).await
+}
+
+/// Translates `source` into `target`.
+///
+/// If `target` already holds rows of an earlier run, those rows are kept and the
+/// translation continues after the last complete one.
+pub async fn translate_table_file<T: Translator + ?Sized>(
+    translator: &mut T,
+    source: &Path,
+    target: &Path,
+    options: &TableOptions,
+) -> anyhow::Result<TableReport> {
+    let done = match target.exists() {
+        true => completed_rows(target, options)?,
+        false => None,
+    };
+    let input = std::fs::File::open(source)?;
+    match done {
+        Some(done) => {
+            let output = OpenOptions::new().append(true).open(target)?;
+            translate_rows(translator, input, output, options, done, false).await
+        }
+        None => {
+            let output = std::fs::File::create(target)?;
+            translate_rows(translator, input, output, options, 0, true).await
+        }
+    }
+}
+
+/// Counts the complete rows of an earlier run and cuts off a partially written one.
+fn completed_rows(target: &Path, options: &TableOptions) -> anyhow::Result<Option<usize>> {
+    let mut reader = ReaderBuilder::new()
+        .delimiter(options.delimiter)
+        .flexible(true)
+        .from_path(target)?;
+    let Ok(header) = reader.headers().cloned() else {
+        return Ok(None);
+    };
+    if header.is_empty() {
+        return Ok(None);
+    }
+    let mut end = reader.position().byte();
+    let mut rows = 0;
+    let mut record = StringRecord::new();
+    while let Ok(true) = reader.read_record(&mut record) {
+        if record.len() != header.len() {
+            break;
+        }
+        rows += 1;
+        end = reader.position().byte();
+    }
+    let file = OpenOptions::new().write(true).open(target)?;
+    if file.metadata()?.len() != end {
+        file.set_len(end)?;
+    }
+    Ok(Some(rows))
+}
+
+async fn translate_rows<T: Translator + ?Sized, R: Read, W: Write>(
+    translator: &mut T,
+    input: R,
+    output: W,
+    options: &TableOptions,
+    skip: usize,
+    header: bool,
+) -> anyhow::Result<TableReport> {
+    let mut reader = ReaderBuilder::new()
+        .delimiter(options.delimiter)
+        .from_reader(input);
+    let mut writer = WriterBuilder::new()
+        .delimiter(options.delimiter)
+        .from_writer(output);
+    let source_header = reader.headers()?.clone();
+    let layout = Layout::new(&source_header, options)?;
+    if header {
+        writer.write_record(&output_header(&source_header, options))?;
+    }
+
+    let mut report = TableReport {
+        resumed: skip,
+        ..Default::default()
+    };
+    let mut records = reader.records().skip(skip);
+    let batch_size = options.batch_size.max(1);
+    loop {
+        let batch = records
+            .by_ref()
+            .take(batch_size)
+            .collect::<Result<Vec<_>, _>>()?;
+        if batch.is_empty() {
+            break;
+        }
+        let mut rows = batch;
+        for to in &options.targets {
+            let translated = translate_columns(translator, &rows, &layout, options, to).await?;
+            report.translated += translated.iter().filter(|v| !v.is_empty()).count();
+            for (i, cell) in translated.into_iter().enumerate() {
+                rows[i / layout.columns.len()].push_field(&cell);
+            }
+        }
+        for row in &rows {
+            writer.write_record(row)?;
+        }
+        writer.flush()?;
+        report.rows += rows.len();
+    }
+    Ok(report)
+}
+
+/// Translations of the selected columns, row by row.
+async fn translate_columns<T: Translator + ?Sized>(
+    translator: &mut T,
+    rows: &[StringRecord],
+    layout: &Layout,
+    options: &TableOptions,
+    to: &Language,
+) -> anyhow::Result<Vec<String>> {
+    let mut out = vec![String::new(); rows.len() * layout.columns.len()];
+    // one request per source language
+    let mut groups: HashMap<Option<Language>, Vec<usize>> = HashMap::new();
+    for (row, record) in rows.iter().enumerate() {
+        let hint = layout
+            .language
+            .and_then(|v| record.get(v))
+            .map(str::trim)
+            .and_then(|v| Language::from_tag(v).or_else(|| Language::from_639_3(v)));
+        let from = hint.or(options.from);
+        for (column, index) in layout.columns.iter().enumerate() {
+            let text = record.get(*index).unwrap_or_default();
+            if crate::is_valuable_text(text) {
+                groups
+                    .entry(from)
+                    .or_default()
+                    .push(row * layout.columns.len() + column);
+            } else {
+                out[row * layout.columns.len() + column] = text.to_owned();
+            }
+        }
+    }
+
+    let mut groups = groups.into_iter().collect::<Vec<_>>();
+    groups.sort_by_key(|v| v.1[0]);
+    for (from, cells) in groups {
+        let query = cells
+            .iter()
+            .map(|v| {
+                let record = &rows[v / layout.columns.len()];
+                record
+                    .get(layout.columns[v % layout.columns.len()])
+                    .unwrap_or_default()
+                    .to_owned()
+            })
+            .collect::<Vec<_>>();
+        let translated = translate_batch(translator, &query, from, to).await?;
+        for (cell, text) in cells.into_iter().zip(translated) {
+            out[cell] = text;
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OriginalTranslator;
+
+    const INPUT: &str = "id,text,lang\n1,Hello,en\n2,,en\n3,Bonjour,fr\n4,Hallo,\n";
+
+    fn options() -> TableOptions {
+        let mut options = TableOptions::new(vec!["text".to_owned()], vec![Language::German]);
+        options.language_column = Some("lang".to_owned());
+        options.from = Some(Language::English);
+        options.batch_size = 2;
+        options
+    }
+
+    #[tokio::test]
+    async fn stream() {
+        let mut output = vec![];
+        let report = translate_table(
+            &mut OriginalTranslator::new(),
+            INPUT.as_bytes(),
+            &mut output,
+            &options(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.rows, 4);
+        assert_eq!(report.translated, 3);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "id,text,lang,text_de\n1,Hello,en,Hello\n2,,en,\n3,Bonjour,fr,Bonjour\n4,Hallo,,Hallo\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn resume() {
+        let dir = std::env::temp_dir().join(format!("aio-table-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("in.csv");
+        let target = dir.join("out.csv");
+        std::fs::write(&source, INPUT).unwrap();
+        // second row was cut off mid-write
+        std::fs::write(&target, "id,text,lang,text_de\n1,Hello,en,Hallo\n2,,e").unwrap();
+
+        let report =
+            translate_table_file(&mut OriginalTranslator::new(), &source, &target, &options())
+                .await
+                .unwrap();
+        assert_eq!(report.resumed, 1);
+        assert_eq!(report.rows, 3);
+        assert_eq!(
+            std::fs::read_to_string(&target).unwrap(),
+            "id,text,lang,text_de\n1,Hello,en,Hallo\n2,,en,\n3,Bonjour,fr,Bonjour\n4,Hallo,,Hallo\n"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,275 @@
+//! XHTML document translation, as used by EPUB chapters.
+//!
+//! Uses the same text runs as [`crate::html`], but splices the translations into the
+//! source, so the document stays well-formed XML.
+
+use std::{fmt, ops::Range};
+
+use aio_translator_interface::{Language, Translator};
+
+use crate::{
+    batch::translate_batch,
+    html::{ATTRIBUTES, INLINE, SKIPPED},
+    markup::{Protected, escape_xml, escape_xml_attr, unescape_xml},
+    xml::{Node, Token, element_end, element_name, local_name, tokenize},
+};
+
+/// Counts of what [`XhtmlDocument::translate`] did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct XhtmlReport {
+    /// Text runs and attributes
+    pub translated: usize,
+    /// Runs whose inline markup could not be restored
+    pub needs_review: usize,
+}
+
+struct Run {
+    span: Range<usize>,
+    text: Protected,
+}
+
+pub struct XhtmlDocument {
+    raw: String,
+}
+
+fn is_skipped(token: &Token) -> bool {
+    element_name(token).is_some_and(|v| SKIPPED.contains(&v))
+        || token.attr("translate").is_some_and(|v| v.value == "no")
+        || token
+            .attr("class")
+            .is_some_and(|v| v.value.split_whitespace().any(|v| v == "notranslate"))
+}
+
+fn is_inline(token: &Token) -> bool {
+    match &token.node {
+        Node::Start { name, .. } | Node::End { name } => INLINE.contains(&local_name(name)),
+        _ => true,
+    }
+}
+
+impl XhtmlDocument {
+    pub fn parse(raw: impl Into<String>) -> anyhow::Result<Self> {
+        let raw = raw.into();
+        tokenize(&raw)?;
+        Ok(Self { raw })
+    }
+
+    /// Language from the `xml:lang` or `lang` attribute of `<html>`.
+    pub fn source_language(&self) -> Option<Language> {
+        let tokens = tokenize(&self.raw).ok()?;
+        let html = tokens.iter().find(|v| element_name(v) == Some("html"))?;
+        Language::from_tag(&html.attr("lang")?.value)
+    }
+
+    pub async fn translate<T: Translator + ?Sized>(
+        &mut self,
+        translator: &mut T,
+        from: Option<Language>,
+        to: Language,
+    ) -> anyhow::Result<XhtmlReport> {
+        let from = from.or_else(|| self.source_language());
+        let mut report = XhtmlReport::default();
+
+        // attributes first, the runs are read from the updated document
+        let tokens = tokenize(&self.raw)?;
+        let attributes = self.attributes(&tokens);
+        let query = attributes.iter().map(|v| v.1.clone()).collect::<Vec<_>>();
+        let translated = translate_batch(translator, &query, from, &to).await?;
+        report.translated += translated.len();
+        let mut edits = attributes
+            .into_iter()
+            .zip(translated)
+            .map(|((range, _), value)| (range, escape_xml_attr(&value)))
+            .collect::<Vec<_>>();
+        if let (Some(html), Some(tag)) = (
+            tokens.iter().find(|v| element_name(v) == Some("html")),
+            to.to_tag(),
+        ) && let Node::Start { attrs, .. } = &html.node
+        {
+            edits.extend(
+                attrs
+                    .iter()
+                    .filter(|v| local_name(&v.name) == "lang")
+                    .map(|v| (v.range.clone(), tag.to_owned())),
+            );
+        }
+        self.splice(edits);
+
+        let tokens = tokenize(&self.raw)?;
+        let runs = self.runs(&tokens);
+        let query = runs
+            .iter()
+            .map(|v| v.text.text().to_owned())
+            .collect::<Vec<_>>();
+        let translated = translate_batch(translator, &query, from, &to).await?;
+        let mut edits = vec![];
+        for (run, translation) in runs.into_iter().zip(translated) {
+            let (text, valid) = run.text.restore(&translation, escape_xml);
+            match valid {
+                true => report.translated += 1,
+                false => report.needs_review += 1,
+            }
+            edits.push((run.span, text));
+        }
+        self.splice(edits);
+        Ok(report)
+    }
+
+    fn splice(&mut self, mut edits: Vec<(Range<usize>, String)>) {
+        edits.sort_by_key(|v| v.0.start);
+        let mut raw = String::with_capacity(self.raw.len());
+        let mut last = 0;
+        for (range, text) in edits {
+            raw.push_str(&self.raw[last..range.start]);
+            raw.push_str(&text);
+            last = range.end;
+        }
+        raw.push_str(&self.raw[last..]);
+        self.raw = raw;
+    }
+
+    /// Translatable attribute values outside of skipped elements.
+    fn attributes(&self, tokens: &[Token]) -> Vec<(Range<usize>, String)> {
+        let mut out = vec![];
+        let mut i = 0;
+        while i < tokens.len() {
+            let token = &tokens[i];
+            if is_skipped(token) {
+                i = element_end(tokens, i) + 1;
+                continue;
+            }
+            if let Node::Start { attrs, .. } = &token.node {
+                out.extend(
+                    attrs
+                        .iter()
+                        .filter(|v| ATTRIBUTES.contains(&local_name(&v.name)))
+                        .filter(|v| crate::is_valuable_text(&v.value))
+                        .map(|v| (v.range.clone(), v.value.clone())),
+                );
+            }
+            i += 1;
+        }
+        out
+    }
+
+    fn runs(&self, tokens: &[Token]) -> Vec<Run> {
+        let mut runs = vec![];
+        // token ranges of the current run, skipped inline elements are one item
+        let mut items: Vec<Range<usize>> = vec![];
+        let mut i = 0;
+        while i < tokens.len() {
+            let token = &tokens[i];
+            let inline = is_inline(token);
+            if is_skipped(token) {
+                let end = element_end(tokens, i);
+                match inline {
+                    true => items.push(i..end + 1),
+                    false => runs.extend(self.run(tokens, std::mem::take(&mut items))),
+                }
+                i = end + 1;
+                continue;
+            }
+            match inline {
+                true => items.push(i..i + 1),
+                false => runs.extend(self.run(tokens, std::mem::take(&mut items))),
+            }
+            i += 1;
+        }
+        runs.extend(self.run(tokens, items));
+        runs
+    }
+
+    fn run(&self, tokens: &[Token], items: Vec<Range<usize>>) -> Option<Run> {
+        let first = items.first()?;
+        let last = items.last()?;
+        let mut span = tokens[first.start].range.start..tokens[last.end - 1].range.end;
+        // whitespace around the run stays where it is
+        if let Node::Text = tokens[first.start].node {
+            let text = &self.raw[span.clone()];
+            span.start += text.len() - text.trim_start().len();
+        }
+        if let Node::Text = tokens[last.end - 1].node {
+            let text = &self.raw[span.clone()];
+            span.end -= text.len() - text.trim_end().len();
+        }
+        if span.is_empty() {
+            return None;
+        }
+
+        let mut text = Protected::new();
+        for item in items {
+            let range = tokens[item.start].range.start.max(span.start)
+                ..tokens[item.end - 1].range.end.min(span.end);
+            let raw = &self.raw[range];
+            if item.len() > 1 {
+                text.push_standalone(raw);
+                continue;
+            }
+            match tokens[item.start].node {
+                Node::Text => text.push_text(&unescape_xml(raw)),
+                Node::CData => text.push_text(&raw[9..raw.len() - 3]),
+                Node::Start { empty: false, .. } => text.push_open(raw),
+                Node::End { .. } => text.push_close(raw),
+                Node::Start { .. } | Node::Other => text.push_standalone(raw),
+            }
+        }
+        (!text.is_blank()).then_some(Run { span, text })
+    }
+}
+
+impl fmt::Display for XhtmlDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OriginalTranslator;
+
+    const CHAPTER: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml" xml:lang="en" lang="en">
+<head><title>Chapter 1</title></head>
+<body>
+  <h1>Chapter <em>One</em></h1>
+  <p>She said &amp; left.<br/>Then <code>nothing</code> happened.</p>
+  <img src="a.png" alt="A cat"/>
+  <pre>kept</pre>
+</body>
+</html>"#;
+
+    #[test]
+    fn runs() {
+        let doc = XhtmlDocument::parse(CHAPTER).unwrap();
+        let tokens = tokenize(&doc.raw).unwrap();
+        let runs = doc
+            .runs(&tokens)
+            .into_iter()
+            .map(|v| v.text.text().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            runs,
+            [
+                "Chapter 1",
+                "Chapter {0}One{1}",
+                "She said & left.{0}Then {1} happened."
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn round_trip() {
+        let mut doc = XhtmlDocument::parse(CHAPTER).unwrap();
+        assert_eq!(doc.source_language(), Some(Language::English));
+        let report = doc
+            .translate(&mut OriginalTranslator::new(), None, Language::German)
+            .await
+            .unwrap();
+        assert_eq!(report.translated, 4);
+        assert_eq!(
+            doc.to_string(),
+            CHAPTER.replace("lang=\"en\"", "lang=\"de\"")
+        );
+    }
+}
@@ -0,0 +1,510 @@
+//! XLIFF 1.2 and 2.0 document translation.
+//!
+//! The document is never re-serialized. Targets are spliced into the original text, so
+//! everything that is not touched stays byte for byte the same.
+
+use std::ops::Range;
+
+use aio_translator_interface::{Language, Translator, error::Error};
+use anyhow::bail;
+
+use crate::{
+    batch::translate_batch,
+    markup::{Protected, escape_xml, unescape_xml},
+    xml::{Node, Token, element_name, indent_before, local_name, set_attr, tokenize},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XliffVersion {
+    V1_2,
+    V2_0,
+}
+
+/// State written for a freshly translated target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetState {
+    /// All inline elements could be restored.
+    Translated,
+    /// The translator lost or reordered inline elements, the target needs a human.
+    NeedsReview,
+}
+
+/// Element that carries the languages: `<file>` in 1.2, `<xliff>` in 2.0.
+#[derive(Debug)]
+struct Scope {
+    tag: usize,
+    source_language: Option<String>,
+    target_language: Option<String>,
+}
+
+/// A translatable segment: `<trans-unit>` in 1.2, `<segment>` in 2.0.
+#[derive(Debug)]
+pub struct Unit {
+    /// Id of the trans-unit or unit
+    pub id: Option<String>,
+    scope: usize,
+    container: usize,
+    translate: bool,
+    /// token range of the `<source>` content
+    source: Range<usize>,
+    /// byte and token range of the whole `<target>` element
+    target: Option<(Range<usize>, Range<usize>)>,
+    /// byte position after `</source>`
+    insert_at: usize,
+    indent: String,
+}
+
+/// Counts of what [`XliffDocument::translate`] did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct XliffReport {
+    pub translated: usize,
+    pub needs_review: usize,
+    pub skipped: usize,
+}
+
+pub struct XliffDocument {
+    raw: String,
+    version: XliffVersion,
+    tokens: Vec<Token>,
+    scopes: Vec<Scope>,
+    units: Vec<Unit>,
+}
+
+impl XliffDocument {
+    pub fn parse(raw: impl Into<String>) -> anyhow::Result<Self> {
+        let raw = raw.into();
+        let tokens = tokenize(&raw)?;
+        let root = tokens
+            .iter()
+            .position(|v| matches!(&v.node, Node::Start { .. }))
+            .ok_or_else(|| anyhow::anyhow!("document has no root element"))?;
+        let version = match tokens[root].attr("version").map(|v| v.value.as_str()) {
+            _ if element_name(&tokens[root]) != Some("xliff") => bail!("root is not <xliff>"),
+            Some(v) if v.starts_with('1') => XliffVersion::V1_2,
+            Some(v) if v.starts_with('2') => XliffVersion::V2_0,
+            v => bail!("unsupported xliff version {v:?}"),
+        };
+
+        let mut scopes = vec![];
+        let mut units = vec![];
+        let mut stack: Vec<String> = vec![];
+        let mut unit_id = None;
+        let mut unit_translate = true;
+        let mut current: Option<Unit> = None;
+        let mut source_start = None;
+        let mut target_start = None;
+        for (i, token) in tokens.iter().enumerate() {
+            match &token.node {
+                Node::Start { name, empty, .. } => {
+                    let name = local_name(name);
+                    let parent = stack.last().map(|v| v.as_str());
+                    match (version, name, parent) {
+                        (XliffVersion::V1_2, "file", _) => scopes.push(Scope {
+                            tag: i,
+                            source_language: token.attr("source-language").map(|v| v.value.clone()),
+                            target_language: token.attr("target-language").map(|v| v.value.clone()),
+                        }),
+                        (XliffVersion::V2_0, "xliff", _) => scopes.push(Scope {
+                            tag: i,
+                            source_language: token.attr("srcLang").map(|v| v.value.clone()),
+                            target_language: token.attr("trgLang").map(|v| v.value.clone()),
+                        }),
+                        (XliffVersion::V2_0, "unit", _) => {
+                            unit_id = token.attr("id").map(|v| v.value.clone());
+                            unit_translate =
+                                token.attr("translate").map(|v| v.value.as_str()) != Some("no");
+                        }
+                        (XliffVersion::V1_2, "trans-unit", _)
+                        | (XliffVersion::V2_0, "segment", _) => {
+                            let id = match version {
+                                XliffVersion::V1_2 => token.attr("id").map(|v| v.value.clone()),
+                                XliffVersion::V2_0 => unit_id.clone(),
+                            };
+                            let translate = match version {
+                                XliffVersion::V1_2 => {
+                                    token.attr("translate").map(|v| v.value.as_str()) != Some("no")
+                                }
+                                XliffVersion::V2_0 => unit_translate,
+                            };
+                            current = Some(Unit {
+                                id,
+                                scope: scopes.len().saturating_sub(1),
+                                container: i,
+                                translate,
+                                source: 0..0,
+                                target: None,
+                                insert_at: 0,
+                                indent: String::new(),
+                            });
+                        }
+                        (_, "source", Some("trans-unit" | "segment")) => {
+                            if let Some(unit) = current.as_mut() {
+                                unit.indent = indent_before(&raw, token.range.start);
+                                match *empty {
+                                    true => {
+                                        unit.source = i + 1..i + 1;
+                                        unit.insert_at = token.range.end;
+                                    }
+                                    false => source_start = Some(i + 1),
+                                }
+                            }
+                        }
+                        (_, "target", Some("trans-unit" | "segment")) => match *empty {
+                            true => {
+                                if let Some(unit) = current.as_mut() {
+                                    unit.target = Some((token.range.clone(), i..i + 1));
+                                }
+                            }
+                            false => target_start = Some((token.range.start, i)),
+                        },
+                        _ => {}
+                    }
+                    if !*empty {
+                        stack.push(name.to_owned());
+                    }
+                }
+                Node::End { name } => {
+                    let name = local_name(name);
+                    match stack.pop() {
+                        Some(open) if open == name => {}
+                        _ => bail!("unbalanced </{name}> at byte {}", token.range.start),
+                    }
+                    let parent = stack.last().map(|v| v.as_str());
+                    match (name, parent) {
+                        ("source", Some("trans-unit" | "segment")) => {
+                            if let (Some(unit), Some(start)) =
+                                (current.as_mut(), source_start.take())
+                            {
+                                unit.source = start..i;
+                                unit.insert_at = token.range.end;
+                            }
+                        }
+                        ("target", Some("trans-unit" | "segment")) => {
+                            if let (Some(unit), Some((start, first))) =
+                                (current.as_mut(), target_start.take())
+                            {
+                                unit.target = Some((start..token.range.end, first..i + 1));
+                            }
+                        }
+                        ("trans-unit", _) | ("segment", _) => units.extend(current.take()),
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let Some(name) = stack.pop() {
+            bail!("<{name}> is never closed");
+        }
+
+        Ok(Self {
+            raw,
+            version,
+            tokens,
+            scopes,
+            units,
+        })
+    }
+
+    pub fn version(&self) -> XliffVersion {
+        self.version
+    }
+
+    pub fn units(&self) -> &[Unit] {
+        &self.units
+    }
+
+    /// Source language of the first `<file>` (1.2) or of the document (2.0).
+    pub fn source_language(&self) -> Option<Language> {
+        self.scopes
+            .first()?
+            .source_language
+            .as_deref()
+            .and_then(Language::from_tag)
+    }
+
+    /// Target language of the first `<file>` (1.2) or of the document (2.0).
+    pub fn target_language(&self) -> Option<Language> {
+        self.scopes
+            .first()?
+            .target_language
+            .as_deref()
+            .and_then(Language::from_tag)
+    }
+
+    /// Plain text of a unit's source with inline elements removed.
+    pub fn source_text(&self, unit: &Unit) -> String {
+        self.protect(unit).text().to_owned()
+    }
+
+    /// Translates every unit that has no target yet.
+    ///
+    /// `to` overrides the target language of the file and is written into the header
+    /// if the file doesn't declare one.
+    pub async fn translate<T: Translator + ?Sized>(
+        &mut self,
+        translator: &mut T,
+        to: Option<Language>,
+    ) -> anyhow::Result<XliffReport> {
+        let mut report = XliffReport::default();
+        let mut edits = vec![];
+        for (scope_index, scope) in self.scopes.iter().enumerate() {
+            let from = match &scope.source_language {
+                Some(v) => {
+                    Some(Language::from_tag(v).ok_or(Error::CouldNotMapLanguage(Some(v.clone())))?)
+                }
+                None => None,
+            };
+            let to = match (to, &scope.target_language) {
+                (Some(to), _) => to,
+                (None, Some(v)) => {
+                    Language::from_tag(v).ok_or(Error::CouldNotMapLanguage(Some(v.clone())))?
+                }
+                (None, None) => return Err(Error::CouldNotMapLanguage(None).into()),
+            };
+
+            let mut pending = vec![];
+            for unit in self.units.iter().filter(|v| v.scope == scope_index) {
+                let protected = self.protect(unit);
+                if !unit.translate || protected.is_blank() || self.has_target(unit) {
+                    report.skipped += 1;
+                    continue;
+                }
+                pending.push((unit, protected));
+            }
+            if pending.is_empty() {
+                continue;
+            }
+
+            let query = pending
+                .iter()
+                .map(|v| v.1.text().to_owned())
+                .collect::<Vec<_>>();
+            let translated = translate_batch(translator, &query, from, &to).await?;
+            for ((unit, protected), translation) in pending.into_iter().zip(translated) {
+                let (content, valid) = protected.restore(&translation, escape_xml);
+                let state = match valid {
+                    true => {
+                        report.translated += 1;
+                        TargetState::Translated
+                    }
+                    false => {
+                        report.needs_review += 1;
+                        TargetState::NeedsReview
+                    }
+                };
+                edits.extend(self.target_edits(unit, &content, state));
+            }
+
+            let attr = match self.version {
+                XliffVersion::V1_2 => "target-language",
+                XliffVersion::V2_0 => "trgLang",
+            };
+            if let (None, Some(tag)) = (&scope.target_language, to.to_tag()) {
+                edits.push(set_attr(&self.tokens[scope.tag], attr, tag));
+            }
+        }
+
+        edits.sort_by_key(|v| v.0.start);
+        let mut raw = String::with_capacity(self.raw.len());
+        let mut last = 0;
+        for (range, text) in edits {
+            raw.push_str(&self.raw[last..range.start]);
+            raw.push_str(&text);
+            last = range.end;
+        }
+        raw.push_str(&self.raw[last..]);
+        *self = Self::parse(raw)?;
+        Ok(report)
+    }
+
+    fn has_target(&self, unit: &Unit) -> bool {
+        let Some((_, target)) = &unit.target else {
+            return false;
+        };
+        self.tokens[target.clone()].iter().any(|v| match v.node {
+            Node::Text => !self.raw[v.range.clone()].trim().is_empty(),
+            Node::CData => true,
+            Node::Start { ref name, .. } => local_name(name) != "target",
+            _ => false,
+        })
+    }
+
+    fn protect(&self, unit: &Unit) -> Protected {
+        let mut protected = Protected::new();
+        let tokens = &self.tokens[unit.source.clone()];
+        let mut i = 0;
+        while i < tokens.len() {
+            let token = &tokens[i];
+            let raw = &self.raw[token.range.clone()];
+            match &token.node {
+                Node::Text => protected.push_text(&unescape_xml(raw)),
+                Node::CData => protected.push_text(&raw[9..raw.len() - 3]),
+                Node::Start { name, empty, .. } if !*empty && is_native_code(local_name(name)) => {
+                    // native code like `<ph>{0}</ph>` is kept as one opaque span
+                    let mut depth = 0;
+                    let start = token.range.start;
+                    let mut end = token.range.end;
+                    while i < tokens.len() {
+                        match &tokens[i].node {
+                            Node::Start { empty: false, .. } => depth += 1,
+                            Node::End { .. } => depth -= 1,
+                            _ => {}
+                        }
+                        end = tokens[i].range.end;
+                        if depth == 0 {
+                            break;
+                        }
+                        i += 1;
+                    }
+                    protected.push_standalone(&self.raw[start..end]);
+                }
+                Node::Start { empty: true, .. } | Node::Other => protected.push_standalone(raw),
+                Node::Start { .. } => protected.push_open(raw),
+                Node::End { .. } => protected.push_close(raw),
+            }
+            i += 1;
+        }
+        protected
+    }
+
+    fn target_edits(
+        &self,
+        unit: &Unit,
+        content: &str,
+        state: TargetState,
+    ) -> Vec<(Range<usize>, String)> {
+        let mut edits = vec![];
+        let target = match (self.version, state) {
+            (XliffVersion::V1_2, TargetState::Translated) => {
+                format!("<target state=\"translated\">{content}</target>")
+            }
+            (XliffVersion::V1_2, TargetState::NeedsReview) => {
+                format!("<target state=\"needs-review-translation\">{content}</target>")
+            }
+            (XliffVersion::V2_0, _) => format!("<target>{content}</target>"),
+        };
+        match &unit.target {
+            Some((range, _)) => edits.push((range.clone(), target)),
+            None => edits.push((
+                unit.insert_at..unit.insert_at,
+                format!("\n{}{target}", unit.indent),
+            )),
+        }
+        if self.version == XliffVersion::V2_0 {
+            // 2.0 keeps the state on the segment and has no review state, the
+            // substate marks segments that lost inline elements
+            let container = &self.tokens[unit.container];
+            match state {
+                TargetState::Translated => edits.push(set_attr(container, "state", "translated")),
+                TargetState::NeedsReview => {
+                    edits.push(set_attr(container, "state", "initial"));
+                    edits.push(set_attr(container, "subState", "aio:needs-review"));
+                }
+            }
+        }
+        edits
+    }
+}
+
+impl std::fmt::Display for XliffDocument {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+/// Elements whose content is native code rather than text.
+fn is_native_code(name: &str) -> bool {
+    matches!(name, "ph" | "bpt" | "ept" | "it" | "sub")
+}
+
+#[cfg(test)]
+mod tests {
+    use aio_translator_interface::Language;
+
+    use super::*;
+    use crate::OriginalTranslator;
+
+    const V1: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<xliff version="1.2" xmlns="urn:oasis:names:tc:xliff:document:1.2">
+  <file source-language="en-US" target-language="de" datatype="plaintext" original="ui">
+    <body>
+      <!-- greeting -->
+      <trans-unit id="1">
+        <source>Hello <g id="1">world</g> &amp; <x id="2"/>friends</source>
+      </trans-unit>
+      <trans-unit id="2" translate="no">
+        <source>Brand</source>
+      </trans-unit>
+      <trans-unit id="3">
+        <source>Click <ph id="1">&lt;b&gt;</ph>here</source>
+        <target/>
+      </trans-unit>
+    </body>
+  </file>
+</xliff>
+"#;
+
+    const V2: &str = r#"<xliff xmlns="urn:oasis:names:tc:xliff:document:2.0" version="2.0" srcLang="ja">
+ <file id="f1">
+  <unit id="u1">
+   <segment>
+    <source>こんにちは<pc id="1">世界</pc></source>
+   </segment>
+  </unit>
+ </file>
+</xliff>"#;
+
+    #[test]
+    fn round_trip() {
+        for raw in [V1, V2] {
+            let doc = XliffDocument::parse(raw).unwrap();
+            assert_eq!(doc.to_string(), raw);
+        }
+    }
+
+    #[test]
+    fn languages() {
+        let doc = XliffDocument::parse(V1).unwrap();
+        assert_eq!(doc.version(), XliffVersion::V1_2);
+        assert_eq!(doc.source_language(), Some(Language::English));
+        assert_eq!(doc.target_language(), Some(Language::German));
+        assert_eq!(doc.units().len(), 3);
+        assert_eq!(
+            doc.source_text(&doc.units()[0]),
+            "Hello {0}world{1} & {2}friends"
+        );
+        assert_eq!(doc.source_text(&doc.units()[2]), "Click {0}here");
+    }
+
+    #[tokio::test]
+    async fn translate_v1() {
+        let mut doc = XliffDocument::parse(V1).unwrap();
+        let report = doc
+            .translate(&mut OriginalTranslator::new(), None)
+            .await
+            .unwrap();
+        assert_eq!(report.translated, 2);
+        assert_eq!(report.skipped, 1);
+        let out = doc.to_string();
+        assert!(out.contains(
+            "friends</source>\n        <target state=\"translated\">Hello <g id=\"1\">world</g> &amp; <x id=\"2\"/>friends</target>"
+        ));
+        assert!(out.contains(
+            "<target state=\"translated\">Click <ph id=\"1\">&lt;b&gt;</ph>here</target>"
+        ));
+        assert!(!out.contains("<target/>"));
+    }
+
+    #[tokio::test]
+    async fn translate_v2() {
+        let mut doc = XliffDocument::parse(V2).unwrap();
+        doc.translate(&mut OriginalTranslator::new(), Some(Language::English))
+            .await
+            .unwrap();
+        let out = doc.to_string();
+        assert!(out.contains(r#"srcLang="ja" trgLang="en">"#));
+        assert!(out.contains(r#"<segment state="translated">"#));
+        assert!(out.contains("</source>\n    <target>こんにちは<pc id=\"1\">世界</pc></target>"));
+    }
+}
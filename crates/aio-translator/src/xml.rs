@@ -0,0 +1,215 @@
+//! Minimal XML tokenizer that keeps byte ranges, so documents can be edited in place.
+
+use std::ops::Range;
+
+use anyhow::bail;
+
+use crate::markup::{escape_xml_attr, unescape_xml};
+
+#[derive(Debug)]
+pub(crate) enum Node {
+    Start {
+        name: String,
+        attrs: Vec<Attr>,
+        empty: bool,
+    },
+    End {
+        name: String,
+    },
+    Text,
+    CData,
+    Other,
+}
+
+#[derive(Debug)]
+pub(crate) struct Attr {
+    pub name: String,
+    pub value: String,
+    /// absolute range of the value without quotes
+    pub range: Range<usize>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Token {
+    pub range: Range<usize>,
+    pub node: Node,
+}
+
+impl Token {
+    pub fn attr(&self, name: &str) -> Option<&Attr> {
+        match &self.node {
+            Node::Start { attrs, .. } => attrs.iter().find(|v| local_name(&v.name) == name),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+pub(crate) fn element_name(token: &Token) -> Option<&str> {
+    match &token.node {
+        Node::Start { name, .. } => Some(local_name(name)),
+        _ => None,
+    }
+}
+
+pub(crate) fn indent_before(raw: &str, pos: usize) -> String {
+    let line = &raw[..pos];
+    let line = &line[line.rfind('\n').map(|v| v + 1).unwrap_or(0)..];
+    match line.trim().is_empty() {
+        true => line.to_owned(),
+        false => String::new(),
+    }
+}
+
+/// Index of the token that closes the element started at `start`.
+pub(crate) fn element_end(tokens: &[Token], start: usize) -> usize {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(start) {
+        match token.node {
+            Node::Start { empty: false, .. } => depth += 1,
+            Node::End { .. } => depth -= 1,
+            _ => {}
+        }
+        if depth == 0 {
+            return i;
+        }
+    }
+    tokens.len() - 1
+}
+
+/// Replaces the value of `name` or inserts the attribute before the end of the tag.
+pub(crate) fn set_attr(token: &Token, name: &str, value: &str) -> (Range<usize>, String) {
+    match token.attr(name) {
+        Some(attr) => (attr.range.clone(), escape_xml_attr(value)),
+        None => {
+            let at = match token.node {
+                Node::Start { empty: true, .. } => token.range.end - 2,
+                _ => token.range.end - 1,
+            };
+            (at..at, format!(" {name}=\"{}\"", escape_xml_attr(value)))
+        }
+    }
+}
+
+pub(crate) fn tokenize(raw: &str) -> anyhow::Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < raw.len() {
+        let rest = &raw[i..];
+        if !rest.starts_with('<') {
+            let end = rest.find('<').map(|v| v + i).unwrap_or(raw.len());
+            tokens.push(Token {
+                range: i..end,
+                node: Node::Text,
+            });
+            i = end;
+            continue;
+        }
+        let (end, node) = if rest.starts_with("<!--") {
+            (find_end(rest, "-->", i)?, Node::Other)
+        } else if rest.starts_with("<![CDATA[") {
+            (find_end(rest, "]]>", i)?, Node::CData)
+        } else if rest.starts_with("<?") {
+            (find_end(rest, "?>", i)?, Node::Other)
+        } else if rest.starts_with("<!") {
+            (find_end(rest, ">", i)?, Node::Other)
+        } else {
+            parse_tag(raw, i)?
+        };
+        tokens.push(Token {
+            range: i..end,
+            node,
+        });
+        i = end;
+    }
+    Ok(tokens)
+}
+
+fn find_end(rest: &str, pat: &str, offset: usize) -> anyhow::Result<usize> {
+    match rest.find(pat) {
+        Some(v) => Ok(offset + v + pat.len()),
+        None => bail!("unterminated markup at byte {offset}"),
+    }
+}
+
+fn parse_tag(raw: &str, start: usize) -> anyhow::Result<(usize, Node)> {
+    let bytes = raw.as_bytes();
+    let closing = bytes.get(start + 1) == Some(&b'/');
+    let mut i = start + 1 + closing as usize;
+    let name_start = i;
+    while i < bytes.len() && !bytes[i].is_ascii_whitespace() && !matches!(bytes[i], b'/' | b'>') {
+        i += 1;
+    }
+    let name = raw[name_start..i].to_owned();
+    if name.is_empty() {
+        bail!("invalid tag at byte {start}");
+    }
+
+    let mut attrs = vec![];
+    loop {
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        match bytes.get(i) {
+            None => bail!("unterminated tag at byte {start}"),
+            Some(b'>') => {
+                let node = match closing {
+                    true => Node::End { name },
+                    false => Node::Start {
+                        name,
+                        attrs,
+                        empty: false,
+                    },
+                };
+                return Ok((i + 1, node));
+            }
+            Some(b'/') if bytes.get(i + 1) == Some(&b'>') => {
+                return Ok((
+                    i + 2,
+                    Node::Start {
+                        name,
+                        attrs,
+                        empty: true,
+                    },
+                ));
+            }
+            _ => {}
+        }
+        let attr_start = i;
+        while i < bytes.len()
+            && !matches!(bytes[i], b'=' | b'>' | b'/')
+            && !bytes[i].is_ascii_whitespace()
+        {
+            i += 1;
+        }
+        let attr_name = raw[attr_start..i].to_owned();
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if bytes.get(i) != Some(&b'=') {
+            bail!("attribute without value at byte {attr_start}");
+        }
+        i += 1;
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        let quote = match bytes.get(i) {
+            Some(q @ (b'"' | b'\'')) => *q,
+            _ => bail!("unquoted attribute at byte {i}"),
+        };
+        let value_start = i + 1;
+        let value_end = raw[value_start..]
+            .find(quote as char)
+            .map(|v| v + value_start)
+            .ok_or_else(|| anyhow::anyhow!("unterminated attribute at byte {i}"))?;
+        attrs.push(Attr {
+            name: attr_name,
+            value: unescape_xml(&raw[value_start..value_end]),
+            range: value_start..value_end,
+        });
+        i = value_end + 1;
+    }
+}
@@ -4,17 +4,18 @@ use aio_translator_interface::{
     AsyncTranslator, Language, TranslationListOutput, TranslationOutput, Translator,
     TranslatorMutTrait, TranslatorTrait,
     error::{ApiError, Error},
+    http::{Http, HttpOptions},
     prompt::PromptBuilder,
 };
 use async_trait::async_trait;
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+pub const BASE_URL: &str = "https://fanyi-api.baidu.com";
+
 pub struct BaiduTranslator {
-    url: String,
+    http: Http,
     app_id: String,
     key: String,
-    client: Client,
 }
 
 impl Translator for BaiduTranslator {
@@ -47,8 +48,8 @@ impl AsyncTranslator for BaiduTranslator {
         };
         let form = Form::new(&self.app_id, query, "0", &self.key, from, to);
         let resp: Response = self
-            .client
-            .post(&self.url)
+            .http
+            .post("/api/trans/vip/translate")
             .form(&form)
             .send()
             .await?
@@ -95,12 +96,19 @@ impl AsyncTranslator for BaiduTranslator {
 impl BaiduTranslator {
     pub fn new(app_id: &str, key: &str) -> Self {
         Self {
-            url: "https://fanyi-api.baidu.com/api/trans/vip/translate".to_string(),
+            http: Http::new(BASE_URL),
             app_id: app_id.to_string(),
             key: key.to_string(),
-            client: Client::new(),
         }
     }
+
+    pub fn with_options(app_id: &str, key: &str, options: HttpOptions) -> Result<Self, Error> {
+        Ok(Self {
+            http: options.build(BASE_URL)?,
+            app_id: app_id.to_string(),
+            key: key.to_string(),
+        })
+    }
 }
 
 /// The data submitted by the form
@@ -226,14 +234,56 @@ mod tests {
 
     use std::collections::HashSet;
 
-    use aio_translator_interface::Language;
+    use aio_translator_interface::{
+        Language, Translator as _,
+        error::{ApiError, Error},
+    };
+    use aio_translator_mock::{APP_ID, Api, Fault, MockServer, SECRET};
 
     use crate::BaiduTranslator;
 
     #[tokio::test]
     async fn translate() {
-        //TODO:
-        BaiduTranslator::new("", "");
+        let server = MockServer::start().await;
+        let trans = BaiduTranslator::with_options(APP_ID, SECRET, server.options(Api::Baidu))
+            .expect("Failed to create translator");
+        let trans = trans.translator();
+        let trans = trans.as_async().expect("Failed to create async translator");
+        let trans = trans
+            .translate_vec(
+                &["Hello World".to_owned(), "This is a test".to_owned()],
+                None,
+                None,
+                &Language::German,
+            )
+            .await
+            .expect("Failed to translate");
+
+        assert_eq!(trans.text, ["Hallo Welt", "Das ist ein Test."]);
+    }
+
+    #[tokio::test]
+    async fn error_codes() {
+        let server = MockServer::start().await;
+        let code = |e: anyhow::Error| match e.downcast::<Error>() {
+            Ok(Error::ApiError(ApiError::Baidu { code, .. })) => code,
+            e => panic!("unexpected error {e:?}"),
+        };
+
+        let trans =
+            BaiduTranslator::with_options(APP_ID, "wrong", server.options(Api::Baidu)).unwrap();
+        let trans = trans.translator();
+        let trans = trans.as_async().unwrap();
+        let e = trans.translate("a", None, None, &Language::German).await;
+        assert_eq!(code(e.unwrap_err()), "54001");
+
+        server.fail(Api::Baidu, Fault::baidu("54003"));
+        let trans =
+            BaiduTranslator::with_options(APP_ID, SECRET, server.options(Api::Baidu)).unwrap();
+        let trans = trans.translator();
+        let trans = trans.as_async().unwrap();
+        let e = trans.translate("a", None, None, &Language::German).await;
+        assert_eq!(code(e.unwrap_err()), "54003");
     }
 
     #[test]
@@ -1,9 +1,11 @@
 use aio_translator_interface::{
     AsyncTranslator, Language, TranslationListOutput, TranslationOutput, Translator,
-    TranslatorMutTrait, TranslatorTrait, error::Error, prompt::PromptBuilder,
+    TranslatorMutTrait, TranslatorTrait,
+    error::Error,
+    http::{Http, HttpOptions},
+    prompt::PromptBuilder,
 };
 
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize)]
@@ -20,8 +22,10 @@ struct CaiyunResponse {
     target: Option<Vec<String>>,
 }
 
+pub const BASE_URL: &str = "https://api.interpreter.caiyunai.com";
+
 pub struct CaiyunTranslator {
-    client: Client,
+    http: Http,
     token: String,
     request_id: String,
 }
@@ -69,8 +73,8 @@ impl AsyncTranslator for CaiyunTranslator {
             request_id: &self.request_id,
         };
         let data: CaiyunResponse = self
-            .client
-            .post("https://api.interpreter.caiyunai.com/v1/translator")
+            .http
+            .post("/v1/translator")
             .header("content-type", "application/json")
             .header("x-authorization", format!("token {}", self.token))
             .json(&request)
@@ -88,11 +92,23 @@ impl AsyncTranslator for CaiyunTranslator {
 impl CaiyunTranslator {
     pub fn new(token: String, request_id: String) -> Self {
         Self {
-            client: Client::new(),
+            http: Http::new(BASE_URL),
             token,
             request_id,
         }
     }
+
+    pub fn with_options(
+        token: String,
+        request_id: String,
+        options: HttpOptions,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            http: options.build(BASE_URL)?,
+            token,
+            request_id,
+        })
+    }
 }
 
 impl Translator for CaiyunTranslator {
@@ -112,12 +128,21 @@ impl Translator for CaiyunTranslator {
 #[cfg(test)]
 mod tests {
     use aio_translator_interface::{Language, Translator as _};
+    use aio_translator_mock::{Api, KEY, MockServer};
 
     use crate::CaiyunTranslator;
 
+    fn translator(server: &MockServer) -> CaiyunTranslator {
+        CaiyunTranslator::with_options(
+            KEY.to_owned(),
+            "demo".to_string(),
+            server.options(Api::Caiyun),
+        )
+        .expect("Failed to create translator")
+    }
+
     #[tokio::test]
     async fn all_langauges_available() {
-        dotenv::dotenv().ok();
         //TODO: Arabic, Greek, Indonesian, Polish, Swahili, Thai,
         let langs = vec![
             "zh", "zh-Hant", "en", "ja", "ko", "de", "fr", "es", "it", "pt", "ru", "tr", "vi",
@@ -130,9 +155,8 @@ mod tests {
 
     #[tokio::test]
     async fn translate_unknown() {
-        dotenv::dotenv().ok();
-        let auth = std::env::var("CAIYUN_TOKEN").expect("CAIYUN_TOKEN not set");
-        let trans = CaiyunTranslator::new(auth, "demo".to_string());
+        let server = MockServer::start().await;
+        let trans = translator(&server);
         let trans = trans.translator();
         let trans = trans.as_async().expect("Failed to create async translator");
         let trans = trans
@@ -142,13 +166,13 @@ mod tests {
 
         assert_eq!(trans.lang, None);
         assert_eq!(trans.text, "Hallo Welt");
+        assert!(server.requests(Api::Caiyun)[0].body.contains("auto2de"));
     }
 
     #[tokio::test]
     async fn translate_known() {
-        dotenv::dotenv().ok();
-        let auth = std::env::var("CAIYUN_TOKEN").expect("CAIYUN_TOKEN not set");
-        let trans = CaiyunTranslator::new(auth, "demo".to_string());
+        let server = MockServer::start().await;
+        let trans = translator(&server);
         let trans = trans.translator();
         let trans = trans.as_async().expect("Failed to create async translator");
         let trans = trans
@@ -2,11 +2,13 @@ use std::collections::HashMap;
 
 use aio_translator_interface::{
     AsyncTranslator, Language, TranslationListOutput, TranslationOutput, Translator,
-    TranslatorMutTrait, TranslatorTrait, error::Error, prompt::PromptBuilder,
+    TranslatorMutTrait, TranslatorTrait,
+    error::Error,
+    http::{Http, HttpOptions},
+    prompt::PromptBuilder,
 };
 
 use anyhow::bail;
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
@@ -24,18 +26,35 @@ fn most_common_string(strings: &[String]) -> Option<String> {
         .cloned()
 }
 
+/// Endpoint of DeepL API Free keys
+pub const FREE_URL: &str = "https://api-free.deepl.com";
+/// Endpoint of DeepL API Pro keys
+pub const PRO_URL: &str = "https://api.deepl.com";
+
 pub struct DeeplTranslator {
-    client: Client,
+    http: Http,
     auth: String,
 }
 
 impl DeeplTranslator {
     pub fn new(auth: String) -> Self {
         Self {
-            client: Default::default(),
+            http: Http::new(FREE_URL),
             auth,
         }
     }
+
+    /// Uses [`FREE_URL`] unless `options` has a base url, pass [`PRO_URL`] for pro keys.
+    pub fn with_options(auth: String, options: HttpOptions) -> Result<Self, Error> {
+        Ok(Self {
+            http: options.build(FREE_URL)?,
+            auth,
+        })
+    }
+
+    pub async fn languages(&self) -> anyhow::Result<Vec<String>> {
+        languages(&self.http, &self.auth).await
+    }
 }
 
 impl Translator for DeeplTranslator {
@@ -76,7 +95,33 @@ impl AsyncTranslator for DeeplTranslator {
         from: Option<Language>,
         to: &Language,
     ) -> anyhow::Result<TranslationListOutput> {
-        let body = match from {
+        self.request(query, from, to, false).await
+    }
+
+    fn handles_html(&self) -> bool {
+        true
+    }
+
+    async fn translate_html_vec(
+        &self,
+        query: &[String],
+        _: Option<PromptBuilder>,
+        from: Option<Language>,
+        to: &Language,
+    ) -> anyhow::Result<TranslationListOutput> {
+        self.request(query, from, to, true).await
+    }
+}
+
+impl DeeplTranslator {
+    async fn request(
+        &self,
+        query: &[String],
+        from: Option<Language>,
+        to: &Language,
+        html: bool,
+    ) -> anyhow::Result<TranslationListOutput> {
+        let mut body = match from {
             Some(s) => json!({"text": query,
                 "source_lang": s.to_deepl(),
                 "target_lang": to.to_deepl()
@@ -84,15 +129,20 @@ impl AsyncTranslator for DeeplTranslator {
             None => json!({"text": query,
                 "target_lang": to.to_deepl()}),
         };
-        let request: Root1 = self
-            .client
-            .post("https://api-free.deepl.com/v2/translate")
+        if html {
+            body["tag_handling"] = json!("html");
+        }
+        let response = self
+            .http
+            .post("/v2/translate")
             .header("Authorization", format!("DeepL-Auth-Key {}", self.auth))
             .json(&body)
             .send()
-            .await?
-            .json()
             .await?;
+        if !response.status().is_success() {
+            Err(Error::RequestFailed(response.status().as_u16()))?;
+        }
+        let request: Root1 = response.json().await?;
         let (texts, langs): (Vec<String>, Vec<String>) = request
             .translations
             .into_iter()
@@ -108,9 +158,12 @@ impl AsyncTranslator for DeeplTranslator {
 }
 
 pub async fn get_languages(auth: &String) -> anyhow::Result<Vec<String>> {
-    let client = Client::new();
-    let response = client
-        .get("https://api-free.deepl.com/v2/languages?type=source")
+    languages(&Http::new(FREE_URL), auth).await
+}
+
+async fn languages(http: &Http, auth: &str) -> anyhow::Result<Vec<String>> {
+    let response = http
+        .get("/v2/languages?type=source")
         .header("Authorization", format!("DeepL-Auth-Key {}", auth))
         .header("accept", "application/json")
         .send()
@@ -147,15 +200,22 @@ struct Root1 {
 
 #[cfg(test)]
 mod tests {
+    use aio_translator_interface::error::Error;
     use aio_translator_interface::{Language, Translator as _};
+    use aio_translator_mock::{Api, Fault, Fixture, KEY, MockServer};
+
+    use crate::{DeeplTranslator, FREE_URL};
 
-    use crate::{DeeplTranslator, get_languages};
+    fn translator(server: &MockServer) -> DeeplTranslator {
+        DeeplTranslator::with_options(KEY.to_owned(), server.options(Api::Deepl))
+            .expect("Failed to create translator")
+    }
 
     #[tokio::test]
     async fn all_langauges_available() {
-        dotenv::dotenv().ok();
-        let auth = std::env::var("DEEPL_API_KEY").expect("DEEPL_API_KEY not set");
-        let langs = get_languages(&auth)
+        let server = MockServer::start().await;
+        let langs = translator(&server)
+            .languages()
             .await
             .expect("Failed to fetch languages");
         assert!(langs.len() > 0);
@@ -166,9 +226,8 @@ mod tests {
 
     #[tokio::test]
     async fn translate_unknown() {
-        dotenv::dotenv().ok();
-        let auth = std::env::var("DEEPL_API_KEY").expect("DEEPL_API_KEY not set");
-        let trans = DeeplTranslator::new(auth);
+        let server = MockServer::start().await;
+        let trans = translator(&server);
         let trans = trans.translator();
         let trans = trans.as_async().expect("Failed to create async translator");
         let trans = trans
@@ -182,9 +241,8 @@ mod tests {
 
     #[tokio::test]
     async fn translate_known() {
-        dotenv::dotenv().ok();
-        let auth = std::env::var("DEEPL_API_KEY").expect("DEEPL_API_KEY not set");
-        let trans = DeeplTranslator::new(auth);
+        let server = MockServer::start().await;
+        let trans = translator(&server);
         let trans = trans.translator();
         let trans = trans.as_async().expect("Failed to create async translator");
         let trans = trans
@@ -199,5 +257,83 @@ mod tests {
 
         assert_eq!(trans.lang, Some(Language::English));
         assert_eq!(trans.text, "Hallo Welt");
+        let request = &server.requests(Api::Deepl)[0];
+        assert!(request.body.contains(r#""source_lang":"EN""#));
+    }
+
+    #[tokio::test]
+    async fn failures() {
+        let server = MockServer::start().await;
+        let wrong_key =
+            DeeplTranslator::with_options("wrong".to_owned(), server.options(Api::Deepl)).unwrap();
+        let trans = wrong_key.translator();
+        let trans = trans.as_async().unwrap();
+        assert!(
+            trans
+                .translate("a", None, None, &Language::German)
+                .await
+                .is_err()
+        );
+
+        server.fail(Api::Deepl, Fault::Status(503));
+        let trans = translator(&server);
+        let trans = trans.translator();
+        let trans = trans.as_async().unwrap();
+        assert!(
+            trans
+                .translate("a", None, None, &Language::German)
+                .await
+                .is_err()
+        );
+        let out = trans.translate("a", None, None, &Language::German).await;
+        assert_eq!(out.unwrap().text, "[DE] a");
+    }
+
+    #[tokio::test]
+    async fn replay_translate() {
+        let fixture = Fixture::start(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/translate.json"),
+            FREE_URL,
+        )
+        .await;
+        let trans =
+            DeeplTranslator::with_options(fixture.credential("DEEPL_API_KEY"), fixture.options())
+                .unwrap();
+        let trans = trans.translator();
+        let trans = trans.as_async().unwrap();
+        let trans = trans
+            .translate_vec(
+                &["Hello World".to_owned(), "It's \"quoted\"".to_owned()],
+                None,
+                Some(Language::English),
+                &Language::German,
+            )
+            .await
+            .expect("Failed to translate");
+
+        assert_eq!(trans.lang, Some(Language::English));
+        assert_eq!(trans.text, ["Hallo Welt", "Es ist „zitiert“"]);
+    }
+
+    #[tokio::test]
+    async fn replay_quota_exceeded() {
+        let fixture = Fixture::start(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/quota.json"),
+            FREE_URL,
+        )
+        .await;
+        let trans =
+            DeeplTranslator::with_options(fixture.credential("DEEPL_API_KEY"), fixture.options())
+                .unwrap();
+        let trans = trans.translator();
+        let trans = trans.as_async().unwrap();
+        let err = trans
+            .translate("Hello World", None, None, &Language::German)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::RequestFailed(456))
+        ));
     }
 }
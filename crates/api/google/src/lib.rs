@@ -1,13 +1,17 @@
 use aio_translator_interface::{
     AsyncTranslator, Language, TranslationListOutput, TranslationOutput, Translator,
-    TranslatorMutTrait, TranslatorTrait, error::Error, prompt::PromptBuilder,
+    TranslatorMutTrait, TranslatorTrait,
+    error::Error,
+    http::{Http, HttpOptions},
+    prompt::PromptBuilder,
 };
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
+pub const BASE_URL: &str = "https://translation.googleapis.com";
+
 pub struct GoogleTranslator {
-    client: Client,
+    http: Http,
     api_key: String,
 }
 
@@ -63,11 +67,8 @@ impl AsyncTranslator for GoogleTranslator {
         to: &Language,
     ) -> anyhow::Result<TranslationListOutput> {
         let resp: Root1 = self
-            .client
-            .post(format!(
-                "https://translation.googleapis.com/language/translate/v2?key={}",
-                self.api_key
-            ))
+            .http
+            .post(&format!("/language/translate/v2?key={}", self.api_key))
             .json(&match from {
                 Some(source) => {
                     json!({"q": query, "source": source.to_google().ok_or(Error::UnknownLanguage(source))?, "target": to.to_google().ok_or(Error::UnknownLanguage(*to))?, "format": "text"})
@@ -93,16 +94,23 @@ impl AsyncTranslator for GoogleTranslator {
 impl GoogleTranslator {
     pub fn new(api_key: String) -> Self {
         Self {
-            client: Client::new(),
+            http: Http::new(BASE_URL),
             api_key,
         }
     }
 
+    pub fn with_options(api_key: String, options: HttpOptions) -> Result<Self, Error> {
+        Ok(Self {
+            http: options.build(BASE_URL)?,
+            api_key,
+        })
+    }
+
     pub async fn languages(&self) -> Result<Vec<String>, reqwest::Error> {
         let langs: Langs = self
-            .client
-            .get(format!(
-                "https://translation.googleapis.com/language/translate/v2/languages?key={}",
+            .http
+            .get(&format!(
+                "/language/translate/v2/languages?key={}",
                 self.api_key
             ))
             .send()
@@ -136,14 +144,19 @@ struct Root1 {
 #[cfg(test)]
 mod tests {
     use aio_translator_interface::{Language, Translator as _};
+    use aio_translator_mock::{Api, Fixture, KEY, MockServer};
+
+    use crate::{BASE_URL, GoogleTranslator};
 
-    use crate::GoogleTranslator;
+    fn translator(server: &MockServer) -> GoogleTranslator {
+        GoogleTranslator::with_options(KEY.to_owned(), server.options(Api::Google))
+            .expect("Failed to create translator")
+    }
 
     #[tokio::test]
     async fn all_langauges_available() {
-        dotenv::dotenv().ok();
-        let auth = std::env::var("GOOGLE_API_KEY").expect("GOOGLE_API_KEY not set");
-        let trans = GoogleTranslator::new(auth);
+        let server = MockServer::start().await;
+        let trans = translator(&server);
 
         let langs = trans.languages().await.expect("Failed to fetch languages");
         assert!(langs.len() > 0);
@@ -163,9 +176,8 @@ mod tests {
 
     #[tokio::test]
     async fn translate_unknown() {
-        dotenv::dotenv().ok();
-        let auth = std::env::var("GOOGLE_API_KEY").expect("GOOGLE_API_KEY not set");
-        let trans = GoogleTranslator::new(auth);
+        let server = MockServer::start().await;
+        let trans = translator(&server);
         let trans = trans.translator();
         let trans = trans.as_async().expect("Failed to create async translator");
         let trans = trans
@@ -179,9 +191,8 @@ mod tests {
 
     #[tokio::test]
     async fn translate_known() {
-        dotenv::dotenv().ok();
-        let auth = std::env::var("GOOGLE_API_KEY").expect("GOOGLE_API_KEY not set");
-        let trans = GoogleTranslator::new(auth);
+        let server = MockServer::start().await;
+        let trans = translator(&server);
         let trans = trans.translator();
         let trans = trans.as_async().expect("Failed to create async translator");
         let trans = trans
@@ -197,4 +208,37 @@ mod tests {
         assert_eq!(trans.lang, None);
         assert_eq!(trans.text, "Hallo Welt");
     }
+
+    #[tokio::test]
+    async fn wrong_key() {
+        let server = MockServer::start().await;
+        let trans = GoogleTranslator::with_options("wrong".to_owned(), server.options(Api::Google))
+            .unwrap();
+        assert!(trans.languages().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn replay_translate() {
+        let fixture = Fixture::start(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/translate.json"),
+            BASE_URL,
+        )
+        .await;
+        let trans =
+            GoogleTranslator::with_options(fixture.credential("GOOGLE_API_KEY"), fixture.options())
+                .unwrap();
+        let trans = trans.translator();
+        let trans = trans.as_async().unwrap();
+        let trans = trans
+            .translate_vec(
+                &["Hello World".to_owned(), "It's a test".to_owned()],
+                None,
+                None,
+                &Language::German,
+            )
+            .await
+            .expect("Failed to translate");
+
+        assert_eq!(trans.text, ["Hallo Welt", "Es ist ein Test"]);
+    }
 }
@@ -1,19 +1,22 @@
 use aio_translator_interface::{
     AsyncTranslator, Language, TranslationListOutput, TranslationOutput, Translator,
-    TranslatorMutTrait, TranslatorTrait, error::Error, prompt::PromptBuilder,
+    TranslatorMutTrait, TranslatorTrait,
+    error::Error,
+    http::{Http, HttpOptions},
+    prompt::PromptBuilder,
 };
 
-use reqwest::{Client, header::REFERER};
+use reqwest::header::REFERER;
 use serde_json::Value;
 
 pub struct MyMemoryTranslator {
     /// how long the text to translate can be
     input_limit: u32,
-    /// host url
-    host: String,
-    client: Client,
+    http: Http,
 }
 
+pub const BASE_URL: &str = "https://api.mymemory.translated.net";
+
 /// default value
 impl Default for MyMemoryTranslator {
     /// new is default
@@ -59,15 +62,14 @@ impl AsyncTranslator for MyMemoryTranslator {
             None => "Autodetect",
         };
         let url = format!(
-            "{}?q={}&langpair={}|{}",
-            self.host,
+            "/get?q={}&langpair={}|{}",
             query,
             from,
             to.to_mymemory().ok_or(Error::UnknownLanguage(*to))?
         );
 
         let response = self
-            .client
+            .http
             .get(&url)
             .header(REFERER, "https://mymemory.translated.net")
             .send()
@@ -77,22 +79,28 @@ impl AsyncTranslator for MyMemoryTranslator {
             unreachable!()
         }
         let resp: Value = response.json().await?;
-        let resp = &resp["responseData"];
-        let lang = resp["detectedLanguage"].to_string();
-        let mut text = resp["translatedText"].to_string();
-        if text == "null" {
-            Err(Error::NoResponse)?;
-            unreachable!()
-        }
-        if text.starts_with('"') && text.ends_with('"') {
-            text = text[1..text.len() - 1].to_string();
+        // errors like an exhausted quota come with status 200, the message is the translation
+        let status = match &resp["responseStatus"] {
+            Value::String(v) => v.parse().unwrap_or_default(),
+            v => v.as_u64().unwrap_or(200),
+        };
+        if status != 200 {
+            Err(Error::RequestFailed(status as u16))?;
         }
+        let resp = &resp["responseData"];
+        let text = resp["translatedText"]
+            .as_str()
+            .ok_or(Error::NoResponse)?
+            .to_owned();
         Ok(TranslationOutput {
             text,
             lang: Some(match from_orig {
                 Some(s) => s,
-                None => Language::from_mymemory_short(&lang.replace("\"", ""))
-                    .ok_or(Error::CouldNotMapLanguage(Some(lang)))?,
+                None => {
+                    let lang = resp["detectedLanguage"].as_str();
+                    lang.and_then(Language::from_mymemory_short)
+                        .ok_or(Error::CouldNotMapLanguage(lang.map(|v| v.to_owned())))?
+                }
             }),
         })
     }
@@ -119,25 +127,37 @@ impl AsyncTranslator for MyMemoryTranslator {
 impl MyMemoryTranslator {
     pub fn new() -> Self {
         MyMemoryTranslator {
-            client: Default::default(),
+            http: Http::new(BASE_URL),
             input_limit: 500,
-            host: "https://api.mymemory.translated.net/get".to_string(),
         }
     }
+
+    pub fn with_options(options: HttpOptions) -> Result<Self, Error> {
+        Ok(MyMemoryTranslator {
+            http: options.build(BASE_URL)?,
+            input_limit: 500,
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use aio_translator_interface::{Language, Translator as _};
+    use aio_translator_interface::{Language, Translator as _, error::Error};
+    use aio_translator_mock::{Api, Fixture, MockServer};
     use reqwest::Client;
     use scraper::{Html, Selector};
 
-    use crate::MyMemoryTranslator;
+    use crate::{BASE_URL, MyMemoryTranslator};
+
+    fn translator(server: &MockServer) -> MyMemoryTranslator {
+        MyMemoryTranslator::with_options(server.options(Api::MyMemory))
+            .expect("Failed to create translator")
+    }
 
     #[tokio::test]
     async fn translate_unknown() {
-        dotenv::dotenv().ok();
-        let trans = MyMemoryTranslator::new();
+        let server = MockServer::start().await;
+        let trans = translator(&server);
         let trans = trans.translator();
         let trans = trans.as_async().expect("Failed to create async translator");
         let trans = trans
@@ -149,10 +169,10 @@ mod tests {
         assert_eq!(trans.text, "Hallo Welt");
     }
 
-    pub async fn get_languages() -> Vec<String> {
+    pub async fn get_languages(url: &str) -> Vec<String> {
         let client = Client::new();
         let data = client
-            .get("https://mymemory.translated.net")
+            .get(url)
             .send()
             .await
             .expect("Failed to send request");
@@ -171,7 +191,8 @@ mod tests {
 
     #[tokio::test]
     async fn all_langauges_available() {
-        let langs = get_languages().await;
+        let server = MockServer::start().await;
+        let langs = get_languages(&format!("{}/", server.url(Api::MyMemory))).await;
         assert!(langs.len() > 0);
         for lang in langs {
             if lang.as_str() == "Autodetect" {
@@ -187,8 +208,8 @@ mod tests {
 
     #[tokio::test]
     async fn translate_known() {
-        dotenv::dotenv().ok();
-        let trans = MyMemoryTranslator::new();
+        let server = MockServer::start().await;
+        let trans = translator(&server);
         let trans = trans.translator();
         let trans = trans.as_async().expect("Failed to create async translator");
         let trans = trans
@@ -204,4 +225,71 @@ mod tests {
         assert_eq!(trans.lang, Some(Language::English));
         assert_eq!(trans.text, "Hallo Welt");
     }
+
+    #[tokio::test]
+    async fn translate_vec() {
+        let server = MockServer::start().await;
+        let trans = translator(&server);
+        let trans = trans.translator();
+        let trans = trans.as_async().unwrap();
+        let trans = trans
+            .translate_vec(
+                &["Hello World".to_owned(), "This is a test".to_owned()],
+                None,
+                Some(Language::English),
+                &Language::German,
+            )
+            .await
+            .expect("Failed to translate");
+
+        assert_eq!(trans.text, ["Hallo Welt", "Das ist ein Test."]);
+    }
+
+    #[tokio::test]
+    async fn replay_quoted() {
+        let fixture = Fixture::start(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/quoted.json"),
+            BASE_URL,
+        )
+        .await;
+        let trans = MyMemoryTranslator::with_options(fixture.options()).unwrap();
+        let trans = trans.translator();
+        let trans = trans.as_async().unwrap();
+        let trans = trans
+            .translate(
+                "\"Hello World\"",
+                None,
+                Some(Language::English),
+                &Language::German,
+            )
+            .await
+            .expect("Failed to translate");
+
+        assert_eq!(trans.text, "\"Hallo Welt\"");
+    }
+
+    #[tokio::test]
+    async fn replay_quota_exceeded() {
+        let fixture = Fixture::start(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/quota.json"),
+            BASE_URL,
+        )
+        .await;
+        let trans = MyMemoryTranslator::with_options(fixture.options()).unwrap();
+        let trans = trans.translator();
+        let trans = trans.as_async().unwrap();
+        let err = trans
+            .translate(
+                "Hello World",
+                None,
+                Some(Language::English),
+                &Language::German,
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::RequestFailed(429))
+        ));
+    }
 }
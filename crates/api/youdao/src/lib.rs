@@ -2,16 +2,21 @@ use std::time::{SystemTime, UNIX_EPOCH};
 
 use aio_translator_interface::{
     AsyncTranslator, Language, TranslationListOutput, TranslationOutput, Translator,
-    TranslatorMutTrait, TranslatorTrait, error::Error, prompt::PromptBuilder,
+    TranslatorMutTrait, TranslatorTrait,
+    error::Error,
+    http::{Http, HttpOptions},
+    prompt::PromptBuilder,
 };
 use rand::Rng as _;
-use reqwest::{Client, header::CONTENT_TYPE};
+use reqwest::header::CONTENT_TYPE;
 use serde::Deserialize;
 use sha2::{Digest, Sha256};
 use uuid::{Context, Timestamp, Uuid};
 
+pub const BASE_URL: &str = "https://openapi.youdao.com";
+
 pub struct YoudaoTranslator {
-    client: reqwest::Client,
+    http: Http,
     app_key: String,
     app_secret: String,
     context: Context,
@@ -31,10 +36,26 @@ fn generate_random_mac() -> [u8; 6] {
 
 impl YoudaoTranslator {
     pub fn new(app_key: String, app_secret: String) -> Self {
+        Self::with_http(app_key, app_secret, Http::new(BASE_URL))
+    }
+
+    pub fn with_options(
+        app_key: String,
+        app_secret: String,
+        options: HttpOptions,
+    ) -> Result<Self, Error> {
+        Ok(Self::with_http(
+            app_key,
+            app_secret,
+            options.build(BASE_URL)?,
+        ))
+    }
+
+    fn with_http(app_key: String, app_secret: String, http: Http) -> Self {
         let seed: u16 = rand::thread_rng().random();
         Self {
             mac: generate_random_mac(),
-            client: Client::new(),
+            http,
             app_key,
             app_secret,
             context: Context::new(seed),
@@ -107,8 +128,8 @@ impl AsyncTranslator for YoudaoTranslator {
             None => "auto",
         };
         let data: Resp = self
-            .client
-            .post("https://openapi.youdao.com/api")
+            .http
+            .post("/api")
             .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
             .form(&vec![
                 ("from", from),
@@ -154,6 +175,7 @@ fn truncate(s: &str) -> String {
 #[cfg(test)]
 mod tests {
     use aio_translator_interface::{Language, Translator as _};
+    use aio_translator_mock::{APP_ID, Api, MockServer, SECRET};
 
     use crate::YoudaoTranslator;
 
@@ -179,10 +201,13 @@ mod tests {
 
     #[tokio::test]
     async fn translate_unknown() {
-        dotenv::dotenv().ok();
-        let key = std::env::var("YOUDAO_APP_KEY").expect("YOUDAO_APP_KEY not set");
-        let secret = std::env::var("YOUDAO_APP_SECRET").expect("YOUDAO_APP_SECRET not set");
-        let trans = YoudaoTranslator::new(key, secret);
+        let server = MockServer::start().await;
+        let trans = YoudaoTranslator::with_options(
+            APP_ID.to_owned(),
+            SECRET.to_owned(),
+            server.options(Api::Youdao),
+        )
+        .expect("Failed to create translator");
         let trans = trans.translator();
         let trans = trans.as_async().expect("Failed to create async translator");
         let trans = trans
@@ -196,10 +221,13 @@ mod tests {
 
     #[tokio::test]
     async fn translate_known() {
-        dotenv::dotenv().ok();
-        let key = std::env::var("YOUDAO_APP_KEY").expect("YOUDAO_APP_KEY not set");
-        let secret = std::env::var("YOUDAO_APP_SECRET").expect("YOUDAO_APP_SECRET not set");
-        let trans = YoudaoTranslator::new(key, secret);
+        let server = MockServer::start().await;
+        let trans = YoudaoTranslator::with_options(
+            APP_ID.to_owned(),
+            SECRET.to_owned(),
+            server.options(Api::Youdao),
+        )
+        .expect("Failed to create translator");
         let trans = trans.translator();
         let trans = trans.as_async().expect("Failed to create async translator");
         let trans = trans
@@ -215,4 +243,49 @@ mod tests {
         assert_eq!(trans.lang, None);
         assert_eq!(trans.text, "Hallo Welt");
     }
+
+    #[tokio::test]
+    async fn long_query_signature() {
+        let server = MockServer::start().await;
+        let trans = YoudaoTranslator::with_options(
+            APP_ID.to_owned(),
+            SECRET.to_owned(),
+            server.options(Api::Youdao),
+        )
+        .unwrap();
+        let trans = trans.translator();
+        let trans = trans.as_async().unwrap();
+        let trans = trans
+            .translate(
+                "This text is longer than twenty characters",
+                None,
+                Some(Language::English),
+                &Language::German,
+            )
+            .await
+            .expect("Failed to translate");
+        assert_eq!(
+            trans.text,
+            "[de] This text is longer than twenty characters"
+        );
+    }
+
+    #[tokio::test]
+    async fn wrong_secret() {
+        let server = MockServer::start().await;
+        let trans = YoudaoTranslator::with_options(
+            APP_ID.to_owned(),
+            "wrong".to_owned(),
+            server.options(Api::Youdao),
+        )
+        .unwrap();
+        let trans = trans.translator();
+        let trans = trans.as_async().unwrap();
+        assert!(
+            trans
+                .translate("Hello World", None, None, &Language::German)
+                .await
+                .is_err()
+        );
+    }
 }
@@ -0,0 +1,203 @@
+use aio_translator::{
+    BaiduTranslator, CaiyunTranslator, ComputeType, DeeplTranslator, GoogleTranslator,
+    JParaCrawlSize, JParaCrawlTranslator, Language, M2M100Size, M2M100Translator,
+    MBart50Translator, MyMemoryTranslator, NLLBSize, NLLBTranslator, NoneTranslator,
+    OriginalTranslator, PapagoTranslator, SugoiTranslator, Translator, YoudaoTranslator,
+};
+use anyhow::{Context, bail};
+use clap::{Args, ValueEnum};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Backend {
+    #[value(name = "sugoi")]
+    Sugoi,
+    #[value(name = "jparacrawl")]
+    JParaCrawl,
+    #[value(name = "m2m100")]
+    M2M100,
+    #[value(name = "mbart50")]
+    MBart50,
+    #[value(name = "nllb")]
+    Nllb,
+    #[value(name = "deepl")]
+    Deepl,
+    #[value(name = "google")]
+    Google,
+    #[value(name = "mymemory")]
+    MyMemory,
+    #[value(name = "baidu")]
+    Baidu,
+    #[value(name = "caiyun")]
+    Caiyun,
+    #[value(name = "youdao")]
+    Youdao,
+    #[value(name = "papago")]
+    Papago,
+    /// Returns empty strings
+    #[value(name = "none")]
+    Empty,
+    /// Returns the input
+    #[value(name = "original")]
+    Original,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Size {
+    Small,
+    Base,
+    Large,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Compute {
+    #[default]
+    Default,
+    Auto,
+    Float32,
+    Float16,
+    Int16,
+    Int8,
+    #[value(name = "int8_float16")]
+    Int8Float16,
+}
+
+/// Settings of the offline backends.
+#[derive(Debug, Clone, Default, Args)]
+pub struct BackendOptions {
+    /// Run offline models on the GPU
+    #[arg(long)]
+    pub cuda: bool,
+    #[arg(long, value_enum, default_value = "default")]
+    pub compute_type: Compute,
+    /// Model size of jparacrawl, m2m100 and nllb
+    #[arg(long, value_enum)]
+    pub size: Option<Size>,
+    /// Honorific speech for papago
+    #[arg(long)]
+    pub honorific: bool,
+}
+
+impl BackendOptions {
+    fn compute_type(&self) -> ComputeType {
+        match self.compute_type {
+            Compute::Default => ComputeType::DEFAULT,
+            Compute::Auto => ComputeType::AUTO,
+            Compute::Float32 => ComputeType::FLOAT32,
+            Compute::Float16 => ComputeType::FLOAT16,
+            Compute::Int16 => ComputeType::INT16,
+            Compute::Int8 => ComputeType::INT8,
+            Compute::Int8Float16 => ComputeType::INT8_FLOAT16,
+        }
+    }
+
+    pub fn sugoi(&self) -> SugoiTranslator {
+        SugoiTranslator::new(self.cuda, self.compute_type())
+    }
+
+    pub fn jparacrawl(&self) -> JParaCrawlTranslator {
+        let size = match self.size.unwrap_or(Size::Base) {
+            Size::Small => JParaCrawlSize::Small,
+            Size::Base => JParaCrawlSize::Base,
+            Size::Large => JParaCrawlSize::Large,
+        };
+        JParaCrawlTranslator::new(true, self.cuda, self.compute_type(), size)
+    }
+
+    pub fn m2m100(&self) -> anyhow::Result<M2M100Translator> {
+        let size = match self.size.unwrap_or(Size::Small) {
+            Size::Small => M2M100Size::Small,
+            Size::Large => M2M100Size::Large,
+            Size::Base => bail!("m2m100 comes in small and large"),
+        };
+        Ok(M2M100Translator::new(self.cuda, self.compute_type(), size))
+    }
+
+    pub fn mbart50(&self) -> MBart50Translator {
+        MBart50Translator::new(self.cuda, self.compute_type())
+    }
+
+    pub fn nllb(&self) -> NLLBTranslator {
+        let size = match self.size.unwrap_or(Size::Small) {
+            Size::Small => NLLBSize::SmallDistilled,
+            Size::Base => NLLBSize::Base,
+            Size::Large => NLLBSize::Large,
+        };
+        NLLBTranslator::new(self.cuda, self.compute_type(), size)
+    }
+}
+
+fn env(name: &str) -> anyhow::Result<String> {
+    std::env::var(name).with_context(|| format!("{name} not set"))
+}
+
+impl Backend {
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Sugoi => "sugoi",
+            Backend::JParaCrawl => "jparacrawl",
+            Backend::M2M100 => "m2m100",
+            Backend::MBart50 => "mbart50",
+            Backend::Nllb => "nllb",
+            Backend::Deepl => "deepl",
+            Backend::Google => "google",
+            Backend::MyMemory => "mymemory",
+            Backend::Baidu => "baidu",
+            Backend::Caiyun => "caiyun",
+            Backend::Youdao => "youdao",
+            Backend::Papago => "papago",
+            Backend::Empty => "none",
+            Backend::Original => "original",
+        }
+    }
+
+    /// Creates the translator, credentials of online backends are read from the environment.
+    pub async fn build(
+        self,
+        options: &BackendOptions,
+    ) -> anyhow::Result<Box<dyn Translator + Send>> {
+        Ok(match self {
+            Backend::Sugoi => Box::new(options.sugoi()),
+            Backend::JParaCrawl => Box::new(options.jparacrawl()),
+            Backend::M2M100 => Box::new(options.m2m100()?),
+            Backend::MBart50 => Box::new(options.mbart50()),
+            Backend::Nllb => Box::new(options.nllb()),
+            Backend::Deepl => Box::new(DeeplTranslator::new(env("DEEPL_API_KEY")?)),
+            Backend::Google => Box::new(GoogleTranslator::new(env("GOOGLE_API_KEY")?)),
+            Backend::MyMemory => Box::new(MyMemoryTranslator::new()),
+            Backend::Baidu => Box::new(BaiduTranslator::new(
+                &env("BAIDU_APP_ID")?,
+                &env("BAIDU_KEY")?,
+            )),
+            Backend::Caiyun => Box::new(CaiyunTranslator::new(
+                env("CAIYUN_TOKEN")?,
+                env("CAIYUN_REQUEST_ID").unwrap_or_else(|_| "demo".to_owned()),
+            )),
+            Backend::Youdao => Box::new(YoudaoTranslator::new(
+                env("YOUDAO_APP_KEY")?,
+                env("YOUDAO_APP_SECRET")?,
+            )),
+            Backend::Papago => Box::new(PapagoTranslator::new(options.honorific).await?),
+            Backend::Empty => Box::new(NoneTranslator::new()),
+            Backend::Original => Box::new(OriginalTranslator::new()),
+        })
+    }
+
+    pub fn supports(self, language: &Language) -> bool {
+        match self {
+            Backend::Sugoi | Backend::JParaCrawl => {
+                matches!(language, Language::English | Language::Japanese)
+            }
+            Backend::M2M100 => language.to_m2m100().is_some(),
+            Backend::MBart50 => language.to_mbart_50().is_some(),
+            Backend::Nllb => language.to_nllb().is_some(),
+            Backend::Deepl => language.to_deepl().is_some(),
+            Backend::Google => language.to_google().is_some(),
+            Backend::MyMemory => language.to_mymemory().is_some(),
+            Backend::Baidu => language.to_baidu().is_some(),
+            Backend::Caiyun => language.to_caiyun().is_some(),
+            Backend::Youdao => language.to_youdao().is_some(),
+            Backend::Papago => language.to_papago().is_some(),
+            Backend::Empty | Backend::Original => true,
+        }
+    }
+}
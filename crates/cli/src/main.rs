@@ -0,0 +1,413 @@
+mod backend;
+mod models;
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::PathBuf,
+    time::Duration,
+};
+
+use aio_translator::{
+    Detector, LangIdDetector, Language, Translator, TranslatorTrait, translate_batch,
+};
+use aio_translator_grpc::TranslationServer;
+use aio_translator_server::{AppState, BatchOptions, TranslatorPool, deepl, libretranslate};
+use anyhow::{Context, bail};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde_json::json;
+use tokio::net::TcpListener;
+
+use crate::backend::{Backend, BackendOptions};
+
+/// Translates text with any of the aio-translator backends.
+///
+/// Without subcommand the arguments, the given files or stdin are translated line by line.
+#[derive(Parser)]
+#[command(
+    name = "aio-translate",
+    version,
+    args_conflicts_with_subcommands = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    translate: TranslateArgs,
+    /// Print one JSON object per line
+    #[arg(long, global = true)]
+    json: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Detect the language of every line
+    Detect {
+        #[command(flatten)]
+        input: Input,
+        #[arg(long, value_enum, default_value = "langid")]
+        detector: DetectorKind,
+    },
+    /// List languages, optionally only those a backend supports
+    Languages {
+        #[arg(short, long, value_enum)]
+        backend: Option<Backend>,
+    },
+    /// Serve LibreTranslate and DeepL compatible HTTP APIs and optionally gRPC
+    Serve(ServeArgs),
+    /// Manage the files of offline backends
+    Models {
+        #[command(subcommand)]
+        command: ModelsCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum ModelsCommand {
+    /// Download the models a backend needs with the given options
+    Download {
+        #[arg(value_enum)]
+        backend: Backend,
+        #[command(flatten)]
+        options: BackendOptions,
+    },
+    /// List the files of all or the given offline backends
+    List {
+        #[arg(value_enum)]
+        backend: Vec<Backend>,
+    },
+    /// Delete all downloaded files of a backend
+    Remove {
+        #[arg(value_enum)]
+        backend: Backend,
+    },
+}
+
+#[derive(Args)]
+struct TranslateArgs {
+    #[arg(short, long, value_enum)]
+    backend: Option<Backend>,
+    /// Source language, detected if missing
+    #[arg(long, value_parser = parse_language)]
+    from: Option<Language>,
+    /// Target language as ISO 639 code or English name
+    #[arg(long, value_parser = parse_language)]
+    to: Option<Language>,
+    /// Lines per request
+    #[arg(long, default_value_t = 32)]
+    batch_size: usize,
+    #[command(flatten)]
+    input: Input,
+    #[command(flatten)]
+    options: BackendOptions,
+}
+
+#[derive(Args)]
+struct ServeArgs {
+    #[arg(short, long, value_enum)]
+    backend: Backend,
+    #[arg(long, default_value = "127.0.0.1")]
+    host: String,
+    #[arg(long, default_value_t = 5000)]
+    port: u16,
+    /// Also serve the gRPC API on this port
+    #[arg(long)]
+    grpc_port: Option<u16>,
+    /// Translator instances, each one loads its own model
+    #[arg(long, default_value_t = 1)]
+    workers: usize,
+    /// Texts translated together
+    #[arg(long, default_value_t = 32)]
+    max_batch: usize,
+    /// Milliseconds a worker waits for more requests
+    #[arg(long, default_value_t = 5)]
+    batch_delay: u64,
+    #[command(flatten)]
+    options: BackendOptions,
+}
+
+#[derive(Args)]
+struct Input {
+    /// Read lines from files instead of stdin
+    #[arg(short, long)]
+    file: Vec<PathBuf>,
+    /// Text to use instead of stdin, one entry per line
+    text: Vec<String>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DetectorKind {
+    Langid,
+    #[cfg(feature = "whatlang")]
+    Whatlang,
+    #[cfg(feature = "lingua")]
+    Lingua,
+}
+
+/// Accepts language tags, ISO 639-3 codes and English names.
+fn parse_language(s: &str) -> Result<Language, String> {
+    Language::from_tag(s)
+        .or_else(|| Language::from_639_3(s))
+        .or_else(|| {
+            Language::all()
+                .into_iter()
+                .find(|v| v.to_name().is_some_and(|v| v.eq_ignore_ascii_case(s)))
+        })
+        .ok_or_else(|| format!("unknown language {s}"))
+}
+
+fn language_json(language: Option<Language>) -> serde_json::Value {
+    json!(language.map(|v| v.to_tag().or(v.to_639_3()).unwrap_or_default()))
+}
+
+impl Input {
+    fn lines(&self) -> anyhow::Result<Box<dyn Iterator<Item = anyhow::Result<String>>>> {
+        if !self.text.is_empty() {
+            return Ok(Box::new(self.text.clone().into_iter().map(Ok)));
+        }
+        if self.file.is_empty() {
+            return Ok(Box::new(std::io::stdin().lock().lines().map(|v| Ok(v?))));
+        }
+        let files = self
+            .file
+            .iter()
+            .map(|path| {
+                File::open(path).with_context(|| format!("could not open {}", path.display()))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Box::new(
+            files
+                .into_iter()
+                .flat_map(|v| BufReader::new(v).lines())
+                .map(|v| Ok(v?)),
+        ))
+    }
+}
+
+fn detector(kind: DetectorKind) -> anyhow::Result<Box<dyn Detector>> {
+    Ok(match kind {
+        DetectorKind::Langid => Box::new(LangIdDetector::new()?),
+        #[cfg(feature = "whatlang")]
+        DetectorKind::Whatlang => Box::new(aio_translator::WhatLangDetector::new()),
+        #[cfg(feature = "lingua")]
+        DetectorKind::Lingua => Box::new(aio_translator::LinguaDetector::new()),
+    })
+}
+
+async fn translate(args: TranslateArgs, json: bool) -> anyhow::Result<()> {
+    let Some(backend) = args.backend else {
+        bail!("--backend is required");
+    };
+    let Some(to) = args.to else {
+        bail!("--to is required");
+    };
+    let mut translator = backend.build(&args.options).await?;
+    // blocking translators need a source language
+    let detector = match (args.from, is_blocking(translator.as_ref())) {
+        (None, true) => Some(LangIdDetector::new()?),
+        _ => None,
+    };
+    let mut lines = args.input.lines()?;
+    loop {
+        let batch = lines
+            .by_ref()
+            .take(args.batch_size.max(1))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if batch.is_empty() {
+            break;
+        }
+        let from = batch
+            .iter()
+            .map(|v| args.from.or_else(|| detector.as_ref()?.detect_language(v)))
+            .collect::<Vec<_>>();
+        let translated = translate_lines(translator.as_mut(), &batch, &from, &to).await?;
+        for ((text, from), translation) in batch.iter().zip(from).zip(translated) {
+            match json {
+                true => println!(
+                    "{}",
+                    json!({
+                        "text": text,
+                        "translation": translation,
+                        "from": language_json(from),
+                        "to": language_json(Some(to)),
+                    })
+                ),
+                false => println!("{translation}"),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn is_blocking(translator: &dyn Translator) -> bool {
+    matches!(translator.translator(), TranslatorTrait::Blocking(_))
+}
+
+/// Translates non-empty lines, one request per source language.
+async fn translate_lines(
+    translator: &mut dyn Translator,
+    lines: &[String],
+    from: &[Option<Language>],
+    to: &Language,
+) -> anyhow::Result<Vec<String>> {
+    let mut out = vec![String::new(); lines.len()];
+    let mut groups: HashMap<Option<Language>, Vec<usize>> = HashMap::new();
+    for (i, line) in lines.iter().enumerate() {
+        if !line.trim().is_empty() {
+            groups.entry(from[i]).or_default().push(i);
+        }
+    }
+    let mut groups = groups.into_iter().collect::<Vec<_>>();
+    groups.sort_by_key(|v| v.1[0]);
+    for (from, indices) in groups {
+        if from.is_none() && is_blocking(translator) {
+            bail!(
+                "could not detect the language of {:?}, pass --from",
+                lines[indices[0]]
+            );
+        }
+        let query = indices
+            .iter()
+            .map(|v| lines[*v].clone())
+            .collect::<Vec<_>>();
+        let translated = translate_batch(&mut *translator, &query, from, to).await?;
+        for (i, text) in indices.into_iter().zip(translated) {
+            out[i] = text;
+        }
+    }
+    Ok(out)
+}
+
+fn detect(input: Input, kind: DetectorKind, json: bool) -> anyhow::Result<()> {
+    let detector = detector(kind)?;
+    for line in input.lines()? {
+        let line = line?;
+        let language = detector.detect_language(&line);
+        match json {
+            true => println!(
+                "{}",
+                json!({
+                    "text": line,
+                    "language": language_json(language),
+                    "name": language.and_then(|v| v.to_name()),
+                })
+            ),
+            false => println!(
+                "{}",
+                language
+                    .and_then(|v| v.to_tag().or(v.to_639_3()))
+                    .unwrap_or("-")
+            ),
+        }
+    }
+    Ok(())
+}
+
+fn languages(backend: Option<Backend>, json: bool) {
+    let languages = Language::all()
+        .into_iter()
+        .filter(|v| backend.is_none_or(|backend| backend.supports(v)));
+    for language in languages {
+        let name = language.to_name().unwrap_or_default();
+        let code = language.to_tag().unwrap_or_default();
+        let iso639_3 = language.to_639_3().unwrap_or_default();
+        match json {
+            true => println!(
+                "{}",
+                json!({ "name": name, "code": code, "iso639_3": iso639_3 })
+            ),
+            false => println!("{code}\t{iso639_3}\t{name}"),
+        }
+    }
+}
+
+async fn serve(args: ServeArgs) -> anyhow::Result<()> {
+    let mut translators = vec![];
+    for _ in 0..args.workers.max(1) {
+        translators.push(args.backend.build(&args.options).await?);
+    }
+    let pool = TranslatorPool::new(
+        translators,
+        BatchOptions {
+            max_batch: args.max_batch.max(1),
+            max_delay: Duration::from_millis(args.batch_delay),
+        },
+    );
+    let languages = Language::all()
+        .into_iter()
+        .filter(|v| args.backend.supports(v))
+        .collect();
+    let state = AppState::new(pool, LangIdDetector::new()?, languages);
+    let listener = TcpListener::bind((args.host.as_str(), args.port)).await?;
+    eprintln!("listening on http://{}", listener.local_addr()?);
+    let router = libretranslate::router(state.clone()).merge(deepl::router(state.clone()));
+    let http = aio_translator_server::serve(listener, router);
+    let Some(port) = args.grpc_port else {
+        http.await?;
+        return Ok(());
+    };
+    let listener = TcpListener::bind((args.host.as_str(), port)).await?;
+    eprintln!("listening on grpc://{}", listener.local_addr()?);
+    let grpc = TranslationServer::new(args.backend.name(), state);
+    tokio::try_join!(async { http.await.map_err(anyhow::Error::from) }, async {
+        aio_translator_grpc::serve(listener, grpc)
+            .await
+            .map_err(anyhow::Error::from)
+    },)?;
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+    let cli = Cli::parse();
+    match cli.command {
+        None => translate(cli.translate, cli.json).await,
+        Some(Command::Detect { input, detector }) => detect(input, detector, cli.json),
+        Some(Command::Languages { backend }) => {
+            languages(backend, cli.json);
+            Ok(())
+        }
+        Some(Command::Serve(args)) => serve(args).await,
+        Some(Command::Models { command }) => match command {
+            ModelsCommand::Download { backend, options } => models::download(backend, &options),
+            ModelsCommand::List { backend } => {
+                let backends = match backend.is_empty() {
+                    true => models::OFFLINE.to_vec(),
+                    false => backend,
+                };
+                models::list(&backends, &BackendOptions::default(), cli.json)
+            }
+            ModelsCommand::Remove { backend } => {
+                let dir = models::remove(backend, &BackendOptions::default())?;
+                match cli.json {
+                    true => println!("{}", json!({ "removed": dir })),
+                    false => println!("removed {}", dir.display()),
+                }
+                Ok(())
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    #[test]
+    fn arguments() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn languages() {
+        assert_eq!(parse_language("ja"), Ok(Language::Japanese));
+        assert_eq!(parse_language("deu"), Ok(Language::German));
+        assert_eq!(parse_language("zh-TW"), Ok(Language::ChineseTraditional));
+        assert_eq!(parse_language("english"), Ok(Language::English));
+        assert!(parse_language("klingon-ish").is_err());
+    }
+}
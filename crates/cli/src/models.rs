@@ -0,0 +1,107 @@
+use std::path::{Path, PathBuf};
+
+use aio_translator::Model;
+use anyhow::{Context, bail};
+use serde_json::json;
+
+use crate::backend::{Backend, BackendOptions};
+
+pub const OFFLINE: [Backend; 5] = [
+    Backend::Sugoi,
+    Backend::JParaCrawl,
+    Backend::M2M100,
+    Backend::MBart50,
+    Backend::Nllb,
+];
+
+/// Runs `$body` with `$model` bound to the offline translator of `$backend`.
+macro_rules! with_model {
+    ($backend:expr, $options:expr, |$model:ident| $body:expr) => {
+        match $backend {
+            Backend::Sugoi => {
+                let $model = &mut $options.sugoi();
+                $body
+            }
+            Backend::JParaCrawl => {
+                let $model = &mut $options.jparacrawl();
+                $body
+            }
+            Backend::M2M100 => {
+                let $model = &mut $options.m2m100()?;
+                $body
+            }
+            Backend::MBart50 => {
+                let $model = &mut $options.mbart50();
+                $body
+            }
+            Backend::Nllb => {
+                let $model = &mut $options.nllb();
+                $body
+            }
+            backend => bail!("{} has no local models", backend.name()),
+        }
+    };
+}
+
+/// Tokenizer of every offline backend, the models are stored next to it.
+fn tokenizer(backend: Backend) -> (&'static str, &'static str) {
+    match backend {
+        Backend::Sugoi => ("spm.ja.nopretok", "spm.ja.nopretok.model"),
+        Backend::JParaCrawl => ("spm.nopretok", "spm.nopretok/spm.ja.nopretok.model"),
+        _ => ("spm", "sentencepiece.bpe.model"),
+    }
+}
+
+/// Prints the downloadable files of `backends`.
+pub fn list(backends: &[Backend], options: &BackendOptions, json: bool) -> anyhow::Result<()> {
+    for backend in backends {
+        let models = with_model!(*backend, options, |model| model.models());
+        let mut models = models.into_iter().collect::<Vec<_>>();
+        models.sort_by_key(|v| v.0);
+        for (name, source) in models {
+            match json {
+                true => println!(
+                    "{}",
+                    json!({ "backend": backend.name(), "model": name, "url": source.url })
+                ),
+                false => println!("{}\t{name}\t{}", backend.name(), source.url),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Downloads and verifies everything the configured size needs.
+pub fn download(backend: Backend, options: &BackendOptions) -> anyhow::Result<()> {
+    with_model!(backend, options, |model| {
+        model.load()?;
+    });
+    Ok(())
+}
+
+pub fn remove(backend: Backend, options: &BackendOptions) -> anyhow::Result<PathBuf> {
+    let (key, file) = tokenizer(backend);
+    let dir = with_model!(backend, options, |model| model_dir(model, key, file)?);
+    // never remove a shared parent
+    if !dir
+        .file_name()
+        .and_then(|v| v.to_str())
+        .is_some_and(|v| v.eq_ignore_ascii_case(backend.name()))
+    {
+        bail!(
+            "{} is not the model directory of {}",
+            dir.display(),
+            backend.name()
+        );
+    }
+    std::fs::remove_dir_all(&dir)?;
+    Ok(dir)
+}
+
+fn model_dir<T: Model>(model: &mut T, key: &str, file: &str) -> anyhow::Result<PathBuf> {
+    let path = model.download_model(key, file)?;
+    path.ancestors()
+        .nth(Path::new(file).components().count())
+        .map(Path::to_path_buf)
+        .context("model path has no parent")
+}
@@ -0,0 +1,399 @@
+//! Scriptable translator for tests of code built on top of the translators.
+//!
+//! ```ignore
+//! let t = FakeTranslator::new()
+//!     .map("Hello", "Hallo")
+//!     .pseudo(Pseudo::Reverse)
+//!     .fail_at(1, Failure::Error("quota".to_owned()));
+//! let log = t.log();
+//! // ... hand `t` to the code under test
+//! assert_eq!(log.calls()[0].query, ["Hello"]);
+//! ```
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use aio_translator_interface::{
+    AsyncTranslator, BlockingTranslator, Language, TranslationListOutput, TranslationOutput,
+    Translator, TranslatorMutTrait, TranslatorTrait, error::Error, prompt::PromptBuilder,
+};
+use anyhow::anyhow;
+
+/// Translation of lines without a mapping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Pseudo {
+    /// Line unchanged
+    Echo,
+    /// Characters in reverse order
+    Reverse,
+    /// `[{tag}] {line}` with the tag of the target language
+    #[default]
+    Bracket,
+}
+
+impl Pseudo {
+    fn apply(&self, line: &str, to: &Language) -> String {
+        match self {
+            Pseudo::Echo => line.to_owned(),
+            Pseudo::Reverse => line.chars().rev().collect(),
+            Pseudo::Bracket => format!("[{}] {line}", to.to_tag().unwrap_or("?")),
+        }
+    }
+}
+
+/// Answer injected in place of a translation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Failure {
+    /// Fails with the message
+    Error(String),
+    /// Translates, but leaves out the last result
+    DropLast,
+}
+
+/// Request received by a [`FakeTranslator`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Call {
+    /// Position among all calls, starting at 0
+    pub index: usize,
+    pub query: Vec<String>,
+    pub from: Option<Language>,
+    pub to: Language,
+    pub html: bool,
+}
+
+/// Calls of a [`FakeTranslator`], still readable after the translator was moved.
+#[derive(Debug, Clone, Default)]
+pub struct CallLog(Arc<Mutex<Vec<Call>>>);
+
+impl CallLog {
+    pub fn calls(&self) -> Vec<Call> {
+        self.0.lock().unwrap().clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Translates with canned mappings and a [`Pseudo`] translation for everything else.
+///
+/// Async unless created with [`FakeTranslator::blocking`]. Every line is looked up on its
+/// own, unless the whole text has a mapping.
+#[derive(Debug, Clone, Default)]
+pub struct FakeTranslator {
+    blocking: bool,
+    mappings: HashMap<String, String>,
+    pseudo: Pseudo,
+    latency: Duration,
+    failures: HashMap<usize, Failure>,
+    detected: Option<Language>,
+    html: bool,
+    log: CallLog,
+}
+
+impl FakeTranslator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn blocking() -> Self {
+        Self {
+            blocking: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn map(mut self, text: impl Into<String>, translated: impl Into<String>) -> Self {
+        self.mappings.insert(text.into(), translated.into());
+        self
+    }
+
+    pub fn pseudo(mut self, pseudo: Pseudo) -> Self {
+        self.pseudo = pseudo;
+        self
+    }
+
+    /// Waits before every answer, also before failures.
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Answers the call with `index`, counted from 0 over all calls, with `failure`.
+    pub fn fail_at(mut self, index: usize, failure: Failure) -> Self {
+        self.failures.insert(index, failure);
+        self
+    }
+
+    /// Language reported when the call has no source language.
+    pub fn detect(mut self, lang: Language) -> Self {
+        self.detected = Some(lang);
+        self
+    }
+
+    /// Reports [`AsyncTranslator::handles_html`], so callers send markup as is.
+    pub fn html(mut self, html: bool) -> Self {
+        self.html = html;
+        self
+    }
+
+    pub fn log(&self) -> CallLog {
+        self.log.clone()
+    }
+
+    pub fn calls(&self) -> Vec<Call> {
+        self.log.calls()
+    }
+
+    fn translate_text(&self, text: &str, to: &Language) -> String {
+        if let Some(v) = self.mappings.get(text) {
+            return v.clone();
+        }
+        text.split('\n')
+            .map(|line| match self.mappings.get(line) {
+                Some(v) => v.clone(),
+                None => self.pseudo.apply(line, to),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Records the call and answers it, without the latency.
+    fn answer(
+        &self,
+        query: &[String],
+        from: Option<Language>,
+        to: &Language,
+        html: bool,
+    ) -> anyhow::Result<Vec<String>> {
+        let index = {
+            let mut calls = self.log.0.lock().unwrap();
+            let index = calls.len();
+            calls.push(Call {
+                index,
+                query: query.to_vec(),
+                from,
+                to: *to,
+                html,
+            });
+            index
+        };
+        let mut text = query
+            .iter()
+            .map(|v| self.translate_text(v, to))
+            .collect::<Vec<_>>();
+        match self.failures.get(&index) {
+            Some(Failure::Error(message)) => Err(anyhow!("call {index}: {message}")),
+            Some(Failure::DropLast) => {
+                text.pop();
+                Ok(text)
+            }
+            None => Ok(text),
+        }
+    }
+
+    async fn answer_async(
+        &self,
+        query: &[String],
+        from: Option<Language>,
+        to: &Language,
+        html: bool,
+    ) -> anyhow::Result<TranslationListOutput> {
+        let text = self.answer(query, from, to, html);
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+        Ok(TranslationListOutput {
+            text: text?,
+            lang: from.or(self.detected),
+        })
+    }
+
+    fn answer_blocking(
+        &self,
+        query: &[String],
+        from: Language,
+        to: &Language,
+    ) -> anyhow::Result<Vec<String>> {
+        let text = self.answer(query, Some(from), to, false);
+        if !self.latency.is_zero() {
+            std::thread::sleep(self.latency);
+        }
+        text
+    }
+}
+
+impl Translator for FakeTranslator {
+    fn local(&self) -> bool {
+        false
+    }
+
+    fn translator<'a>(&'a self) -> TranslatorTrait<'a> {
+        match self.blocking {
+            true => TranslatorTrait::Blocking(self),
+            false => TranslatorTrait::Async(self),
+        }
+    }
+
+    fn translator_mut<'a>(&'a mut self) -> TranslatorMutTrait<'a> {
+        match self.blocking {
+            true => TranslatorMutTrait::Blocking(self),
+            false => TranslatorMutTrait::Async(self),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl AsyncTranslator for FakeTranslator {
+    async fn translate(
+        &self,
+        query: &str,
+        _: Option<PromptBuilder>,
+        from: Option<Language>,
+        to: &Language,
+    ) -> anyhow::Result<TranslationOutput> {
+        let mut t = self
+            .answer_async(&[query.to_owned()], from, to, false)
+            .await?;
+        Ok(TranslationOutput {
+            text: t.text.pop().ok_or(Error::NoResponse)?,
+            lang: t.lang,
+        })
+    }
+
+    async fn translate_vec(
+        &self,
+        query: &[String],
+        _: Option<PromptBuilder>,
+        from: Option<Language>,
+        to: &Language,
+    ) -> anyhow::Result<TranslationListOutput> {
+        self.answer_async(query, from, to, false).await
+    }
+
+    fn handles_html(&self) -> bool {
+        self.html
+    }
+
+    async fn translate_html_vec(
+        &self,
+        query: &[String],
+        _: Option<PromptBuilder>,
+        from: Option<Language>,
+        to: &Language,
+    ) -> anyhow::Result<TranslationListOutput> {
+        self.answer_async(query, from, to, true).await
+    }
+}
+
+impl BlockingTranslator for FakeTranslator {
+    fn translate(
+        &mut self,
+        query: &str,
+        _: Option<PromptBuilder>,
+        from: Language,
+        to: &Language,
+    ) -> anyhow::Result<String> {
+        let mut text = self.answer_blocking(&[query.to_owned()], from, to)?;
+        Ok(text.pop().ok_or(Error::NoResponse)?)
+    }
+
+    fn translate_vec(
+        &mut self,
+        query: &[String],
+        _: Option<PromptBuilder>,
+        from: Language,
+        to: &Language,
+    ) -> anyhow::Result<Vec<String>> {
+        self.answer_blocking(query, from, to)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn scripted_async() {
+        let t = FakeTranslator::new()
+            .map("Hello", "Hallo")
+            .detect(Language::English)
+            .fail_at(1, Failure::Error("quota".to_owned()))
+            .fail_at(2, Failure::DropLast);
+        let log = t.log();
+        let a = t.translator();
+        let a = a.as_async().unwrap();
+        let query = ["Hello".to_owned(), "World\nHello".to_owned()];
+
+        let out = a
+            .translate_vec(&query, None, None, &Language::German)
+            .await
+            .unwrap();
+        assert_eq!(out.text, ["Hallo", "[de] World\nHallo"]);
+        assert_eq!(out.lang, Some(Language::English));
+        let e = a
+            .translate("Hello", None, None, &Language::German)
+            .await
+            .unwrap_err();
+        assert_eq!(e.to_string(), "call 1: quota");
+        let out = a
+            .translate_vec(&query, None, Some(Language::French), &Language::German)
+            .await
+            .unwrap();
+        assert_eq!(out.text, ["Hallo"]);
+        assert_eq!(out.lang, Some(Language::French));
+
+        let calls = log.calls();
+        assert_eq!(calls.len(), 3);
+        assert_eq!(calls[1].query, ["Hello"]);
+        assert_eq!(calls[2].from, Some(Language::French));
+        assert_eq!(calls[2].index, 2);
+        assert!(!a.handles_html());
+    }
+
+    #[tokio::test]
+    async fn html() {
+        let t = FakeTranslator::new().pseudo(Pseudo::Echo).html(true);
+        let a = t.translator();
+        let a = a.as_async().unwrap();
+        assert!(a.handles_html());
+        let query = ["Hello <b>world</b>".to_owned()];
+        let out = a
+            .translate_html_vec(&query, None, Some(Language::English), &Language::German)
+            .await
+            .unwrap();
+        assert_eq!(out.text, query);
+        assert!(t.calls()[0].html);
+    }
+
+    #[test]
+    fn scripted_blocking() {
+        let mut t = FakeTranslator::blocking()
+            .pseudo(Pseudo::Reverse)
+            .latency(Duration::from_millis(20));
+        let start = Instant::now();
+        let mut b = t.translator_mut();
+        let b = b.as_blocking().unwrap();
+        let out = b
+            .translate_vec(
+                &["abc".to_owned(), "de".to_owned()],
+                None,
+                Language::English,
+                &Language::German,
+            )
+            .unwrap();
+        assert_eq!(out, ["cba", "ed"]);
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert!(t.translator().as_async().is_none());
+        assert_eq!(t.calls()[0].from, Some(Language::English));
+    }
+}
@@ -38,11 +38,11 @@ impl BlockingTranslator for NoneTranslator {
 
     fn translate_vec(
         &mut self,
-        _: &[String],
+        query: &[String],
         _: Option<PromptBuilder>,
         _: Language,
         _: &Language,
     ) -> anyhow::Result<Vec<String>> {
-        Ok(Vec::new())
+        Ok(vec![String::new(); query.len()])
     }
 }
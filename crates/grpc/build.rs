@@ -0,0 +1,8 @@
+fn main() -> std::io::Result<()> {
+    // protoc is vendored so building does not depend on a system installation
+    if std::env::var_os("PROTOC").is_none() {
+        let protoc = protoc_bin_vendored::protoc_bin_path().map_err(std::io::Error::other)?;
+        unsafe { std::env::set_var("PROTOC", protoc) };
+    }
+    tonic_prost_build::compile_protos("proto/translator.proto")
+}
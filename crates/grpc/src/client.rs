@@ -0,0 +1,136 @@
+use aio_translator::Language;
+use anyhow::anyhow;
+use tonic::transport::Channel;
+
+use crate::{
+    from_code,
+    proto::{
+        DetectRequest, ListLanguagesRequest, TranslateOptions, TranslateRequest,
+        translation_service_client::TranslationServiceClient,
+    },
+    to_code,
+};
+
+/// Typed wrapper around the generated client.
+#[derive(Clone)]
+pub struct Client {
+    inner: TranslationServiceClient<Channel>,
+    backend: String,
+}
+
+impl Client {
+    /// Connects to an address like `http://127.0.0.1:50051`.
+    pub async fn connect(address: impl Into<String>) -> anyhow::Result<Self> {
+        Ok(Self {
+            inner: TranslationServiceClient::connect(address.into()).await?,
+            backend: String::new(),
+        })
+    }
+
+    /// Sends all requests to the named backend instead of the default one.
+    pub fn with_backend(mut self, backend: impl Into<String>) -> Self {
+        self.backend = backend.into();
+        self
+    }
+
+    pub fn inner(&mut self) -> &mut TranslationServiceClient<Channel> {
+        &mut self.inner
+    }
+
+    fn request(
+        &self,
+        texts: Vec<String>,
+        from: Option<Language>,
+        to: Language,
+        html: bool,
+    ) -> anyhow::Result<TranslateRequest> {
+        Ok(TranslateRequest {
+            texts,
+            source: from.map(|v| code(&v)).transpose()?.unwrap_or_default(),
+            target: code(&to)?,
+            backend: self.backend.clone(),
+            options: Some(TranslateOptions { html }),
+        })
+    }
+
+    pub async fn translate(
+        &mut self,
+        texts: &[String],
+        from: Option<Language>,
+        to: Language,
+    ) -> anyhow::Result<Vec<String>> {
+        let request = self.request(texts.to_vec(), from, to, false)?;
+        let response = self.inner.translate(request).await?.into_inner();
+        Ok(response.translations.into_iter().map(|v| v.text).collect())
+    }
+
+    pub async fn translate_html(
+        &mut self,
+        html: &str,
+        from: Option<Language>,
+        to: Language,
+    ) -> anyhow::Result<String> {
+        let request = self.request(vec![html.to_owned()], from, to, true)?;
+        let response = self.inner.translate(request).await?.into_inner();
+        response
+            .translations
+            .into_iter()
+            .next()
+            .map(|v| v.text)
+            .ok_or_else(|| anyhow!("empty response"))
+    }
+
+    /// Streams every batch as its own request.
+    pub async fn translate_batch(
+        &mut self,
+        batches: Vec<Vec<String>>,
+        from: Option<Language>,
+        to: Language,
+    ) -> anyhow::Result<Vec<Vec<String>>> {
+        let requests = batches
+            .into_iter()
+            .map(|v| self.request(v, from, to, false))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let mut responses = self
+            .inner
+            .translate_batch(tokio_stream::iter(requests))
+            .await?
+            .into_inner();
+        let mut out = vec![];
+        while let Some(response) = responses.message().await? {
+            out.push(response.translations.into_iter().map(|v| v.text).collect());
+        }
+        Ok(out)
+    }
+
+    pub async fn detect(&mut self, text: &str) -> anyhow::Result<Option<Language>> {
+        let response = self
+            .inner
+            .detect(DetectRequest {
+                text: text.to_owned(),
+            })
+            .await?
+            .into_inner();
+        Ok(from_code(&response.language))
+    }
+
+    /// Languages of the selected backend.
+    pub async fn languages(&mut self) -> anyhow::Result<Vec<Language>> {
+        let response = self
+            .inner
+            .list_languages(ListLanguagesRequest {
+                backend: self.backend.clone(),
+            })
+            .await?
+            .into_inner();
+        Ok(response
+            .languages
+            .iter()
+            .filter_map(|v| from_code(&v.code))
+            .collect())
+    }
+}
+
+fn code(language: &Language) -> anyhow::Result<String> {
+    to_code(language).ok_or_else(|| anyhow!("{language:?} has no ISO 639-3 code"))
+}
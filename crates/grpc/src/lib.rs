@@ -0,0 +1,62 @@
+//! gRPC service over a pool of translators, see `proto/translator.proto`.
+
+mod client;
+mod service;
+
+use aio_translator::Language;
+
+pub use client::Client;
+pub use service::{TranslationServer, serve};
+
+pub mod proto {
+    tonic::include_proto!("aio_translator.v1");
+}
+
+/// ISO 639-3 code the service uses for a language, without the script of
+/// [`Language::to_639_3`].
+pub fn to_code(language: &Language) -> Option<String> {
+    match language {
+        Language::ChineseTraditional => Some("zho-Hant".to_owned()),
+        _ => language
+            .to_639_3()
+            .and_then(|v| v.split('_').next())
+            .map(ToOwned::to_owned),
+    }
+}
+
+/// Also accepts codes with script and the tags [`Language::from_tag`] understands.
+pub fn from_code(code: &str) -> Option<Language> {
+    if code.is_empty() {
+        return None;
+    }
+    match code {
+        "zho-Hant" => Some(Language::ChineseTraditional),
+        _ => Language::from_639_3(code)
+            .or_else(|| {
+                Language::all()
+                    .into_iter()
+                    .find(|v| to_code(v).as_deref() == Some(code))
+            })
+            .or_else(|| Language::from_tag(code)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes() {
+        for language in [
+            Language::English,
+            Language::Japanese,
+            Language::ChineseTraditional,
+        ] {
+            assert_eq!(from_code(&to_code(&language).unwrap()), Some(language));
+        }
+        assert_eq!(to_code(&Language::German).as_deref(), Some("deu"));
+        assert_eq!(from_code("deu_Latn"), Some(Language::German));
+        assert_eq!(from_code("de"), Some(Language::German));
+        assert_eq!(from_code("xx"), None);
+    }
+}
@@ -0,0 +1,366 @@
+use aio_translator::Language;
+use aio_translator_server::AppState;
+use tokio::{net::TcpListener, sync::mpsc, task::JoinHandle};
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tonic::{Request, Response, Status, Streaming, transport::Server};
+
+use crate::{
+    from_code,
+    proto::{
+        DetectRequest, DetectResponse, LanguageInfo, ListLanguagesRequest, ListLanguagesResponse,
+        TranslateRequest, TranslateResponse, Translation,
+        translation_service_server::{TranslationService, TranslationServiceServer},
+    },
+    to_code,
+};
+
+/// Requests of a `TranslateBatch` stream that are translated at the same time.
+const PIPELINE: usize = 32;
+
+/// Named backends, the first one answers requests without backend.
+#[derive(Clone)]
+pub struct TranslationServer {
+    backends: Vec<(String, AppState)>,
+}
+
+impl TranslationServer {
+    pub fn new(name: impl Into<String>, state: AppState) -> Self {
+        Self {
+            backends: vec![(name.into(), state)],
+        }
+    }
+
+    pub fn with_backend(mut self, name: impl Into<String>, state: AppState) -> Self {
+        self.backends.push((name.into(), state));
+        self
+    }
+
+    pub fn into_service(self) -> TranslationServiceServer<Self> {
+        TranslationServiceServer::new(self)
+    }
+
+    fn backend(&self, name: &str) -> Result<&AppState, Status> {
+        let backend = match name {
+            "" => self.backends.first(),
+            _ => self.backends.iter().find(|(v, _)| v == name),
+        };
+        backend
+            .map(|(_, state)| state)
+            .ok_or_else(|| Status::not_found(format!("unknown backend {name}")))
+    }
+
+    async fn run(&self, request: TranslateRequest) -> Result<TranslateResponse, Status> {
+        let state = self.backend(&request.backend)?;
+        let to = match request.target.as_str() {
+            "" => Err(Status::invalid_argument("target is required"))?,
+            code => language(code)?,
+        };
+        let from = match request.source.as_str() {
+            "" => None,
+            code => Some(language(code)?),
+        };
+        let sources = request
+            .texts
+            .iter()
+            .map(|v| from.or_else(|| state.detector.detect_language(v)))
+            .collect::<Vec<_>>();
+
+        let html = request.options.is_some_and(|v| v.html);
+        let translated = match html {
+            true => {
+                let mut translated = vec![];
+                for (text, from) in request.texts.into_iter().zip(&sources) {
+                    translated.push(state.pool.translate_html(text, *from, to).await);
+                }
+                translated.into_iter().collect()
+            }
+            false => {
+                let items = request.texts.into_iter().zip(sources.clone()).collect();
+                state.pool.translate(items, to).await
+            }
+        }
+        .map_err(|e| Status::internal(format!("{e:#}")))?;
+
+        Ok(TranslateResponse {
+            translations: translated
+                .into_iter()
+                .zip(sources)
+                .map(|(text, source)| Translation {
+                    text,
+                    source: source.and_then(|v| to_code(&v)).unwrap_or_default(),
+                })
+                .collect(),
+        })
+    }
+}
+
+fn language(code: &str) -> Result<Language, Status> {
+    from_code(code).ok_or_else(|| Status::invalid_argument(format!("{code} is not supported")))
+}
+
+#[tonic::async_trait]
+impl TranslationService for TranslationServer {
+    type TranslateBatchStream = ReceiverStream<Result<TranslateResponse, Status>>;
+
+    async fn translate(
+        &self,
+        request: Request<TranslateRequest>,
+    ) -> Result<Response<TranslateResponse>, Status> {
+        self.run(request.into_inner()).await.map(Response::new)
+    }
+
+    /// Requests are translated concurrently so the pool can batch them, the answers keep
+    /// their order. The first failure ends the stream.
+    async fn translate_batch(
+        &self,
+        request: Request<Streaming<TranslateRequest>>,
+    ) -> Result<Response<Self::TranslateBatchStream>, Status> {
+        type Pending = JoinHandle<Result<TranslateResponse, Status>>;
+        let mut requests = request.into_inner();
+        let (pending, mut running) = mpsc::channel::<Pending>(PIPELINE);
+        let (responses, stream) = mpsc::channel(PIPELINE);
+
+        let server = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let handle = match requests.message().await {
+                    Ok(Some(request)) => {
+                        let server = server.clone();
+                        tokio::spawn(async move { server.run(request).await })
+                    }
+                    Ok(None) => break,
+                    Err(status) => tokio::spawn(async move { Err(status) }),
+                };
+                if pending.send(handle).await.is_err() {
+                    break;
+                }
+            }
+        });
+        tokio::spawn(async move {
+            while let Some(handle) = running.recv().await {
+                let response = handle
+                    .await
+                    .unwrap_or_else(|e| Err(Status::internal(e.to_string())));
+                let failed = response.is_err();
+                if responses.send(response).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(stream)))
+    }
+
+    async fn detect(
+        &self,
+        request: Request<DetectRequest>,
+    ) -> Result<Response<DetectResponse>, Status> {
+        let state = self.backend("")?;
+        let language = state.detector.detect_language(&request.into_inner().text);
+        Ok(Response::new(DetectResponse {
+            language: language.and_then(|v| to_code(&v)).unwrap_or_default(),
+        }))
+    }
+
+    async fn list_languages(
+        &self,
+        request: Request<ListLanguagesRequest>,
+    ) -> Result<Response<ListLanguagesResponse>, Status> {
+        let state = self.backend(&request.into_inner().backend)?;
+        Ok(Response::new(ListLanguagesResponse {
+            languages: state
+                .languages
+                .iter()
+                .filter_map(|language| {
+                    Some(LanguageInfo {
+                        code: to_code(language)?,
+                        name: language.to_name().unwrap_or_default().to_owned(),
+                    })
+                })
+                .collect(),
+            backends: self.backends.iter().map(|(v, _)| v.clone()).collect(),
+        }))
+    }
+}
+
+pub async fn serve(
+    listener: TcpListener,
+    server: TranslationServer,
+) -> Result<(), tonic::transport::Error> {
+    Server::builder()
+        .add_service(server.into_service())
+        .serve_with_incoming(TcpListenerStream::new(listener))
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use aio_translator::{
+        BlockingTranslator, Detector, OriginalTranslator, PromptBuilder, Translator,
+        TranslatorMutTrait, TranslatorTrait,
+    };
+    use aio_translator_server::{BatchOptions, BoxedTranslator, TranslatorPool};
+
+    use super::*;
+    use crate::Client;
+
+    struct AsciiDetector;
+
+    impl Detector for AsciiDetector {
+        fn detect_language(&self, text: &str) -> Option<Language> {
+            text.is_ascii().then_some(Language::English)
+        }
+    }
+
+    struct Upper;
+
+    impl Translator for Upper {
+        fn local(&self) -> bool {
+            true
+        }
+
+        fn translator<'a>(&'a self) -> TranslatorTrait<'a> {
+            TranslatorTrait::Blocking(self)
+        }
+
+        fn translator_mut<'a>(&'a mut self) -> TranslatorMutTrait<'a> {
+            TranslatorMutTrait::Blocking(self)
+        }
+    }
+
+    impl BlockingTranslator for Upper {
+        fn translate(
+            &mut self,
+            query: &str,
+            _: Option<PromptBuilder>,
+            _: Language,
+            _: &Language,
+        ) -> anyhow::Result<String> {
+            Ok(query.to_uppercase())
+        }
+
+        fn translate_vec(
+            &mut self,
+            query: &[String],
+            _: Option<PromptBuilder>,
+            _: Language,
+            _: &Language,
+        ) -> anyhow::Result<Vec<String>> {
+            Ok(query.iter().map(|v| v.to_uppercase()).collect())
+        }
+    }
+
+    fn state(translator: BoxedTranslator, languages: Vec<Language>) -> AppState {
+        let pool = TranslatorPool::new(vec![translator], BatchOptions::default());
+        AppState::new(pool, AsciiDetector, languages)
+    }
+
+    async fn start() -> Client {
+        let server = TranslationServer::new(
+            "original",
+            state(
+                Box::new(OriginalTranslator::new()),
+                vec![Language::English, Language::ChineseTraditional],
+            ),
+        )
+        .with_backend("upper", state(Box::new(Upper), vec![Language::German]));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, server));
+        Client::connect(format!("http://{address}")).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn translate() {
+        let mut client = start().await;
+        let texts = ["Hello".to_owned(), "<b>World</b>".to_owned()];
+        let translated = client
+            .translate(&texts, None, Language::German)
+            .await
+            .unwrap();
+        assert_eq!(translated, texts);
+
+        let mut upper = client.clone().with_backend("upper");
+        let translated = upper
+            .translate(&texts, Some(Language::English), Language::German)
+            .await
+            .unwrap();
+        assert_eq!(translated, ["HELLO", "<B>WORLD</B>"]);
+        let translated = upper
+            .translate_html(&texts[1], Some(Language::English), Language::German)
+            .await
+            .unwrap();
+        assert_eq!(translated, "<b>WORLD</b>");
+
+        let response = client
+            .inner()
+            .translate(TranslateRequest {
+                texts: texts.to_vec(),
+                target: "deu".to_owned(),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.translations[0].source, "eng");
+
+        let status = client
+            .inner()
+            .translate(TranslateRequest {
+                texts: texts.to_vec(),
+                target: "xx".to_owned(),
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        let status = client
+            .with_backend("missing")
+            .translate(&texts, None, Language::German)
+            .await
+            .unwrap_err()
+            .downcast::<Status>()
+            .unwrap();
+        assert_eq!(status.code(), tonic::Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn translate_batch() {
+        let mut client = start().await.with_backend("upper");
+        let batches = (0..50)
+            .map(|i| vec![format!("text {i}"), format!("more {i}")])
+            .collect::<Vec<_>>();
+        let translated = client
+            .translate_batch(batches.clone(), Some(Language::English), Language::German)
+            .await
+            .unwrap();
+        assert_eq!(translated.len(), batches.len());
+        for (i, batch) in translated.into_iter().enumerate() {
+            assert_eq!(batch, [format!("TEXT {i}"), format!("MORE {i}")]);
+        }
+    }
+
+    #[tokio::test]
+    async fn detect_and_languages() {
+        let mut client = start().await;
+        assert_eq!(
+            client.detect("Hello").await.unwrap(),
+            Some(Language::English)
+        );
+        assert_eq!(client.detect("こんにちは").await.unwrap(), None);
+
+        let response = client
+            .inner()
+            .list_languages(ListLanguagesRequest::default())
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.backends, ["original", "upper"]);
+        assert_eq!(response.languages[0].code, "eng");
+        assert_eq!(response.languages[0].name, "English");
+        assert_eq!(response.languages[1].code, "zho-Hant");
+        assert_eq!(
+            client.with_backend("upper").languages().await.unwrap(),
+            [Language::German]
+        );
+    }
+}
@@ -0,0 +1,313 @@
+//! CTranslate2 model with its tokenizer.
+
+use std::path::Path;
+
+use anyhow::{Context, ensure};
+use ct2rs::{BatchType, ComputeType, Config, Device, Tokenizer, TranslationOptions, sys};
+
+use crate::decode::Alternative;
+
+/// Longest input of the offline models in tokens, longer queries are split. All of them were
+/// trained with 1024 source positions.
+pub const MAX_INPUT_LENGTH: usize = 1024;
+
+/// Log-likelihood of a target under the model, given its source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Score {
+    /// Target tokens, ending with the end of sentence token
+    pub tokens: Vec<String>,
+    /// Log probability of every token
+    pub token_scores: Vec<f32>,
+}
+
+impl Score {
+    /// Log probability of the whole target.
+    pub fn total(&self) -> f32 {
+        self.token_scores.iter().sum()
+    }
+
+    /// Mean log probability per token, comparable between targets of different length.
+    pub fn normalized(&self) -> f32 {
+        match self.token_scores.len() {
+            0 => 0.0,
+            len => self.total() / len as f32,
+        }
+    }
+}
+
+/// Threads of a loaded model.
+#[derive(Debug, Clone, Copy)]
+pub struct Threads {
+    /// Batches translated at once, each by a replica of the model sharing its weights
+    pub inter: usize,
+    /// Threads per batch, 0 lets CTranslate2 pick
+    pub intra: usize,
+}
+
+impl Default for Threads {
+    fn default() -> Self {
+        Self { inter: 1, intra: 0 }
+    }
+}
+
+impl Threads {
+    /// Config of a model on the CPU, or on the first GPU with `cuda`.
+    pub fn config(&self, cuda: bool, compute_type: ComputeType) -> Config {
+        Config {
+            device: match cuda {
+                true => Device::CUDA,
+                false => Device::CPU,
+            },
+            compute_type,
+            device_indices: vec![0; self.inter.max(1)],
+            num_threads_per_replica: self.intra,
+            ..Default::default()
+        }
+    }
+}
+
+/// Key of `model` in the [`ModelManager`](crate::manager::ModelManager), the same for
+/// translators that load it from the directory `path` the same way.
+pub fn model_key(
+    model: &str,
+    path: &Path,
+    cuda: bool,
+    compute_type: ComputeType,
+    threads: Threads,
+) -> String {
+    let device = match cuda {
+        true => "cuda",
+        false => "cpu",
+    };
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    format!(
+        "{model}/{device}/{compute_type:?}/{}x{}@{}",
+        threads.inter,
+        threads.intra,
+        path.display()
+    )
+}
+
+/// Indices of the inputs with `lengths` in batches of up to `max_batch_size` inputs or
+/// tokens, shortest first. A batch costs its longest input for every input, like with padding.
+/// With 0 all inputs are one batch in their order.
+fn batches(lengths: &[usize], max_batch_size: usize, batch_type: BatchType) -> Vec<Vec<usize>> {
+    if lengths.is_empty() {
+        return vec![];
+    }
+    if max_batch_size == 0 {
+        return vec![(0..lengths.len()).collect()];
+    }
+    let mut order = (0..lengths.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| lengths[i]);
+    let mut batches: Vec<Vec<usize>> = vec![];
+    for i in order {
+        let size = |batch: &Vec<usize>| match batch_type {
+            BatchType::Examples => batch.len() + 1,
+            BatchType::Tokens => (batch.len() + 1) * lengths[i],
+        };
+        match batches.last_mut() {
+            Some(batch) if size(batch) <= max_batch_size => batch.push(i),
+            _ => batches.push(vec![i]),
+        }
+    }
+    batches
+}
+
+/// Like `ct2rs::Translator`, but keeps every hypothesis and its score.
+pub struct Ct2Translator<T: Tokenizer> {
+    translator: sys::Translator,
+    tokenizer: T,
+}
+
+impl<T: Tokenizer> Ct2Translator<T> {
+    pub fn new(
+        model_path: impl AsRef<Path>,
+        tokenizer: T,
+        config: &Config,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            translator: sys::Translator::new(model_path, config)?,
+            tokenizer,
+        })
+    }
+
+    pub fn tokenizer(&self) -> &T {
+        &self.tokenizer
+    }
+
+    /// Hypotheses of every source, best first. Translations start with the tokens of
+    /// `target_prefix`, if given.
+    ///
+    /// Encodes the sources with [`Tokenizer::encode`], which fails for models that need a
+    /// language token per input like NLLB, see [`Ct2Translator::translate_tokens`].
+    pub fn translate_batch(
+        &self,
+        source: &[String],
+        target_prefix: Option<&[Vec<String>]>,
+        options: &TranslationOptions<String, String>,
+    ) -> anyhow::Result<Vec<Vec<Alternative>>> {
+        let tokens = source
+            .iter()
+            .map(|v| self.tokenizer.encode(v))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        self.translate_tokens(tokens, target_prefix, options)
+    }
+
+    /// [`Ct2Translator::translate_batch`] of sources the caller encoded, e.g. each with the
+    /// token of its own language. The only way to translate with NLLB, M2M100 and mBART-50.
+    pub fn translate_tokens(
+        &self,
+        mut tokens: Vec<Vec<String>>,
+        target_prefix: Option<&[Vec<String>]>,
+        options: &TranslationOptions<String, String>,
+    ) -> anyhow::Result<Vec<Vec<Alternative>>> {
+        if let Some(prefix) = target_prefix {
+            ensure!(
+                tokens.len() == prefix.len(),
+                "{} sources for {} prefixes",
+                tokens.len(),
+                prefix.len()
+            );
+        }
+        let lengths = tokens.iter().map(|v| v.len()).collect::<Vec<_>>();
+        let mut results = (0..tokens.len()).map(|_| None).collect::<Vec<_>>();
+        let batches = batches(&lengths, options.max_batch_size, options.batch_type);
+        // the batches are sorted and sized already, CTranslate2 would do it again for each
+        let options = &TranslationOptions {
+            max_batch_size: 0,
+            ..options.clone()
+        };
+        for batch in batches {
+            let source = batch
+                .iter()
+                .map(|&i| std::mem::take(&mut tokens[i]))
+                .collect::<Vec<_>>();
+            let translated = match target_prefix {
+                Some(prefix) => {
+                    let prefix = batch.iter().map(|&i| prefix[i].clone()).collect::<Vec<_>>();
+                    self.translator
+                        .translate_batch_with_target_prefix(&source, &prefix, options, None)?
+                }
+                None => self.translator.translate_batch(&source, options, None)?,
+            };
+            for (i, result) in batch.into_iter().zip(translated) {
+                results[i] = Some(result);
+            }
+        }
+        results
+            .into_iter()
+            .map(|result| {
+                let result = result.context("translation missing")?;
+                let scores = result.scores;
+                result
+                    .hypotheses
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, tokens)| {
+                        Ok(Alternative {
+                            text: self.tokenizer.decode(tokens)?,
+                            score: scores.get(i).copied(),
+                        })
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Scores every `target`, given as tokens of the target side, as translation of its
+    /// source. Like [`Ct2Translator::translate_batch`], only for tokenizers without language
+    /// tokens.
+    pub fn score_batch(
+        &self,
+        source: &[String],
+        target: &[Vec<String>],
+    ) -> anyhow::Result<Vec<Score>> {
+        let source = source
+            .iter()
+            .map(|v| self.tokenizer.encode(v))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        self.score_tokens(&source, target)
+    }
+
+    /// [`Ct2Translator::score_batch`] of sources the caller encoded, also with language
+    /// tokens.
+    pub fn score_tokens(
+        &self,
+        source: &[Vec<String>],
+        target: &[Vec<String>],
+    ) -> anyhow::Result<Vec<Score>> {
+        ensure!(
+            source.len() == target.len(),
+            "{} sources for {} targets",
+            source.len(),
+            target.len()
+        );
+        Ok(self
+            .translator
+            .score_batch(source, target, &Default::default())?
+            .into_iter()
+            .map(|v| Score {
+                tokens: v.tokens,
+                token_scores: v.tokens_score,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn score() {
+        let score = Score {
+            tokens: vec!["▁Hallo".to_owned(), "▁Welt".to_owned(), "</s>".to_owned()],
+            token_scores: vec![-0.5, -1.0, -0.0],
+        };
+        assert_eq!(score.total(), -1.5);
+        assert_eq!(score.normalized(), -0.5);
+        let empty = Score {
+            tokens: vec![],
+            token_scores: vec![],
+        };
+        assert_eq!(empty.normalized(), 0.0);
+    }
+
+    #[test]
+    fn keys_of_model_dirs() {
+        let dir = std::env::temp_dir().join(format!("aio-ct2-key-{}", std::process::id()));
+        let (a, b) = (dir.join("a"), dir.join("b"));
+        std::fs::create_dir_all(&a).unwrap();
+        std::fs::create_dir_all(&b).unwrap();
+        let key = |path: &Path| {
+            model_key(
+                "nllb/600M",
+                path,
+                false,
+                ComputeType::INT8,
+                Threads::default(),
+            )
+        };
+        assert_ne!(key(&a), key(&b));
+        assert_eq!(key(&a), key(&b.join("..").join("a")));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn sorted_batches() {
+        let lengths = [5, 1, 3, 8, 2];
+        assert_eq!(batches(&lengths, 0, BatchType::Tokens), [[0, 1, 2, 3, 4]]);
+        assert_eq!(
+            batches(&lengths, 2, BatchType::Examples),
+            [vec![1, 4], vec![2, 0], vec![3]]
+        );
+        // 3 * 3 tokens fit, 8 is over the budget but still translated
+        assert_eq!(
+            batches(&lengths, 9, BatchType::Tokens),
+            [vec![1, 4, 2], vec![0], vec![3]]
+        );
+        assert!(batches(&[], 4, BatchType::Tokens).is_empty());
+        assert!(batches(&[], 0, BatchType::Tokens).is_empty());
+    }
+}
@@ -0,0 +1,176 @@
+//! Decoding settings of the CTranslate2 translators.
+
+use ct2rs::{BatchType, TranslationOptions};
+
+use crate::error::Error;
+
+/// Hypothesis of a translation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alternative {
+    pub text: String,
+    /// Log probability, normalized by the length penalty. Higher is more confident.
+    pub score: Option<f32>,
+}
+
+/// Text of the best alternative of every query.
+pub fn best(alternatives: Vec<Vec<Alternative>>) -> Result<Vec<String>, Error> {
+    alternatives
+        .into_iter()
+        .map(|v| {
+            v.into_iter()
+                .next()
+                .map(|v| v.text)
+                .ok_or(Error::NoResponse)
+        })
+        .collect()
+}
+
+/// Search and sampling settings of a translation.
+///
+/// [`Default`] is the beam search the offline translators always used,
+/// [`DecodeOptions::greedy`] is deterministic and fast, [`DecodeOptions::sampling`] varies the
+/// wording.
+#[derive(Debug, Clone, Copy)]
+pub struct DecodeOptions {
+    pub beam_size: usize,
+    /// Exponent of the length normalization, above 1 favors longer translations
+    pub length_penalty: f32,
+    /// Above 1 penalizes tokens that were already generated
+    pub repetition_penalty: f32,
+    /// Tokens generated at most per translation
+    pub max_decoding_length: usize,
+    /// Samples from the k most likely tokens, 1 picks the most likely one
+    pub sampling_topk: usize,
+    pub sampling_temperature: f32,
+    /// No n-gram of this size is generated twice, 0 allows repetitions
+    pub no_repeat_ngram_size: usize,
+    /// Queries per batch, or tokens with [`BatchType::Tokens`]. Batches are built from the
+    /// queries sorted by length, so they need little padding. 0 translates all queries at once.
+    pub max_batch_size: usize,
+    /// Whether `max_batch_size` counts queries or tokens
+    pub batch_type: BatchType,
+    /// Alternatives returned per query, beam search widens the beam to at least this
+    pub num_hypotheses: usize,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self {
+            beam_size: 5,
+            length_penalty: 1.0,
+            repetition_penalty: 3.0,
+            max_decoding_length: 256,
+            sampling_topk: 1,
+            sampling_temperature: 1.0,
+            no_repeat_ngram_size: 0,
+            max_batch_size: 0,
+            batch_type: BatchType::Examples,
+            num_hypotheses: 1,
+        }
+    }
+}
+
+impl DecodeOptions {
+    /// Most likely token at every step.
+    pub fn greedy() -> Self {
+        Self {
+            beam_size: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Random sampling from the `topk` most likely tokens, 0 samples from all of them.
+    pub fn sampling(topk: usize, temperature: f32) -> Self {
+        Self {
+            beam_size: 1,
+            sampling_topk: topk,
+            sampling_temperature: temperature,
+            ..Self::default()
+        }
+    }
+
+    /// `n` alternatives per query.
+    pub fn alternatives(mut self, n: usize) -> Self {
+        self.num_hypotheses = n.max(1);
+        self
+    }
+
+    /// Batches of up to `tokens` tokens, padding included.
+    pub fn batch_tokens(mut self, tokens: usize) -> Self {
+        self.max_batch_size = tokens;
+        self.batch_type = BatchType::Tokens;
+        self
+    }
+
+    /// Longest piece a query is split into for a model reading up to `model_length` tokens.
+    /// Translations are cut at `max_decoding_length`, so the pieces stay below it as well.
+    pub fn segment_length(&self, model_length: usize) -> usize {
+        model_length.min(self.max_decoding_length)
+    }
+
+    /// Options of scored translations without unknown tokens.
+    pub fn to_translation_options(&self) -> TranslationOptions<String, String> {
+        let sampling = self.sampling_topk != 1;
+        TranslationOptions {
+            beam_size: match sampling {
+                true => self.beam_size,
+                false => self.beam_size.max(self.num_hypotheses),
+            },
+            length_penalty: self.length_penalty,
+            repetition_penalty: self.repetition_penalty,
+            max_decoding_length: self.max_decoding_length,
+            sampling_topk: self.sampling_topk,
+            sampling_temperature: self.sampling_temperature,
+            no_repeat_ngram_size: self.no_repeat_ngram_size,
+            max_batch_size: self.max_batch_size,
+            batch_type: self.batch_type,
+            num_hypotheses: self.num_hypotheses,
+            return_scores: true,
+            replace_unknowns: true,
+            disable_unk: true,
+            return_alternatives: false,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translation_options() {
+        let options = DecodeOptions::default().to_translation_options();
+        assert_eq!(options.beam_size, 5);
+        assert_eq!(options.repetition_penalty, 3.0);
+        assert_eq!(options.num_hypotheses, 1);
+        assert!(options.disable_unk);
+        assert_eq!(DecodeOptions::default().segment_length(1024), 256);
+
+        let options = DecodeOptions::sampling(10, 0.8).to_translation_options();
+        assert_eq!(options.beam_size, 1);
+        assert_eq!(options.sampling_topk, 10);
+        assert_eq!(options.sampling_temperature, 0.8);
+
+        let options = DecodeOptions::greedy()
+            .alternatives(3)
+            .to_translation_options();
+        assert_eq!(options.beam_size, 3);
+        assert_eq!(options.num_hypotheses, 3);
+        assert!(options.return_scores);
+    }
+
+    #[test]
+    fn best_alternative() {
+        let alternative = |text: &str, score| Alternative {
+            text: text.to_owned(),
+            score: Some(score),
+        };
+        let alternatives = vec![
+            vec![alternative("a", -0.1), alternative("b", -0.5)],
+            vec![alternative("c", -0.2)],
+        ];
+        assert_eq!(best(alternatives).unwrap(), ["a", "c"]);
+        assert!(best(vec![vec![]]).is_err());
+    }
+}
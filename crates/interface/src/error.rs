@@ -18,6 +18,42 @@ pub enum Error {
     RequestToLong(u32, u32),
     #[error("Request failed with status code")]
     RequestFailed(u16),
+    #[error("Translator needs a source language")]
+    MissingSourceLanguage,
+    #[error("Translator returned a different amount of results")]
+    ResultCountMismatch(usize, usize),
+}
+
+impl Error {
+    /// Whether repeating the request may succeed, after a timeout, 429 or 5xx.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::Reqwest(e) => transient_reqwest(e),
+            Error::RequestFailed(status) => transient_status(*status),
+            _ => false,
+        }
+    }
+}
+
+/// [`Error::is_transient`] of any error, also of a [`reqwest::Error`] returned as is.
+pub fn is_transient(e: &anyhow::Error) -> bool {
+    e.chain()
+        .find_map(|e| match e.downcast_ref::<Error>() {
+            Some(e) => Some(e.is_transient()),
+            None => e.downcast_ref::<reqwest::Error>().map(transient_reqwest),
+        })
+        .unwrap_or(false)
+}
+
+fn transient_status(status: u16) -> bool {
+    status == 429 || (500..600).contains(&status)
+}
+
+fn transient_reqwest(e: &reqwest::Error) -> bool {
+    match e.status() {
+        Some(status) => transient_status(status.as_u16()),
+        None => e.is_timeout() || e.is_connect() || e.is_request(),
+    }
 }
 
 #[derive(Debug)]
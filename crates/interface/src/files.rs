@@ -0,0 +1,397 @@
+//! Model files of the offline translators from disk or a mirror instead of the default URLs.
+//!
+//! A local artifact is the file its URL serves, like `600M-distilled.tar.gz` or
+//! `sentencepiece.bpe.model`, and has to match the hash of the download. Archives are extracted
+//! once into a directory named like the model key, next to the archive unless
+//! [`ModelFiles::extract_to`] points elsewhere.
+//!
+//! An already extracted model directory can be given with [`ModelFiles::path`]. The hash is the
+//! one of the archive, so the directory has to hold the `.sha256` marker [`ModelFiles`] writes
+//! when it extracts an archive. Directories without one are only taken with
+//! [`ModelFiles::trust_unverified`].
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::{Context, bail, ensure};
+use flate2::read::GzDecoder;
+use interface_model::ModelSource;
+use sha2::{Digest, Sha256};
+
+/// Marks an extracted archive with its hash.
+const MARKER: &str = ".sha256";
+
+/// Where an offline translator gets its model files, by default all are downloaded.
+#[derive(Debug, Clone, Default)]
+pub struct ModelFiles {
+    dir: Option<PathBuf>,
+    paths: HashMap<String, PathBuf>,
+    extract: Option<PathBuf>,
+    mirrors: HashMap<String, String>,
+    trust_unverified: bool,
+}
+
+impl ModelFiles {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes the artifacts from `dir`, named like the last segment of their URL.
+    pub fn local(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: Some(dir.into()),
+            ..Self::default()
+        }
+    }
+
+    /// Takes the artifact of `key`, an archive, a file or an extracted model directory, from
+    /// `path`.
+    pub fn path(mut self, key: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        self.paths.insert(key.into(), path.into());
+        self
+    }
+
+    /// Extracts the archives into `dir`, e.g. when they are on a read-only mount.
+    pub fn extract_to(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.extract = Some(dir.into());
+        self
+    }
+
+    /// Takes extracted model directories given with [`ModelFiles::path`] without a `.sha256`
+    /// marker, their files are not checked.
+    pub fn trust_unverified(mut self, trust: bool) -> Self {
+        self.trust_unverified = trust;
+        self
+    }
+
+    /// Downloads `key` from `url` instead, which has to serve the same file.
+    pub fn mirror(mut self, key: impl Into<String>, url: impl Into<String>) -> Self {
+        self.mirrors.insert(key.into(), url.into());
+        self
+    }
+
+    /// `models` with the URLs of the mirrors.
+    pub fn sources(
+        &self,
+        mut models: HashMap<&'static str, ModelSource>,
+    ) -> HashMap<&'static str, ModelSource> {
+        for (key, source) in models.iter_mut() {
+            if let Some(url) = self.mirrors.get(*key) {
+                source.url = static_url(url);
+            }
+        }
+        models
+    }
+
+    /// Path of `file`, relative to the model directory, of the model `key` in `models`.
+    /// `None` if the artifact is not local and has to be downloaded.
+    pub fn resolve(
+        &self,
+        models: &HashMap<&'static str, ModelSource>,
+        key: &str,
+        file: &str,
+    ) -> anyhow::Result<Option<PathBuf>> {
+        let source = models
+            .get(key)
+            .with_context(|| format!("unknown model {key}"))?;
+        let artifact = match (self.paths.get(key), &self.dir) {
+            (Some(path), _) => path.clone(),
+            (None, Some(dir)) => dir.join(artifact_name(source.url)),
+            (None, None) => return Ok(None),
+        };
+        if artifact.is_dir() {
+            let marked = check_marker(&artifact, source.hash)?;
+            ensure!(
+                marked || self.trust_unverified,
+                "{} has no {MARKER} marker to verify it, see ModelFiles::trust_unverified",
+                artifact.display()
+            );
+            return extracted(&artifact, key, file).map(Some);
+        }
+        if !is_archive(&artifact) {
+            verify(&artifact, source.hash)?;
+            return Ok(Some(artifact));
+        }
+
+        let dir = match &self.extract {
+            Some(dir) => dir.clone(),
+            None => artifact.parent().map(Path::to_path_buf).unwrap_or_default(),
+        };
+        let target = dir.join(key);
+        // the marker is only written after the archive was verified
+        let extracted_before =
+            fs::read_to_string(target.join(MARKER)).is_ok_and(|v| v == source.hash);
+        if !extracted_before {
+            verify(&artifact, source.hash)?;
+            extract(&artifact, &dir, key)
+                .with_context(|| format!("could not extract {}", artifact.display()))?;
+            fs::write(target.join(MARKER), source.hash)?;
+        }
+        extracted(&target, key, file).map(Some)
+    }
+}
+
+/// `url` as [`ModelSource`] holds it, every distinct URL is leaked once.
+fn static_url(url: &str) -> &'static str {
+    static URLS: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+    let mut urls = URLS.lock().unwrap_or_else(|e| e.into_inner());
+    match urls.get(url) {
+        Some(url) => url,
+        None => {
+            let url = Box::leak(url.to_owned().into_boxed_str());
+            urls.insert(url);
+            url
+        }
+    }
+}
+
+/// Whether the extracted model `dir` has a marker, which has to be the one of `hash`.
+fn check_marker(dir: &Path, hash: &str) -> anyhow::Result<bool> {
+    let Ok(marker) = fs::read_to_string(dir.join(MARKER)) else {
+        return Ok(false);
+    };
+    ensure!(
+        marker == hash,
+        "{} was extracted from an archive with hash {marker}, expected {hash}",
+        dir.display()
+    );
+    Ok(true)
+}
+
+/// Path of `file`, which may start with the directory `key`, in the extracted model `dir`.
+fn extracted(dir: &Path, key: &str, file: &str) -> anyhow::Result<PathBuf> {
+    let path = match file.strip_prefix(key).and_then(|v| v.strip_prefix('/')) {
+        Some(file) => dir.join(file),
+        None => dir.join(file),
+    };
+    ensure!(path.exists(), "{file} is missing in {}", dir.display());
+    Ok(path)
+}
+
+/// Last segment of `url` without the query.
+fn artifact_name(url: &str) -> &str {
+    let url = url.split(['?', '#']).next().unwrap_or(url);
+    url.rsplit('/').next().unwrap_or(url)
+}
+
+fn is_archive(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|v| v.to_str())
+        .is_some_and(|v| v.ends_with(".tar.gz") || v.ends_with(".tgz"))
+}
+
+/// Fails unless the SHA-256 of the file at `path` is `hash`.
+fn verify(path: &Path, hash: &str) -> anyhow::Result<()> {
+    let mut file =
+        File::open(path).with_context(|| format!("could not open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    let actual = format!("{:x}", hasher.finalize());
+    if !actual.eq_ignore_ascii_case(hash) {
+        bail!("hash of {} is {actual}, expected {hash}", path.display());
+    }
+    Ok(())
+}
+
+/// Unpacks `archive` into `dir/key`, whether its files are in a `key` directory or not.
+fn extract(archive: &Path, dir: &Path, key: &str) -> anyhow::Result<()> {
+    let partial = dir.join(format!(".{key}.partial"));
+    if partial.exists() {
+        fs::remove_dir_all(&partial)?;
+    }
+    fs::create_dir_all(&partial)?;
+    tar::Archive::new(GzDecoder::new(File::open(archive)?)).unpack(&partial)?;
+
+    let entries = fs::read_dir(&partial)?.collect::<Result<Vec<_>, _>>()?;
+    let source = match entries.as_slice() {
+        [entry] if entry.file_name() == key && entry.path().is_dir() => entry.path(),
+        _ => partial.clone(),
+    };
+    let target = dir.join(key);
+    if target.exists() {
+        fs::remove_dir_all(&target)?;
+    }
+    fs::rename(&source, &target)?;
+    if partial.exists() {
+        fs::remove_dir_all(&partial)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use flate2::{Compression, write::GzEncoder};
+
+    use super::*;
+
+    const HELLO_HASH: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    /// Empty directory for one test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("aio-files-{name}-{}", std::process::id()));
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn hash(path: &Path) -> &'static str {
+        let mut hasher = Sha256::new();
+        io::copy(&mut File::open(path).unwrap(), &mut hasher).unwrap();
+        Box::leak(format!("{:x}", hasher.finalize()).into_boxed_str())
+    }
+
+    /// Model `600M` as archive with a `600M` directory, and a plain tokenizer `spm`.
+    fn fake_models(dir: &Path) -> HashMap<&'static str, ModelSource> {
+        let model = dir.join("src").join("600M");
+        fs::create_dir_all(&model).unwrap();
+        fs::write(model.join("model.bin"), "weights").unwrap();
+        fs::write(model.join("config.json"), "{}").unwrap();
+        let archive = dir.join("600M.tar.gz");
+        let mut builder = tar::Builder::new(GzEncoder::new(
+            File::create(&archive).unwrap(),
+            Compression::fast(),
+        ));
+        builder.append_dir_all("600M", &model).unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+        fs::write(dir.join("sentencepiece.bpe.model"), "hello").unwrap();
+
+        HashMap::from([
+            (
+                "600M",
+                ModelSource {
+                    url: "https://example.com/releases/600M.tar.gz?download=true",
+                    hash: hash(&archive),
+                },
+            ),
+            (
+                "spm",
+                ModelSource {
+                    url: "https://example.com/releases/sentencepiece.bpe.model",
+                    hash: HELLO_HASH,
+                },
+            ),
+        ])
+    }
+
+    #[test]
+    fn local_dir() {
+        let dir = temp_dir("dir");
+        let models = fake_models(&dir);
+        let files = ModelFiles::local(&dir);
+
+        let spm = files.resolve(&models, "spm", "sentencepiece.bpe.model");
+        assert_eq!(spm.unwrap().unwrap(), dir.join("sentencepiece.bpe.model"));
+        let model = files.resolve(&models, "600M", "600M/model.bin");
+        let model = model.unwrap().unwrap();
+        assert_eq!(model, dir.join("600M").join("model.bin"));
+        assert_eq!(fs::read_to_string(&model).unwrap(), "weights");
+        assert!(dir.join("600M").join("config.json").exists());
+        // extracted once
+        fs::remove_file(dir.join("600M.tar.gz")).unwrap();
+        assert!(files.resolve(&models, "600M", "600M/model.bin").is_ok());
+
+        assert!(
+            ModelFiles::new()
+                .resolve(&models, "spm", "x")
+                .unwrap()
+                .is_none()
+        );
+        assert!(files.resolve(&models, "unknown", "x").is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn verifies_hash() {
+        let dir = temp_dir("hash");
+        let models = fake_models(&dir);
+        fs::write(dir.join("sentencepiece.bpe.model"), "tampered").unwrap();
+        let e = ModelFiles::local(&dir)
+            .resolve(&models, "spm", "sentencepiece.bpe.model")
+            .unwrap_err();
+        assert!(e.to_string().contains(HELLO_HASH), "{e}");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn archive_path_and_mirror() {
+        let dir = temp_dir("path");
+        let models = fake_models(&dir);
+        let archive = dir.join("renamed.tgz");
+        fs::rename(dir.join("600M.tar.gz"), &archive).unwrap();
+        let out = dir.join("out");
+        let files = ModelFiles::new()
+            .path("600M", &archive)
+            .extract_to(&out)
+            .mirror("spm", "https://mirror.internal/spm.model");
+
+        let model = files.resolve(&models, "600M", "600M/model.bin").unwrap();
+        assert_eq!(model.unwrap(), out.join("600M").join("model.bin"));
+        assert!(files.resolve(&models, "spm", "x").unwrap().is_none());
+
+        let sources = files.sources(models.clone());
+        assert_eq!(sources["spm"].url, "https://mirror.internal/spm.model");
+        assert_eq!(sources["spm"].hash, HELLO_HASH);
+        assert!(sources["600M"].url.starts_with("https://example.com"));
+        // the URL is leaked once, not per call
+        let again = files.clone().sources(models);
+        assert!(std::ptr::eq(sources["spm"].url, again["spm"].url));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn extracted_dirs() {
+        let dir = temp_dir("extracted");
+        let models = fake_models(&dir);
+
+        // given as path under any name, without a marker only if trusted
+        let renamed = dir.join("nllb-600M");
+        fs::rename(dir.join("src").join("600M"), &renamed).unwrap();
+        let files = ModelFiles::new().path("600M", &renamed);
+        let e = files
+            .resolve(&models, "600M", "600M/model.bin")
+            .unwrap_err();
+        assert!(e.to_string().contains("trust_unverified"), "{e}");
+        let files = files.trust_unverified(true);
+        let path = files.resolve(&models, "600M", "600M/model.bin").unwrap();
+        assert_eq!(path.unwrap(), renamed.join("model.bin"));
+        assert!(files.resolve(&models, "600M", "600M/missing.bin").is_err());
+
+        // extracted by ModelFiles before
+        let files = ModelFiles::local(&dir);
+        files.resolve(&models, "600M", "600M/model.bin").unwrap();
+        let files = ModelFiles::new().path("600M", dir.join("600M"));
+        let path = files.resolve(&models, "600M", "600M/model.bin").unwrap();
+        assert_eq!(path.unwrap(), dir.join("600M").join("model.bin"));
+
+        // extracted from another archive
+        fs::write(dir.join("600M").join(MARKER), HELLO_HASH).unwrap();
+        let e = files
+            .trust_unverified(true)
+            .resolve(&models, "600M", "600M/model.bin")
+            .unwrap_err();
+        assert!(e.to_string().contains("extracted from an archive"), "{e}");
+
+        // never taken just for its name
+        fs::remove_file(dir.join("600M").join(MARKER)).unwrap();
+        fs::remove_file(dir.join("600M.tar.gz")).unwrap();
+        let files = ModelFiles::local(&dir).trust_unverified(true);
+        assert!(files.resolve(&models, "600M", "600M/model.bin").is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn artifact_names() {
+        assert_eq!(
+            artifact_name("https://huggingface.co/a/resolve/main/1.3B.tar.gz?download=true"),
+            "1.3B.tar.gz"
+        );
+        assert_eq!(artifact_name("spm.model"), "spm.model");
+    }
+}
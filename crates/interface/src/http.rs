@@ -0,0 +1,153 @@
+use std::time::Duration;
+
+use reqwest::{Client, Proxy, RequestBuilder};
+
+use crate::error::Error;
+
+/// Connection settings of the online translators. Unset fields keep the translator's defaults.
+#[derive(Clone, Debug, Default)]
+pub struct HttpOptions {
+    client: Option<Client>,
+    base_url: Option<String>,
+    proxy: Option<String>,
+    user_agent: Option<String>,
+    timeout: Option<Duration>,
+}
+
+impl HttpOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sends requests through `client`, the proxy and user agent are not applied to it.
+    pub fn client(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Replaces scheme, host and optional path prefix of the api, e.g. `https://api.deepl.com`.
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    pub fn proxy(mut self, proxy: impl Into<String>) -> Self {
+        self.proxy = Some(proxy.into());
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Time limit of every single request.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Creates the client, `base_url` is used unless another one was set.
+    pub fn build(self, base_url: &str) -> Result<Http, Error> {
+        let client = match self.client {
+            Some(client) => client,
+            None => {
+                let mut builder = Client::builder();
+                if let Some(proxy) = self.proxy {
+                    builder = builder.proxy(Proxy::all(proxy)?);
+                }
+                if let Some(user_agent) = self.user_agent {
+                    builder = builder.user_agent(user_agent);
+                }
+                builder.build()?
+            }
+        };
+        Ok(Http {
+            client,
+            base_url: self
+                .base_url
+                .as_deref()
+                .unwrap_or(base_url)
+                .trim_end_matches('/')
+                .to_owned(),
+            timeout: self.timeout,
+        })
+    }
+}
+
+/// Client bound to the base url of an api.
+#[derive(Clone, Debug)]
+pub struct Http {
+    client: Client,
+    base_url: String,
+    timeout: Option<Duration>,
+}
+
+impl Http {
+    /// Default client without timeout.
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            client: Client::new(),
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            timeout: None,
+        }
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Appends `path`, which starts with a slash, to the base url.
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{path}", self.base_url)
+    }
+
+    pub fn get(&self, path: &str) -> RequestBuilder {
+        self.with_timeout(self.client.get(self.url(path)))
+    }
+
+    pub fn post(&self, path: &str) -> RequestBuilder {
+        self.with_timeout(self.client.post(self.url(path)))
+    }
+
+    fn with_timeout(&self, request: RequestBuilder) -> RequestBuilder {
+        match self.timeout {
+            Some(timeout) => request.timeout(timeout),
+            None => request,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options() {
+        let http = HttpOptions::new().build("https://example.com/").unwrap();
+        assert_eq!(
+            http.url("/v2/translate"),
+            "https://example.com/v2/translate"
+        );
+
+        let http = HttpOptions::new()
+            .base_url("http://127.0.0.1:8080/deepl/")
+            .timeout(Duration::from_secs(1))
+            .user_agent("aio-translator")
+            .proxy("http://127.0.0.1:3128")
+            .build("https://example.com")
+            .unwrap();
+        assert_eq!(http.url("/v2"), "http://127.0.0.1:8080/deepl/v2");
+        let request = http.post("/v2").build().unwrap();
+        assert_eq!(request.timeout(), Some(&Duration::from_secs(1)));
+
+        let e = HttpOptions::new()
+            .proxy("not a url")
+            .build("https://example.com");
+        assert!(matches!(e, Err(Error::Reqwest(_))));
+    }
+}
@@ -1,5 +1,15 @@
+#[cfg(feature = "ct2")]
+pub mod ct2;
+#[cfg(feature = "ct2")]
+pub mod decode;
 pub mod error;
+pub mod files;
+pub mod http;
+pub mod manager;
+pub mod pool;
 pub mod prompt;
+pub mod segment;
+#[cfg(feature = "ct2")]
 pub mod tokenizer;
 
 use crate::prompt::PromptBuilder;
@@ -8,12 +18,51 @@ pub use interface_model::Model;
 
 generate_language!();
 
+impl Language {
+    /// Resolves a BCP 47 style tag like `en-US` or `zh-Hant` through the ISO 639 lookups.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let tag = tag.trim().replace('_', "-");
+        let mut parts = tag.split('-');
+        let primary = parts.next()?.to_lowercase();
+        if primary == "zh"
+            && parts.any(|v| matches!(v.to_lowercase().as_str(), "hant" | "tw" | "hk" | "mo"))
+        {
+            return Some(Self::ChineseTraditional);
+        }
+        Self::from_639_1(&primary)
+            .or_else(|| Self::from_639_2B(&primary))
+            .or_else(|| Self::from_639_2T(&primary))
+    }
+
+    /// Shortest ISO 639 code of the language, suitable for file headers.
+    pub fn to_tag(&self) -> Option<&'static str> {
+        match self {
+            Self::ChineseTraditional => Some("zh-Hant"),
+            _ => self.to_639_1().or_else(|| self.to_639_2B()),
+        }
+    }
+}
+
 pub trait Translator {
     fn local(&self) -> bool;
     fn translator<'a>(&'a self) -> TranslatorTrait<'a>;
     fn translator_mut<'a>(&'a mut self) -> TranslatorMutTrait<'a>;
 }
 
+impl<T: Translator + ?Sized> Translator for Box<T> {
+    fn local(&self) -> bool {
+        (**self).local()
+    }
+
+    fn translator<'a>(&'a self) -> TranslatorTrait<'a> {
+        (**self).translator()
+    }
+
+    fn translator_mut<'a>(&'a mut self) -> TranslatorMutTrait<'a> {
+        (**self).translator_mut()
+    }
+}
+
 pub trait Detector {
     fn detect_language(&self, text: &str) -> Option<Language>;
 }
@@ -81,6 +130,22 @@ pub trait AsyncTranslator: Send + Sync {
         from: Option<Language>,
         to: &Language,
     ) -> anyhow::Result<TranslationListOutput>;
+
+    /// True if [`AsyncTranslator::translate_html_vec`] keeps markup intact on its own.
+    fn handles_html(&self) -> bool {
+        false
+    }
+
+    /// Translates HTML fragments. Translators without markup support treat them as text.
+    async fn translate_html_vec(
+        &self,
+        query: &[String],
+        context: Option<PromptBuilder>,
+        from: Option<Language>,
+        to: &Language,
+    ) -> anyhow::Result<TranslationListOutput> {
+        self.translate_vec(query, context, from, to).await
+    }
 }
 
 /// Translation Result containing the translation and the language
@@ -0,0 +1,289 @@
+//! Process-wide memory budget of the loaded offline models.
+//!
+//! The offline translators hand every model they load to [`ModelManager::global`] and only
+//! keep a [`Managed`] handle. Once the models exceed the budget, the least recently used ones
+//! are unloaded, and the translator owning one loads it again with its next request.
+
+use std::{
+    any::Any,
+    collections::HashMap,
+    fs,
+    marker::PhantomData,
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard, OnceLock},
+};
+
+struct Entry {
+    /// Tells apart a model loaded again under the same key
+    id: u64,
+    bytes: u64,
+    last_used: u64,
+    /// Handles of the model, it is unloaded once all of them are dropped
+    holders: usize,
+    model: Arc<dyn Any + Send + Sync>,
+}
+
+#[derive(Default)]
+struct State {
+    budget: Option<u64>,
+    used: u64,
+    tick: u64,
+    next_id: u64,
+    entries: HashMap<String, Entry>,
+}
+
+impl State {
+    fn touch(&mut self, key: &str, id: u64) -> Option<&Entry> {
+        self.tick += 1;
+        let tick = self.tick;
+        let entry = self.entries.get_mut(key).filter(|v| v.id == id)?;
+        entry.last_used = tick;
+        Some(entry)
+    }
+
+    /// Unloads the least recently used models until `extra` more bytes fit into the budget.
+    fn evict(&mut self, extra: u64) {
+        let Some(budget) = self.budget else {
+            return;
+        };
+        while self.used + extra > budget {
+            let Some(key) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, v)| v.last_used)
+                .map(|(k, _)| k.clone())
+            else {
+                return;
+            };
+            self.remove(&key);
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.used -= entry.bytes;
+        }
+    }
+}
+
+/// Loaded models with their approximate memory. Clones share the models.
+#[derive(Clone, Default)]
+pub struct ModelManager {
+    state: Arc<Mutex<State>>,
+}
+
+impl ModelManager {
+    /// Manager without budget.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Manager of all offline translators.
+    pub fn global() -> &'static ModelManager {
+        static GLOBAL: OnceLock<ModelManager> = OnceLock::new();
+        GLOBAL.get_or_init(ModelManager::new)
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Bytes the loaded models may take, `None` for no limit. Models over it are unloaded
+    /// right away.
+    pub fn set_budget(&self, bytes: Option<u64>) {
+        let mut state = self.state();
+        state.budget = bytes;
+        state.evict(0);
+    }
+
+    pub fn budget(&self) -> Option<u64> {
+        self.state().budget
+    }
+
+    /// Approximate bytes of the loaded models.
+    pub fn used(&self) -> u64 {
+        self.state().used
+    }
+
+    /// Keys of the loaded models, most recently used first.
+    pub fn loaded(&self) -> Vec<String> {
+        let state = self.state();
+        let mut entries = state.entries.iter().collect::<Vec<_>>();
+        entries.sort_by_key(|(_, v)| std::cmp::Reverse(v.last_used));
+        entries.into_iter().map(|(k, _)| k.clone()).collect()
+    }
+
+    /// Handle of the model loaded under `key`, shared with its other handles.
+    pub fn get<T: Send + Sync + 'static>(&self, key: &str) -> Option<Managed<T>> {
+        let mut state = self.state();
+        let id = state.entries.get(key)?.id;
+        state.touch(key, id)?.model.clone().downcast::<T>().ok()?;
+        state.entries.get_mut(key)?.holders += 1;
+        Some(self.handle(key, id))
+    }
+
+    /// Adds `model` of approximately `bytes`, after unloading the least recently used models
+    /// it does not fit next to. A model over the whole budget is still kept until the next
+    /// one is added.
+    pub fn insert<T: Send + Sync + 'static>(
+        &self,
+        key: impl Into<String>,
+        bytes: u64,
+        model: T,
+    ) -> Managed<T> {
+        let key = key.into();
+        let mut state = self.state();
+        state.remove(&key);
+        state.evict(bytes);
+        state.tick += 1;
+        state.next_id += 1;
+        let id = state.next_id;
+        let entry = Entry {
+            id,
+            bytes,
+            last_used: state.tick,
+            holders: 1,
+            model: Arc::new(model),
+        };
+        state.used += bytes;
+        state.entries.insert(key.clone(), entry);
+        self.handle(&key, id)
+    }
+
+    fn handle<T>(&self, key: &str, id: u64) -> Managed<T> {
+        Managed {
+            manager: self.clone(),
+            key: key.to_owned(),
+            id,
+            model: PhantomData,
+        }
+    }
+}
+
+/// Handle of a model in a [`ModelManager`].
+pub struct Managed<T> {
+    manager: ModelManager,
+    key: String,
+    id: u64,
+    model: PhantomData<fn() -> T>,
+}
+
+impl<T: Send + Sync + 'static> Managed<T> {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Whether the manager still holds the model.
+    pub fn is_loaded(&self) -> bool {
+        let state = self.manager.state();
+        state
+            .entries
+            .get(&self.key)
+            .is_some_and(|v| v.id == self.id)
+    }
+
+    /// The model, marked as used, or `None` once it was unloaded. It stays in memory as long
+    /// as the returned reference lives.
+    pub fn get(&self) -> Option<Arc<T>> {
+        let mut state = self.manager.state();
+        state
+            .touch(&self.key, self.id)?
+            .model
+            .clone()
+            .downcast::<T>()
+            .ok()
+    }
+}
+
+impl<T> Drop for Managed<T> {
+    fn drop(&mut self) {
+        let mut state = self.manager.state();
+        let Some(entry) = state.entries.get_mut(&self.key).filter(|v| v.id == self.id) else {
+            return;
+        };
+        entry.holders -= 1;
+        if entry.holders == 0 {
+            state.remove(&self.key);
+        }
+    }
+}
+
+/// Bytes of the files in the model directory `path`, about the memory of the loaded model.
+pub fn model_size(path: &Path) -> u64 {
+    fs::read_dir(path)
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v.ok()?.metadata().ok())
+        .filter(|v| v.is_file())
+        .map(|v| v.len())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unloads_least_recently_used() {
+        let manager = ModelManager::new();
+        manager.set_budget(Some(100));
+        let a = manager.insert("a", 40, "model a");
+        let b = manager.insert("b", 40, "model b");
+        assert_eq!(manager.used(), 80);
+        assert_eq!(*a.get().unwrap(), "model a");
+
+        // a was used after b
+        let c = manager.insert("c", 40, "model c");
+        assert!(a.is_loaded());
+        assert!(!b.is_loaded());
+        assert!(b.get().is_none());
+        assert_eq!(manager.loaded(), ["c", "a"]);
+        assert_eq!(manager.used(), 80);
+
+        // loaded again by its owner
+        let b = manager.insert("b", 40, "model b");
+        assert!(!a.is_loaded());
+        assert_eq!(manager.loaded(), ["b", "c"]);
+
+        manager.set_budget(Some(50));
+        assert!(b.is_loaded());
+        assert!(!c.is_loaded());
+        assert_eq!(manager.used(), 40);
+    }
+
+    #[test]
+    fn shares_and_releases() {
+        let manager = ModelManager::new();
+        let a = manager.insert("a", 10, 1u32);
+        let shared = manager.get::<u32>("a").unwrap();
+        assert!(manager.get::<String>("a").is_none());
+        drop(a);
+        assert_eq!(*shared.get().unwrap(), 1);
+        let model = shared.get().unwrap();
+        drop(shared);
+        assert!(manager.loaded().is_empty());
+        assert_eq!(manager.used(), 0);
+        // still usable while a translation holds it
+        assert_eq!(*model, 1);
+
+        // a handle of an unloaded model does not release the one loaded again
+        let old = manager.insert("b", 10, 1u32);
+        manager.set_budget(Some(0));
+        manager.set_budget(None);
+        let new = manager.insert("b", 10, 2u32);
+        drop(old);
+        assert_eq!(*new.get().unwrap(), 2);
+        assert_eq!(manager.used(), 10);
+    }
+
+    #[test]
+    fn size_of_model_dir() {
+        let dir = std::env::temp_dir().join(format!("aio-manager-{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("model.bin"), [0u8; 100]).unwrap();
+        fs::write(dir.join("shared_vocabulary.json"), [0u8; 20]).unwrap();
+        assert_eq!(model_size(&dir), 120);
+        assert_eq!(model_size(&dir.join("missing")), 0);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
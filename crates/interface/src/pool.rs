@@ -0,0 +1,433 @@
+//! One loaded offline model serving many callers.
+//!
+//! [`BlockingTranslator`](crate::BlockingTranslator) takes `&mut self`, so a model can only
+//! translate for one caller at a time. [`SharedTranslator`] owns the model instead, queues the
+//! requests of all its clones and lets worker threads translate them, merging queued requests
+//! of the same language pair into one batch.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Condvar, Mutex, RwLock},
+    thread,
+};
+
+use anyhow::anyhow;
+use tokio::sync::oneshot;
+
+use crate::{
+    AsyncTranslator, Language, TranslationListOutput, TranslationOutput, Translator,
+    TranslatorMutTrait, TranslatorTrait, error::Error, prompt::PromptBuilder,
+};
+
+/// Translator that translates through a shared reference once its model is loaded.
+pub trait SharedModel: Send + Sync + 'static {
+    /// Loads the model translating `from` into `to`, unless it is loaded.
+    fn prepare(&mut self, from: Language, to: &Language) -> anyhow::Result<()>;
+
+    /// Like [`BlockingTranslator::translate_vec`](crate::BlockingTranslator::translate_vec), with
+    /// the loaded model. `None` if it is not loaded, also when it was unloaded after
+    /// [`SharedModel::prepare`], e.g. by the [`ModelManager`](crate::manager::ModelManager).
+    fn translate_shared(
+        &self,
+        query: &[String],
+        from: Language,
+        to: &Language,
+    ) -> anyhow::Result<Option<Vec<String>>>;
+}
+
+/// Workers and batching of a [`SharedTranslator`].
+#[derive(Debug, Clone, Copy)]
+pub struct PoolOptions {
+    /// Batches translated at once. More than the replicas of the model, `Threads::inter` for
+    /// the CTranslate2 models, only wait for a free one.
+    pub workers: usize,
+    /// Queries merged into one batch at most. A single larger request is never split.
+    pub max_queries: usize,
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        Self {
+            workers: 1,
+            max_queries: 64,
+        }
+    }
+}
+
+struct Request {
+    query: Vec<String>,
+    from: Language,
+    to: Language,
+    reply: oneshot::Sender<anyhow::Result<Vec<String>>>,
+}
+
+#[derive(Default)]
+struct Queue {
+    requests: VecDeque<Request>,
+    closed: bool,
+}
+
+struct Inner<T> {
+    translator: RwLock<T>,
+    queue: Mutex<Queue>,
+    available: Condvar,
+    max_queries: usize,
+}
+
+/// Stops the workers once they finished the queue, when the last handle is dropped.
+struct Closer<T>(Arc<Inner<T>>);
+
+impl<T> Drop for Closer<T> {
+    fn drop(&mut self) {
+        if let Ok(mut queue) = self.0.queue.lock() {
+            queue.closed = true;
+        }
+        self.0.available.notify_all();
+    }
+}
+
+/// Handle of a model shared by all its clones, see the [module](self).
+pub struct SharedTranslator<T> {
+    inner: Arc<Inner<T>>,
+    _closer: Arc<Closer<T>>,
+}
+
+impl<T> Clone for SharedTranslator<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            _closer: self._closer.clone(),
+        }
+    }
+}
+
+impl<T: SharedModel> SharedTranslator<T> {
+    /// Starts the workers. Models are loaded by the first request that needs them.
+    pub fn new(translator: T, options: PoolOptions) -> Self {
+        let inner = Arc::new(Inner {
+            translator: RwLock::new(translator),
+            queue: Mutex::new(Queue::default()),
+            available: Condvar::new(),
+            max_queries: options.max_queries,
+        });
+        for _ in 0..options.workers.max(1) {
+            let inner = inner.clone();
+            thread::spawn(move || inner.work());
+        }
+        Self {
+            _closer: Arc::new(Closer(inner.clone())),
+            inner,
+        }
+    }
+
+    /// Runs `f` with the translator while no request is translated, e.g. to change its
+    /// settings.
+    pub fn with_translator<R>(&self, f: impl FnOnce(&mut T) -> R) -> anyhow::Result<R> {
+        let mut translator = self
+            .inner
+            .translator
+            .write()
+            .map_err(|_| anyhow!("translator poisoned"))?;
+        Ok(f(&mut translator))
+    }
+
+    /// Queues `query` and waits for its translation.
+    async fn request(
+        &self,
+        query: &[String],
+        from: Option<Language>,
+        to: &Language,
+    ) -> anyhow::Result<TranslationListOutput> {
+        let from = from.ok_or(Error::MissingSourceLanguage)?;
+        if query.is_empty() {
+            return Ok(TranslationListOutput {
+                text: vec![],
+                lang: Some(from),
+            });
+        }
+        let (reply, receiver) = oneshot::channel();
+        self.inner
+            .queue
+            .lock()
+            .map_err(|_| anyhow!("queue poisoned"))?
+            .requests
+            .push_back(Request {
+                query: query.to_vec(),
+                from,
+                to: *to,
+                reply,
+            });
+        self.inner.available.notify_one();
+        let text = receiver
+            .await
+            .map_err(|_| anyhow!("worker stopped before translating"))??;
+        Ok(TranslationListOutput {
+            text,
+            lang: Some(from),
+        })
+    }
+}
+
+impl<T: SharedModel> Inner<T> {
+    fn work(&self) {
+        while let Some(batch) = self.next_batch() {
+            let query = batch
+                .iter()
+                .flat_map(|v| v.query.iter().cloned())
+                .collect::<Vec<_>>();
+            match self.translate(&query, batch[0].from, &batch[0].to) {
+                Ok(text) if text.len() == query.len() => {
+                    let mut text = text.into_iter();
+                    for request in batch {
+                        let text = text.by_ref().take(request.query.len()).collect();
+                        let _ = request.reply.send(Ok(text));
+                    }
+                }
+                Ok(text) => {
+                    let (expected, got) = (query.len(), text.len());
+                    for request in batch {
+                        let _ = request
+                            .reply
+                            .send(Err(Error::ResultCountMismatch(expected, got).into()));
+                    }
+                }
+                Err(e) => {
+                    for request in batch {
+                        let _ = request.reply.send(Err(anyhow!("{e:#}")));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Oldest request with the queued requests of its language pair that fit into the batch,
+    /// `None` once the handles are dropped and the queue is empty.
+    fn next_batch(&self) -> Option<Vec<Request>> {
+        let mut queue = self.queue.lock().ok()?;
+        let first = loop {
+            match queue.requests.pop_front() {
+                Some(v) => break v,
+                None if queue.closed => return None,
+                None => queue = self.available.wait(queue).ok()?,
+            }
+        };
+        let mut size = first.query.len();
+        let mut batch = vec![first];
+        let mut i = 0;
+        while i < queue.requests.len() {
+            let request = &queue.requests[i];
+            if request.from == batch[0].from
+                && request.to == batch[0].to
+                && size + request.query.len() <= self.max_queries
+            {
+                size += request.query.len();
+                batch.extend(queue.requests.remove(i));
+            } else {
+                i += 1;
+            }
+        }
+        Some(batch)
+    }
+
+    fn translate(
+        &self,
+        query: &[String],
+        from: Language,
+        to: &Language,
+    ) -> anyhow::Result<Vec<String>> {
+        let translator = self
+            .translator
+            .read()
+            .map_err(|_| anyhow!("translator poisoned"))?;
+        if let Some(text) = translator.translate_shared(query, from, to)? {
+            return Ok(text);
+        }
+        drop(translator);
+        // translated under the write lock, so other workers can't unload it in between
+        let mut translator = self
+            .translator
+            .write()
+            .map_err(|_| anyhow!("translator poisoned"))?;
+        translator.prepare(from, to)?;
+        translator
+            .translate_shared(query, from, to)?
+            .ok_or_else(|| anyhow!("model for {from:?} to {to:?} was unloaded right after loading"))
+    }
+}
+
+impl<T: SharedModel> Translator for SharedTranslator<T> {
+    fn local(&self) -> bool {
+        true
+    }
+
+    fn translator<'a>(&'a self) -> TranslatorTrait<'a> {
+        TranslatorTrait::Async(self)
+    }
+
+    fn translator_mut<'a>(&'a mut self) -> TranslatorMutTrait<'a> {
+        TranslatorMutTrait::Async(self)
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: SharedModel> AsyncTranslator for SharedTranslator<T> {
+    async fn translate(
+        &self,
+        query: &str,
+        _: Option<PromptBuilder>,
+        from: Option<Language>,
+        to: &Language,
+    ) -> anyhow::Result<TranslationOutput> {
+        let mut t = self.request(&[query.to_owned()], from, to).await?;
+        Ok(TranslationOutput {
+            text: t.text.pop().ok_or(Error::NoResponse)?,
+            lang: t.lang,
+        })
+    }
+
+    async fn translate_vec(
+        &self,
+        query: &[String],
+        _: Option<PromptBuilder>,
+        from: Option<Language>,
+        to: &Language,
+    ) -> anyhow::Result<TranslationListOutput> {
+        self.request(query, from, to).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Barrier,
+        atomic::{AtomicBool, Ordering},
+    };
+
+    use super::*;
+
+    /// Upper-cases and records the size of every batch.
+    #[derive(Default)]
+    struct Upper {
+        loaded: Option<(Language, Language)>,
+        loads: usize,
+        /// Unloaded before the next translation, like by another translator
+        evicted: AtomicBool,
+        /// Waited on twice by the first translation, once it started and to finish
+        gate: Mutex<Option<Arc<Barrier>>>,
+        batches: Mutex<Vec<usize>>,
+    }
+
+    impl SharedModel for Upper {
+        fn prepare(&mut self, from: Language, to: &Language) -> anyhow::Result<()> {
+            if self.loaded != Some((from, *to)) || *self.evicted.get_mut() {
+                self.loaded = Some((from, *to));
+                self.loads += 1;
+                *self.evicted.get_mut() = false;
+            }
+            Ok(())
+        }
+
+        fn translate_shared(
+            &self,
+            query: &[String],
+            from: Language,
+            to: &Language,
+        ) -> anyhow::Result<Option<Vec<String>>> {
+            if self.loaded != Some((from, *to)) || self.evicted.load(Ordering::SeqCst) {
+                return Ok(None);
+            }
+            if let Some(gate) = self.gate.lock().unwrap().take() {
+                gate.wait();
+                gate.wait();
+            }
+            self.batches.lock().unwrap().push(query.len());
+            Ok(Some(query.iter().map(|v| v.to_uppercase()).collect()))
+        }
+    }
+
+    #[tokio::test]
+    async fn coalesces_requests() {
+        let gate = Arc::new(Barrier::new(2));
+        let upper = Upper {
+            gate: Mutex::new(Some(gate.clone())),
+            ..Upper::default()
+        };
+        let shared = SharedTranslator::new(upper, PoolOptions::default());
+        let spawn = |i| {
+            let shared = shared.clone();
+            tokio::spawn(async move {
+                let query = [format!("a{i}"), format!("b{i}")];
+                shared
+                    .translate_vec(&query, None, Some(Language::English), &Language::German)
+                    .await
+                    .map(|v| (i, v.text))
+            })
+        };
+        let wait = || {
+            let gate = gate.clone();
+            tokio::task::spawn_blocking(move || {
+                gate.wait();
+            })
+        };
+
+        // the first request blocks the worker, the others queue up and are merged
+        let mut tasks = vec![spawn(0)];
+        wait().await.unwrap();
+        tasks.extend((1..8).map(spawn));
+        while shared.inner.queue.lock().unwrap().requests.len() < 7 {
+            tokio::task::yield_now().await;
+        }
+        wait().await.unwrap();
+        for task in tasks {
+            let (i, text) = task.await.unwrap().unwrap();
+            assert_eq!(text, [format!("A{i}"), format!("B{i}")]);
+        }
+        shared
+            .with_translator(|t| {
+                assert_eq!(*t.batches.lock().unwrap(), [2, 14]);
+                assert_eq!(t.loads, 1);
+            })
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn separates_language_pairs() {
+        let shared = SharedTranslator::new(
+            Upper::default(),
+            PoolOptions {
+                workers: 2,
+                max_queries: 1,
+            },
+        );
+        let (de, fr) = tokio::join!(
+            shared.translate("a", None, Some(Language::English), &Language::German),
+            shared.translate("b", None, Some(Language::English), &Language::French),
+        );
+        assert_eq!(de.unwrap().text, "A");
+        assert_eq!(fr.unwrap().text, "B");
+        let batches = shared
+            .with_translator(|t| t.batches.lock().unwrap().clone())
+            .unwrap();
+        assert_eq!(batches, [1, 1]);
+        assert!(
+            shared
+                .translate("a", None, None, &Language::German)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn reloads_unloaded_model() {
+        let shared = SharedTranslator::new(Upper::default(), PoolOptions::default());
+        let query = ["a".to_owned()];
+        let translate =
+            || shared.translate_vec(&query, None, Some(Language::English), &Language::German);
+        translate().await.unwrap();
+        shared
+            .with_translator(|t| t.evicted.store(true, Ordering::SeqCst))
+            .unwrap();
+        assert_eq!(translate().await.unwrap().text, ["A"]);
+        assert_eq!(shared.with_translator(|t| t.loads).unwrap(), 2);
+    }
+}
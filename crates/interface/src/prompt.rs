@@ -2,6 +2,7 @@ use std::collections::HashMap;
 
 type ContentBuilder =
     fn(from: &str, to: &str, queries: &[String], data: PromptData) -> Option<String>;
+#[derive(Clone)]
 pub struct PromptBuilder {
     pd: PromptData,
     msgs: Vec<Message>,
@@ -20,6 +21,7 @@ impl PromptBuilder {
     }
 }
 
+#[derive(Clone)]
 pub struct Message {
     role: Role,
     content_builder: ContentBuilder,
@@ -78,11 +80,13 @@ impl Message {
     }
 }
 
+#[derive(Clone)]
 pub struct PromptData {
     chat_system_template: String,
     chat_sample: HashMap<String, Vec<String>>,
 }
 
+#[derive(Clone)]
 enum Role {
     System,
     User,
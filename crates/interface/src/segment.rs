@@ -0,0 +1,445 @@
+//! Splits long inputs into pieces under the token limit of a model and joins their
+//! translations again.
+//!
+//! Queries that fit are translated whole, line breaks included. Longer ones are split into
+//! lines, and the sentences of a line, also the ones ending in CJK punctuation, are grouped as
+//! long as the group fits. A sentence that is too long by itself is cut at spaces, or between
+//! characters in scripts without them. Line breaks and spaces of the input are kept in the
+//! output.
+
+use std::ops::Range;
+
+use anyhow::ensure;
+
+use crate::Language;
+#[cfg(feature = "ct2")]
+use crate::decode::Alternative;
+
+/// End a sentence, but only before a space, like `.` in `3.5`.
+const SPACED_ENDS: [char; 4] = ['.', '!', '?', '…'];
+/// End a sentence anywhere.
+const ENDS: [char; 5] = ['。', '！', '？', '｡', '‥'];
+/// Belong to the sentence they follow.
+const CLOSING: [char; 12] = [
+    '"', '\'', '”', '’', ')', ']', '）', '」', '』', '】', '》', '〉',
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Piece {
+    /// Next translation
+    Text,
+    /// Input kept as is
+    Raw(String),
+    /// Between two translations without a space in the input
+    Join,
+}
+
+/// Splits queries with the token count of a model.
+pub struct Segmenter<F> {
+    max_tokens: usize,
+    count: F,
+    joiner: &'static str,
+}
+
+impl<F: Fn(&str) -> anyhow::Result<usize>> Segmenter<F> {
+    /// Pieces of at most `max_tokens` tokens, as `count` counts them.
+    pub fn new(max_tokens: usize, count: F) -> Self {
+        Self {
+            max_tokens,
+            count,
+            joiner: " ",
+        }
+    }
+
+    /// Translations of pieces without a space between them are joined like `to` separates
+    /// sentences, by default with a space.
+    pub fn target(mut self, to: &Language) -> Self {
+        self.joiner = match to {
+            Language::Chinese
+            | Language::ChineseTraditional
+            | Language::ClassicalChinese
+            | Language::YueChinese
+            | Language::Japanese => "",
+            _ => " ",
+        };
+        self
+    }
+
+    pub fn split<S: AsRef<str>>(&self, queries: &[S]) -> anyhow::Result<Segments> {
+        let mut texts = vec![];
+        let mut layouts = vec![];
+        for query in queries {
+            let query = query.as_ref();
+            let mut layout = vec![];
+            let content = query.trim();
+            if !content.is_empty() && self.fits(content)? {
+                let start = query.len() - query.trim_start().len();
+                push_raw(&mut layout, &query[..start]);
+                texts.push(content.to_owned());
+                layout.push(Piece::Text);
+                push_raw(&mut layout, &query[start + content.len()..]);
+                layouts.push(layout);
+                continue;
+            }
+            for line in query.split_inclusive('\n') {
+                let content = line.trim();
+                if content.is_empty() {
+                    push_raw(&mut layout, line);
+                    continue;
+                }
+                let start = line.len() - line.trim_start().len();
+                let end = start + content.len();
+                push_raw(&mut layout, &line[..start]);
+                let mut last = None;
+                for piece in self.pieces(line, start..end, Level::Sentence)? {
+                    if let Some(last) = last {
+                        match &line[last..piece.start] {
+                            "" => layout.push(Piece::Join),
+                            gap => push_raw(&mut layout, gap),
+                        }
+                    }
+                    last = Some(piece.end);
+                    texts.push(line[piece].to_owned());
+                    layout.push(Piece::Text);
+                }
+                push_raw(&mut layout, &line[end..]);
+            }
+            layouts.push(layout);
+        }
+        Ok(Segments {
+            texts,
+            layouts,
+            joiner: self.joiner,
+        })
+    }
+
+    fn fits(&self, text: &str) -> anyhow::Result<bool> {
+        Ok((self.count)(text)? <= self.max_tokens)
+    }
+
+    /// Ranges of `text` in `range` that fit, made of the largest units of `level` possible.
+    fn pieces(
+        &self,
+        text: &str,
+        range: Range<usize>,
+        level: Level,
+    ) -> anyhow::Result<Vec<Range<usize>>> {
+        if self.fits(&text[range.clone()])? {
+            return Ok(vec![range]);
+        }
+        let units = match level {
+            Level::Sentence => sentences(text, range),
+            Level::Word => words(text, range),
+            Level::Char => return self.cut(text, range),
+        };
+        let mut pieces = vec![];
+        let mut current: Option<Range<usize>> = None;
+        for unit in units {
+            if let Some(c) = current.take() {
+                if self.fits(&text[c.start..unit.end])? {
+                    current = Some(c.start..unit.end);
+                    continue;
+                }
+                pieces.push(c);
+            }
+            match self.fits(&text[unit.clone()])? {
+                true => current = Some(unit),
+                false => pieces.extend(self.pieces(text, unit, level.next())?),
+            }
+        }
+        pieces.extend(current);
+        Ok(pieces)
+    }
+
+    /// Longest prefixes that fit, at least one character each.
+    fn cut(&self, text: &str, range: Range<usize>) -> anyhow::Result<Vec<Range<usize>>> {
+        let mut pieces = vec![];
+        let mut start = range.start;
+        while start < range.end {
+            let ends = text[start..range.end]
+                .char_indices()
+                .map(|(i, c)| start + i + c.len_utf8())
+                .collect::<Vec<_>>();
+            // ends[..fitting] fit
+            let (mut fitting, mut above) = (1, ends.len());
+            while fitting < above {
+                let mid = (fitting + above).div_ceil(2);
+                match self.fits(&text[start..ends[mid - 1]])? {
+                    true => fitting = mid,
+                    false => above = mid - 1,
+                }
+            }
+            pieces.push(start..ends[fitting - 1]);
+            start = ends[fitting - 1];
+        }
+        Ok(pieces)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Level {
+    Sentence,
+    Word,
+    Char,
+}
+
+impl Level {
+    fn next(self) -> Self {
+        match self {
+            Level::Sentence => Level::Word,
+            Level::Word | Level::Char => Level::Char,
+        }
+    }
+}
+
+fn push_raw(layout: &mut Vec<Piece>, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    match layout.last_mut() {
+        Some(Piece::Raw(raw)) => raw.push_str(text),
+        _ => layout.push(Piece::Raw(text.to_owned())),
+    }
+}
+
+/// Sentences in `range`, without the spaces between them.
+fn sentences(text: &str, range: Range<usize>) -> Vec<Range<usize>> {
+    let mut sentences = vec![];
+    let mut start = None;
+    let mut chars = text[range.clone()]
+        .char_indices()
+        .map(|(i, c)| (range.start + i, c))
+        .peekable();
+    while let Some((i, c)) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let first = *start.get_or_insert(i);
+        let spaced = SPACED_ENDS.contains(&c);
+        if !spaced && !ENDS.contains(&c) {
+            continue;
+        }
+        let mut end = i + c.len_utf8();
+        let mut anywhere = !spaced;
+        while let Some(&(i, c)) = chars.peek() {
+            if SPACED_ENDS.contains(&c) || ENDS.contains(&c) || CLOSING.contains(&c) {
+                anywhere |= ENDS.contains(&c);
+                end = i + c.len_utf8();
+                chars.next();
+            } else {
+                break;
+            }
+        }
+        if anywhere || chars.peek().is_none_or(|(_, c)| c.is_whitespace()) {
+            sentences.push(first..end);
+            start = None;
+        }
+    }
+    if let Some(start) = start {
+        sentences.push(start..range.end);
+    }
+    sentences
+}
+
+fn words(text: &str, range: Range<usize>) -> Vec<Range<usize>> {
+    let mut words = vec![];
+    let mut start = None;
+    for (i, c) in text[range.clone()].char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                words.push(range.start + s..range.start + i);
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        words.push(range.start + s..range.end);
+    }
+    words
+}
+
+/// Pieces of split queries and how to join their translations.
+#[derive(Debug, Clone)]
+pub struct Segments {
+    /// Pieces to translate, of all queries in order
+    pub texts: Vec<String>,
+    layouts: Vec<Vec<Piece>>,
+    joiner: &'static str,
+}
+
+impl Segments {
+    /// Query of every piece of [`Segments::texts`], as index into the split queries.
+    pub fn query_indices(&self) -> Vec<usize> {
+        self.layouts
+            .iter()
+            .enumerate()
+            .flat_map(|(i, layout)| layout.iter().filter(|v| **v == Piece::Text).map(move |_| i))
+            .collect()
+    }
+
+    /// Translation of every query, from the translations of [`Segments::texts`].
+    pub fn join(&self, translations: Vec<String>) -> anyhow::Result<Vec<String>> {
+        self.check(translations.len())?;
+        let mut translations = translations.into_iter();
+        Ok(self
+            .layouts
+            .iter()
+            .map(|layout| self.assemble(layout, translations.by_ref()))
+            .collect())
+    }
+
+    /// [`Segments::join`] for alternatives. The k-th alternatives of the pieces of a query are
+    /// joined and their log probabilities add up.
+    #[cfg(feature = "ct2")]
+    pub fn join_alternatives(
+        &self,
+        translations: Vec<Vec<Alternative>>,
+    ) -> anyhow::Result<Vec<Vec<Alternative>>> {
+        self.check(translations.len())?;
+        let mut translations = translations.into_iter();
+        Ok(self
+            .layouts
+            .iter()
+            .map(|layout| {
+                let count = layout.iter().filter(|v| **v == Piece::Text).count();
+                let pieces = translations.by_ref().take(count).collect::<Vec<_>>();
+                let n = pieces.iter().map(|v| v.len()).min().unwrap_or(1);
+                (0..n)
+                    .map(|k| Alternative {
+                        text: self.assemble(layout, pieces.iter().map(|v| v[k].text.clone())),
+                        score: pieces.iter().map(|v| v[k].score).sum(),
+                    })
+                    .collect()
+            })
+            .collect())
+    }
+
+    fn check(&self, translations: usize) -> anyhow::Result<()> {
+        ensure!(
+            translations == self.texts.len(),
+            "{} translations for {} pieces",
+            translations,
+            self.texts.len()
+        );
+        Ok(())
+    }
+
+    fn assemble(&self, layout: &[Piece], mut translations: impl Iterator<Item = String>) -> String {
+        let mut out = String::new();
+        for piece in layout {
+            match piece {
+                Piece::Text => out.push_str(translations.next().unwrap_or_default().trim()),
+                Piece::Raw(raw) => out.push_str(raw),
+                Piece::Join => out.push_str(self.joiner),
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One token per character, like CJK text in most vocabularies.
+    fn chars(text: &str) -> anyhow::Result<usize> {
+        Ok(text.chars().count())
+    }
+
+    fn split(max_tokens: usize, query: &str) -> Segments {
+        Segmenter::new(max_tokens, chars)
+            .split(&[query.to_owned()])
+            .unwrap()
+    }
+
+    #[test]
+    fn short_queries_stay_whole() {
+        let segments = Segmenter::new(100, chars)
+            .split(&["Hello World. Bye.".to_owned(), "".to_owned()])
+            .unwrap();
+        assert_eq!(segments.texts, ["Hello World. Bye."]);
+        let out = segments.join(vec!["Hallo Welt. Tschüss.".to_owned()]);
+        assert_eq!(out.unwrap(), ["Hallo Welt. Tschüss.", ""]);
+    }
+
+    #[test]
+    fn short_lines_stay_whole() {
+        // a speech bubble wrapped in the middle of the sentence
+        let segments = split(100, " Wait, where are\nyou going? \n");
+        assert_eq!(segments.texts, ["Wait, where are\nyou going?"]);
+        let out = segments.join(vec!["Warte, wohin gehst du?".to_owned()]);
+        assert_eq!(out.unwrap(), [" Warte, wohin gehst du? \n"]);
+    }
+
+    #[test]
+    fn sentences_are_grouped() {
+        let segments = split(12, "One. Two!  Three? 3.5 is four.");
+        assert_eq!(segments.texts, ["One. Two!", "Three?", "3.5 is four."]);
+        assert_eq!(segments.query_indices(), [0, 0, 0]);
+        let out = segments.join(vec!["1.".to_owned(), " 2? ".to_owned(), "4.".to_owned()]);
+        assert_eq!(out.unwrap(), ["1.  2? 4."]);
+    }
+
+    #[test]
+    fn cjk_sentences() {
+        let segments = split(8, "今日は雨。「明日は？」そうですね…");
+        assert_eq!(
+            segments.texts,
+            ["今日は雨。", "「明日は？」", "そうですね…"]
+        );
+        let translations = vec!["Rain today.".into(), "Tomorrow?".into(), "Yes.".into()];
+        let out = segments.join(translations).unwrap();
+        assert_eq!(out, ["Rain today. Tomorrow? Yes."]);
+
+        let segments = Segmenter::new(6, chars)
+            .target(&Language::Japanese)
+            .split(&["今日は雨。明日は晴れ。".to_owned()])
+            .unwrap();
+        assert_eq!(segments.texts, ["今日は雨。", "明日は晴れ。"]);
+        let out = segments.join(vec!["雨".into(), "晴れ".into()]).unwrap();
+        assert_eq!(out, ["雨晴れ"]);
+    }
+
+    #[test]
+    fn long_sentences_are_cut() {
+        let segments = split(7, "aa bb cc dddddddddd");
+        assert_eq!(segments.texts, ["aa bb", "cc", "ddddddd", "ddd"]);
+        let out = segments.join(vec!["1".into(), "2".into(), "3".into(), "4".into()]);
+        assert_eq!(out.unwrap(), ["1 2 3 4"]);
+    }
+
+    #[test]
+    fn lines_and_spaces_are_kept() {
+        let segments = split(6, "  One. Two.\r\n\n\tThree.  \n");
+        assert_eq!(segments.texts, ["One.", "Two.", "Three."]);
+        let out = segments.join(vec!["1.".into(), "2.".into(), "3.".into()]);
+        assert_eq!(out.unwrap(), ["  1. 2.\r\n\n\t3.  \n"]);
+        assert!(segments.join(vec![]).is_err());
+    }
+
+    #[cfg(feature = "ct2")]
+    #[test]
+    fn alternatives_of_pieces() {
+        let alternative = |text: &str, score| Alternative {
+            text: text.to_owned(),
+            score: Some(score),
+        };
+        let segments = Segmenter::new(4, chars)
+            .split(&["Ab. Cd.".to_owned(), " ".to_owned(), "Ef.".to_owned()])
+            .unwrap();
+        assert_eq!(segments.query_indices(), [0, 0, 2]);
+        let out = segments
+            .join_alternatives(vec![
+                vec![alternative("It.", -0.5), alternative("This.", -1.0)],
+                vec![alternative("Yes.", -0.25)],
+                vec![alternative("No.", -0.5), alternative("Nope.", -2.0)],
+            ])
+            .unwrap();
+        assert_eq!(out[0], [alternative("It. Yes.", -0.75)]);
+        assert_eq!(out[1], [alternative(" ", 0.0)]);
+        assert_eq!(out[2].len(), 2);
+        assert_eq!(out[2][1], alternative("Nope.", -2.0));
+    }
+}
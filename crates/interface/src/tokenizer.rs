@@ -15,6 +15,11 @@ impl SentenceTokenizer {
         let spp = SentencePieceTokenizer::from_file(path, false).unwrap();
         Self { spp }
     }
+
+    /// Tokens of the start of a text, without the end of sentence token.
+    pub fn encode_prefix(&self, input: &str) -> Vec<String> {
+        self.spp.tokenize(input)
+    }
 }
 
 impl ct2rs::Tokenizer for SentenceTokenizer {
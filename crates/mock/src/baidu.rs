@@ -0,0 +1,75 @@
+//! Baidu general translation `/api/trans/vip/translate`. The form is signed with
+//! `md5(appid + q + salt + key)`, using [`APP_ID`] and [`SECRET`].
+
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    Form, Json, Router,
+    extract::State,
+    response::{IntoResponse, Response},
+    routing::post,
+};
+use md5::{Digest, Md5};
+use serde_json::json;
+
+use crate::{APP_ID, SECRET, Shared};
+
+pub(crate) fn router() -> Router<Arc<Shared>> {
+    Router::new().route("/baidu/api/trans/vip/translate", post(translate))
+}
+
+/// `error_msg` of the error codes, the service answers them with status 200.
+pub(crate) fn message(code: &str) -> &'static str {
+    match code {
+        "52001" => "TIMEOUT",
+        "52002" => "SYSTEM ERROR",
+        "52003" => "UNAUTHORIZED USER",
+        "54000" => "PARAM_FROM_TO_OR_Q_EMPTY",
+        "54001" => "Invalid Sign",
+        "54003" => "Invalid Access Limit",
+        "54004" => "Insufficient account balance",
+        "54005" => "Long query too frequent",
+        "58000" => "Client IP illegal",
+        "58001" => "Unsupported language direction",
+        "58002" => "Service is closed",
+        "90107" => "Certification not passed",
+        _ => "Unknown error",
+    }
+}
+
+fn error(code: &str) -> Response {
+    Json(json!({ "error_code": code, "error_msg": message(code) })).into_response()
+}
+
+async fn translate(
+    State(shared): State<Arc<Shared>>,
+    Form(form): Form<HashMap<String, String>>,
+) -> Response {
+    let field = |name: &str| form.get(name).map(|v| v.as_str()).unwrap_or_default();
+    let (q, from, to) = (field("q"), field("from"), field("to"));
+    let (appid, salt, sign) = (field("appid"), field("salt"), field("sign"));
+    if [q, from, to, appid, salt, sign]
+        .iter()
+        .any(|v| v.is_empty())
+    {
+        return error("54000");
+    }
+    if appid != APP_ID {
+        return error("52003");
+    }
+    if sign != hex::encode(Md5::digest(format!("{appid}{q}{salt}{SECRET}"))) {
+        return error("54001");
+    }
+    if to == "auto" {
+        return error("58001");
+    }
+    let trans_result = q
+        .split('\n')
+        .map(|line| json!({ "src": line, "dst": shared.translate(line, to) }))
+        .collect::<Vec<_>>();
+    let from = match from {
+        "auto" => "en",
+        from => from,
+    };
+    Json(json!({ "from": from, "to": to, "trans_result": trans_result })).into_response()
+}
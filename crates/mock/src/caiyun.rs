@@ -0,0 +1,54 @@
+//! Caiyun `/v1/translator`, authenticated with `x-authorization: token` [`KEY`].
+
+use std::sync::Arc;
+
+use axum::{
+    Json, Router,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{KEY, Shared};
+
+const LANGUAGES: [&str; 13] = [
+    "zh", "zh-Hant", "en", "ja", "ko", "de", "fr", "es", "it", "pt", "ru", "tr", "vi",
+];
+
+pub(crate) fn router() -> Router<Arc<Shared>> {
+    Router::new().route("/caiyun/v1/translator", post(translate))
+}
+
+#[derive(Deserialize)]
+struct TranslateRequest {
+    source: Vec<String>,
+    trans_type: String,
+}
+
+async fn translate(
+    State(shared): State<Arc<Shared>>,
+    headers: HeaderMap,
+    Json(req): Json<TranslateRequest>,
+) -> Response {
+    let auth = headers.get("x-authorization").and_then(|v| v.to_str().ok());
+    if auth != Some(&format!("token {KEY}")) {
+        let body = json!({ "message": "Invalid token" });
+        return (StatusCode::UNAUTHORIZED, Json(body)).into_response();
+    }
+    let direction = req.trans_type.split_once('2').filter(|(from, to)| {
+        (*from == "auto" || LANGUAGES.contains(from)) && LANGUAGES.contains(to)
+    });
+    let Some((_, to)) = direction else {
+        let body = json!({ "message": "Unsupported trans_type" });
+        return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+    };
+    let target = req
+        .source
+        .iter()
+        .map(|v| shared.translate(v, to))
+        .collect::<Vec<_>>();
+    Json(json!({ "target": target, "rc": 0, "confidence": 0.8 })).into_response()
+}
@@ -0,0 +1,136 @@
+//! DeepL v2 `/v2/translate` and `/v2/languages`, authenticated with [`KEY`].
+
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode, header::AUTHORIZATION},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{KEY, Shared};
+
+const LANGUAGES: [(&str, &str); 30] = [
+    ("AR", "Arabic"),
+    ("BG", "Bulgarian"),
+    ("CS", "Czech"),
+    ("DA", "Danish"),
+    ("DE", "German"),
+    ("EL", "Greek"),
+    ("EN", "English"),
+    ("ES", "Spanish"),
+    ("ET", "Estonian"),
+    ("FI", "Finnish"),
+    ("FR", "French"),
+    ("HU", "Hungarian"),
+    ("ID", "Indonesian"),
+    ("IT", "Italian"),
+    ("JA", "Japanese"),
+    ("KO", "Korean"),
+    ("LT", "Lithuanian"),
+    ("LV", "Latvian"),
+    ("NB", "Norwegian"),
+    ("NL", "Dutch"),
+    ("PL", "Polish"),
+    ("PT", "Portuguese"),
+    ("RO", "Romanian"),
+    ("RU", "Russian"),
+    ("SK", "Slovak"),
+    ("SL", "Slovenian"),
+    ("SV", "Swedish"),
+    ("TR", "Turkish"),
+    ("UK", "Ukrainian"),
+    ("ZH", "Chinese"),
+];
+
+pub(crate) fn router() -> Router<Arc<Shared>> {
+    Router::new()
+        .route("/deepl/v2/translate", post(translate))
+        .route("/deepl/v2/languages", get(languages))
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "message": message }))).into_response()
+}
+
+fn unauthorized(headers: &HeaderMap) -> Option<Response> {
+    let auth = headers.get(AUTHORIZATION).and_then(|v| v.to_str().ok());
+    match auth == Some(&format!("DeepL-Auth-Key {KEY}")) {
+        true => None,
+        false => Some(error(
+            StatusCode::FORBIDDEN,
+            "Authorization failed. Please supply a valid auth_key parameter.",
+        )),
+    }
+}
+
+/// Accepts `EN`, `en` and regional variants like `EN-GB`.
+fn supported(code: &str) -> bool {
+    let code = code.split('-').next().unwrap_or_default().to_uppercase();
+    LANGUAGES.iter().any(|(v, _)| *v == code)
+}
+
+#[derive(Deserialize)]
+struct TranslateRequest {
+    text: Vec<String>,
+    source_lang: Option<String>,
+    target_lang: String,
+}
+
+async fn translate(
+    State(shared): State<Arc<Shared>>,
+    headers: HeaderMap,
+    Json(req): Json<TranslateRequest>,
+) -> Response {
+    if let Some(e) = unauthorized(&headers) {
+        return e;
+    }
+    if !supported(&req.target_lang) {
+        return error(
+            StatusCode::BAD_REQUEST,
+            "Value for 'target_lang' not supported.",
+        );
+    }
+    let source = match req.source_lang {
+        Some(source) if !supported(&source) => {
+            return error(
+                StatusCode::BAD_REQUEST,
+                "Value for 'source_lang' not supported.",
+            );
+        }
+        Some(source) => source.to_uppercase(),
+        None => "EN".to_owned(),
+    };
+    let translations = req
+        .text
+        .iter()
+        .map(|text| {
+            json!({
+                "detected_source_language": source,
+                "text": shared.translate(text, &req.target_lang),
+            })
+        })
+        .collect::<Vec<_>>();
+    Json(json!({ "translations": translations })).into_response()
+}
+
+async fn languages(headers: HeaderMap, Query(query): Query<HashMap<String, String>>) -> Response {
+    if let Some(e) = unauthorized(&headers) {
+        return e;
+    }
+    if query
+        .get("type")
+        .is_some_and(|v| v != "source" && v != "target")
+    {
+        return error(StatusCode::BAD_REQUEST, "Value for 'type' not supported.");
+    }
+    let languages = LANGUAGES
+        .iter()
+        .map(|(code, name)| json!({ "language": code, "name": name }))
+        .collect::<Vec<_>>();
+    Json(languages).into_response()
+}
@@ -0,0 +1,379 @@
+//! Record and replay of real api responses.
+//!
+//! With [`RECORD_VAR`] set, a [`Fixture`] forwards every request to the real api and saves
+//! the exchanges when dropped. Otherwise it answers with the saved exchanges in order and
+//! rejects requests that differ from the recorded ones.
+//!
+//! Request headers are not saved. Credentials handed out by [`Fixture::credential`] and the
+//! values of [`REDACTED_FIELDS`] in queries and bodies are replaced with `REDACTED`.
+
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use aio_translator_interface::http::HttpOptions;
+use axum::{
+    Router,
+    body::to_bytes,
+    extract::{Request, State},
+    http::{
+        HeaderMap, HeaderValue, StatusCode,
+        header::{CONTENT_LENGTH, CONTENT_TYPE, HOST},
+    },
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::{net::TcpListener, task::JoinHandle};
+
+/// Environment variable that switches fixtures to record mode
+pub const RECORD_VAR: &str = "AIO_TRANSLATOR_RECORD";
+/// Query and form fields holding credentials, signatures or nonces
+pub const REDACTED_FIELDS: [&str; 8] = [
+    "key",
+    "auth_key",
+    "appid",
+    "appKey",
+    "sign",
+    "salt",
+    "curtime",
+    "request_id",
+];
+const REDACTED: &str = "REDACTED";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Exchange {
+    method: String,
+    /// Path and query relative to the upstream
+    path: String,
+    request: String,
+    status: u16,
+    content_type: Option<String>,
+    response: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Tape {
+    upstream: String,
+    exchanges: Vec<Exchange>,
+}
+
+struct Inner {
+    recording: bool,
+    upstream: String,
+    client: reqwest::Client,
+    tape: Mutex<Tape>,
+    next: Mutex<usize>,
+    secrets: Mutex<Vec<String>>,
+}
+
+/// Stands in for `upstream` and replays or records the exchanges of `path`.
+pub struct Fixture {
+    path: PathBuf,
+    base_url: String,
+    inner: Arc<Inner>,
+    task: JoinHandle<()>,
+}
+
+impl Fixture {
+    /// Replays `path`, or records it from `upstream` if [`RECORD_VAR`] is set.
+    pub async fn start(path: impl Into<PathBuf>, upstream: &str) -> Self {
+        let path = path.into();
+        let recording = std::env::var_os(RECORD_VAR).is_some_and(|v| !v.is_empty());
+        let tape = match recording {
+            true => Tape {
+                upstream: upstream.to_owned(),
+                exchanges: vec![],
+            },
+            false => {
+                let data = std::fs::read_to_string(&path).unwrap_or_else(|e| {
+                    panic!(
+                        "Failed to read fixture {}, record it with {RECORD_VAR}=1: {e}",
+                        path.display()
+                    )
+                });
+                serde_json::from_str(&data).expect("Failed to parse fixture")
+            }
+        };
+        let inner = Arc::new(Inner {
+            recording,
+            upstream: upstream.trim_end_matches('/').to_owned(),
+            client: reqwest::Client::new(),
+            tape: Mutex::new(tape),
+            next: Mutex::new(0),
+            secrets: Mutex::new(vec![]),
+        });
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind fixture server");
+        let addr = listener
+            .local_addr()
+            .expect("Failed to get fixture address");
+        let router = Router::new().fallback(handle).with_state(inner.clone());
+        let task = tokio::spawn(async move {
+            let _ = axum::serve(listener, router).await;
+        });
+        Self {
+            path,
+            base_url: format!("http://{addr}"),
+            inner,
+            task,
+        }
+    }
+
+    pub fn recording(&self) -> bool {
+        self.inner.recording
+    }
+
+    pub fn url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn options(&self) -> HttpOptions {
+        HttpOptions::new().base_url(&self.base_url)
+    }
+
+    /// The environment variable `var` while recording, which is kept out of the fixture.
+    /// `REDACTED` while replaying.
+    pub fn credential(&self, var: &str) -> String {
+        if !self.inner.recording {
+            return REDACTED.to_owned();
+        }
+        let value = std::env::var(var).unwrap_or_else(|_| panic!("{var} not set"));
+        self.inner.secrets.lock().unwrap().push(value.clone());
+        value
+    }
+
+    fn save(&self) -> std::io::Result<()> {
+        let mut data = serde_json::to_string_pretty(&*self.inner.tape.lock().unwrap())?;
+        for secret in self.inner.secrets.lock().unwrap().iter() {
+            if !secret.is_empty() {
+                data = data.replace(secret.as_str(), REDACTED);
+            }
+        }
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.path, data + "\n")
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        self.task.abort();
+        if self.inner.recording && !std::thread::panicking() {
+            self.save().expect("Failed to save fixture");
+        }
+    }
+}
+
+fn redact_fields(fields: Vec<(String, String)>) -> Vec<(String, String)> {
+    fields
+        .into_iter()
+        .map(|(k, v)| match REDACTED_FIELDS.contains(&k.as_str()) {
+            true => (k, REDACTED.to_owned()),
+            false => (k, v),
+        })
+        .collect()
+}
+
+fn redact_json(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (k, v) in map.iter_mut() {
+                match REDACTED_FIELDS.contains(&k.as_str()) {
+                    true => *v = Value::String(REDACTED.to_owned()),
+                    false => redact_json(v),
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(redact_json),
+        _ => {}
+    }
+}
+
+/// Path with redacted query, unchanged if nothing had to be redacted.
+fn redact_path(path: &str) -> String {
+    let Some((path, query)) = path.split_once('?') else {
+        return path.to_owned();
+    };
+    let fields = serde_urlencoded::from_str::<Vec<(String, String)>>(query).unwrap_or_default();
+    if !fields
+        .iter()
+        .any(|(k, _)| REDACTED_FIELDS.contains(&k.as_str()))
+    {
+        return format!("{path}?{query}");
+    }
+    let query = serde_urlencoded::to_string(redact_fields(fields)).unwrap_or_default();
+    format!("{path}?{query}")
+}
+
+/// JSON and form bodies are normalized and redacted, others are kept as they are.
+fn redact_body(headers: &HeaderMap, body: &[u8]) -> String {
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    if content_type.starts_with("application/json")
+        && let Ok(mut value) = serde_json::from_slice::<Value>(body)
+    {
+        redact_json(&mut value);
+        return value.to_string();
+    }
+    if content_type.starts_with("application/x-www-form-urlencoded")
+        && let Ok(fields) = serde_urlencoded::from_bytes::<Vec<(String, String)>>(body)
+    {
+        return serde_urlencoded::to_string(redact_fields(fields)).unwrap_or_default();
+    }
+    String::from_utf8_lossy(body).into_owned()
+}
+
+fn respond(exchange: &Exchange) -> Response {
+    let status = StatusCode::from_u16(exchange.status).unwrap_or(StatusCode::OK);
+    let mut response = (status, exchange.response.clone()).into_response();
+    if let Some(content_type) = exchange
+        .content_type
+        .as_deref()
+        .and_then(|v| HeaderValue::from_str(v).ok())
+    {
+        response.headers_mut().insert(CONTENT_TYPE, content_type);
+    }
+    response
+}
+
+/// Also printed, translators rarely pass the body of a failed response on.
+fn mismatch(message: String) -> Response {
+    eprintln!("{message}");
+    (StatusCode::INTERNAL_SERVER_ERROR, message).into_response()
+}
+
+async fn handle(State(inner): State<Arc<Inner>>, req: Request) -> Response {
+    let (parts, body) = req.into_parts();
+    let body = to_bytes(body, usize::MAX).await.unwrap_or_default();
+    let path = parts
+        .uri
+        .path_and_query()
+        .map(|v| v.as_str())
+        .unwrap_or("/")
+        .to_owned();
+    let method = parts.method.to_string();
+    let request = redact_body(&parts.headers, &body);
+
+    if !inner.recording {
+        let index = {
+            let mut next = inner.next.lock().unwrap();
+            *next += 1;
+            *next - 1
+        };
+        let tape = inner.tape.lock().unwrap();
+        let Some(exchange) = tape.exchanges.get(index) else {
+            return mismatch(format!("no exchange recorded for {method} {path}"));
+        };
+        let received = (method.as_str(), redact_path(&path), request.as_str());
+        let expected = (
+            exchange.method.as_str(),
+            exchange.path.clone(),
+            exchange.request.as_str(),
+        );
+        if received != expected {
+            return mismatch(format!("expected {expected:?}, received {received:?}"));
+        }
+        return respond(exchange);
+    }
+
+    let mut headers = parts.headers.clone();
+    headers.remove(HOST);
+    headers.remove(CONTENT_LENGTH);
+    let upstream = inner
+        .client
+        .request(parts.method, format!("{}{path}", inner.upstream))
+        .headers(headers)
+        .body(body)
+        .send()
+        .await;
+    let upstream = match upstream {
+        Ok(v) => v,
+        Err(e) => return (StatusCode::BAD_GATEWAY, e.to_string()).into_response(),
+    };
+    let status = upstream.status().as_u16();
+    let content_type = upstream
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_owned());
+    let response = match upstream.bytes().await {
+        Ok(v) => String::from_utf8_lossy(&v).into_owned(),
+        Err(e) => return (StatusCode::BAD_GATEWAY, e.to_string()).into_response(),
+    };
+    let exchange = Exchange {
+        method,
+        path: redact_path(&path),
+        request,
+        status,
+        content_type,
+        response,
+    };
+    let reply = respond(&exchange);
+    inner.tape.lock().unwrap().exchanges.push(exchange);
+    reply
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redaction() {
+        assert_eq!(
+            redact_path("/language/translate/v2?key=secret&q=a"),
+            "/language/translate/v2?key=REDACTED&q=a"
+        );
+        assert_eq!(redact_path("/get?q=%22a%20b%22"), "/get?q=%22a%20b%22");
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/x-www-form-urlencoded"),
+        );
+        assert_eq!(
+            redact_body(&headers, b"q=a+b&salt=1&sign=abc&appid=42"),
+            "q=a+b&salt=REDACTED&sign=REDACTED&appid=REDACTED"
+        );
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        assert_eq!(
+            redact_body(&headers, br#"{"source":["a"],"request_id":"x"}"#),
+            r#"{"request_id":"REDACTED","source":["a"]}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn replay() {
+        let dir = std::env::temp_dir().join(format!("aio-fixture-{}", std::process::id()));
+        let path = dir.join("replay.json");
+        let tape = Tape {
+            upstream: "https://example.com".to_owned(),
+            exchanges: vec![Exchange {
+                method: "GET".to_owned(),
+                path: "/v2?key=REDACTED".to_owned(),
+                request: String::new(),
+                status: 200,
+                content_type: Some("application/json".to_owned()),
+                response: r#"{"ok":true}"#.to_owned(),
+            }],
+        };
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, serde_json::to_string(&tape).unwrap()).unwrap();
+
+        let fixture = Fixture::start(&path, "https://example.com").await;
+        assert!(!fixture.recording());
+        let key = fixture.credential("AIO_TRANSLATOR_TEST_MISSING_KEY");
+        let url = format!("{}/v2?key={key}", fixture.url());
+        let resp = reqwest::get(&url).await.unwrap();
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.text().await.unwrap(), r#"{"ok":true}"#);
+        // every exchange is replayed once
+        assert_eq!(reqwest::get(&url).await.unwrap().status(), 500);
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
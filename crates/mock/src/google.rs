@@ -0,0 +1,98 @@
+//! Google Cloud Translation v2 `/language/translate/v2` and its `/languages`, the api key
+//! [`KEY`] is passed as `key` query parameter.
+
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{KEY, Shared};
+
+/// Includes the deprecated `iw`, `jw` and the ambiguous `zh` like the real list.
+const LANGUAGES: &str = "af sq am ar hy az eu be bn bs bg ca ceb ny zh zh-CN zh-TW co hr cs da nl \
+    en eo et tl fi fr fy gl ka de el gu ht ha haw iw hi hmn hu is ig id ga it ja jw kn kk km rw \
+    ko ku ky lo la lv lt lb mk mg ms ml mt mi mr mn my ne no or ps fa pl pt pa ro ru sm gd sr st \
+    sn sd si sk sl so es su sw sv tg ta tt te th tr tk uk ur ug uz vi cy xh yi yo zu he jv";
+
+pub(crate) fn router() -> Router<Arc<Shared>> {
+    Router::new()
+        .route("/google/language/translate/v2", post(translate))
+        .route("/google/language/translate/v2/languages", get(languages))
+}
+
+fn error(status: StatusCode, message: &str, reason: &str) -> Response {
+    let body = json!({
+        "error": {
+            "code": status.as_u16(),
+            "message": message,
+            "errors": [{ "message": message, "domain": "global", "reason": reason }],
+            "status": "INVALID_ARGUMENT",
+        }
+    });
+    (status, Json(body)).into_response()
+}
+
+fn unauthorized(query: &HashMap<String, String>) -> Option<Response> {
+    match query.get("key").map(|v| v.as_str()) == Some(KEY) {
+        true => None,
+        false => Some(error(
+            StatusCode::BAD_REQUEST,
+            "API key not valid. Please pass a valid API key.",
+            "badRequest",
+        )),
+    }
+}
+
+fn supported(code: &str) -> bool {
+    LANGUAGES.split_whitespace().any(|v| v == code)
+}
+
+#[derive(Deserialize)]
+struct TranslateRequest {
+    q: Vec<String>,
+    source: Option<String>,
+    target: String,
+}
+
+async fn translate(
+    State(shared): State<Arc<Shared>>,
+    Query(query): Query<HashMap<String, String>>,
+    Json(req): Json<TranslateRequest>,
+) -> Response {
+    if let Some(e) = unauthorized(&query) {
+        return e;
+    }
+    if !supported(&req.target) || req.source.as_deref().is_some_and(|v| !supported(v)) {
+        return error(StatusCode::BAD_REQUEST, "Invalid Value", "invalid");
+    }
+    let translations = req
+        .q
+        .iter()
+        .map(|text| {
+            let translated = shared.translate(text, &req.target);
+            match req.source {
+                Some(_) => json!({ "translatedText": translated }),
+                None => json!({ "translatedText": translated, "detectedSourceLanguage": "en" }),
+            }
+        })
+        .collect::<Vec<_>>();
+    Json(json!({ "data": { "translations": translations } })).into_response()
+}
+
+async fn languages(Query(query): Query<HashMap<String, String>>) -> Response {
+    if let Some(e) = unauthorized(&query) {
+        return e;
+    }
+    let languages = LANGUAGES
+        .split_whitespace()
+        .map(|v| json!({ "language": v }))
+        .collect::<Vec<_>>();
+    Json(json!({ "data": { "languages": languages } })).into_response()
+}
@@ -0,0 +1,298 @@
+//! In-process HTTP server imitating the online translation apis, so their tests run offline.
+//! [`Fixture`] replays recorded responses of the real apis instead.
+//!
+//! Every api is served under its own prefix, [`MockServer::options`] points a translator at it.
+//! Texts are translated line by line with a small dictionary, unknown lines become
+//! `[{target}] {line}`. Every text is detected as English.
+//!
+//! ```ignore
+//! let server = MockServer::start().await;
+//! server.fail(Api::Deepl, Fault::Status(503));
+//! let t = DeeplTranslator::with_options(KEY.to_owned(), server.options(Api::Deepl))?;
+//! ```
+
+mod baidu;
+mod caiyun;
+mod deepl;
+mod fixture;
+mod google;
+mod mymemory;
+mod papago;
+mod youdao;
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use aio_translator_interface::http::HttpOptions;
+use axum::{
+    Json, Router,
+    body::{Body, to_bytes},
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+};
+use serde_json::{Value, json};
+use tokio::{net::TcpListener, task::JoinHandle};
+
+pub use fixture::{Fixture, RECORD_VAR, REDACTED_FIELDS};
+
+/// DeepL auth key, Google api key and Caiyun token
+pub const KEY: &str = "mock-key";
+/// Baidu app id and Youdao app key
+pub const APP_ID: &str = "mock-app-id";
+/// Baidu key and Youdao app secret
+pub const SECRET: &str = "mock-secret";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Api {
+    Deepl,
+    Google,
+    Baidu,
+    Youdao,
+    Caiyun,
+    MyMemory,
+    Papago,
+}
+
+impl Api {
+    pub const ALL: [Api; 7] = [
+        Api::Deepl,
+        Api::Google,
+        Api::Baidu,
+        Api::Youdao,
+        Api::Caiyun,
+        Api::MyMemory,
+        Api::Papago,
+    ];
+
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            Api::Deepl => "/deepl",
+            Api::Google => "/google",
+            Api::Baidu => "/baidu",
+            Api::Youdao => "/youdao",
+            Api::Caiyun => "/caiyun",
+            Api::MyMemory => "/mymemory",
+            Api::Papago => "/papago",
+        }
+    }
+}
+
+/// Scripted answer to the next request of an api.
+#[derive(Debug, Clone)]
+pub enum Fault {
+    /// Status with an empty body
+    Status(u16),
+    /// Status with a JSON body
+    Json(u16, Value),
+    /// Answers normally after the delay
+    Delay(Duration),
+}
+
+impl Fault {
+    /// Baidu reports errors with status 200 and an `error_code`.
+    pub fn baidu(code: &str) -> Self {
+        Fault::Json(
+            200,
+            json!({ "error_code": code, "error_msg": baidu::message(code) }),
+        )
+    }
+}
+
+/// Request as the server received it.
+#[derive(Debug, Clone)]
+pub struct Recorded {
+    pub method: String,
+    /// Path without the prefix of the api
+    pub path: String,
+    pub query: Option<String>,
+    pub body: String,
+}
+
+#[derive(Default)]
+struct Script {
+    faults: HashMap<Api, VecDeque<Fault>>,
+    requests: HashMap<Api, Vec<Recorded>>,
+    dictionary: HashMap<String, String>,
+}
+
+pub(crate) struct Shared {
+    base_url: String,
+    script: Mutex<Script>,
+}
+
+impl Shared {
+    /// Looks up every line of `text` in the dictionary.
+    pub(crate) fn translate(&self, text: &str, to: &str) -> String {
+        let script = self.script.lock().unwrap();
+        text.split('\n')
+            .map(|line| match script.dictionary.get(line) {
+                Some(translated) => translated.clone(),
+                None => format!("[{to}] {line}"),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Stops serving when dropped.
+pub struct MockServer {
+    shared: Arc<Shared>,
+    task: JoinHandle<()>,
+}
+
+impl MockServer {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind mock server");
+        let addr = listener.local_addr().expect("Failed to get mock address");
+        let dictionary = [
+            ("Hello World", "Hallo Welt"),
+            ("This is a test", "Das ist ein Test."),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_owned(), v.to_owned()))
+        .collect();
+        let shared = Arc::new(Shared {
+            base_url: format!("http://{addr}"),
+            script: Mutex::new(Script {
+                dictionary,
+                ..Default::default()
+            }),
+        });
+        let router = Router::new()
+            .merge(deepl::router())
+            .merge(google::router())
+            .merge(baidu::router())
+            .merge(youdao::router())
+            .merge(caiyun::router())
+            .merge(mymemory::router())
+            .merge(papago::router())
+            .layer(middleware::from_fn_with_state(shared.clone(), script))
+            .with_state(shared.clone());
+        let task = tokio::spawn(async move {
+            let _ = axum::serve(listener, router).await;
+        });
+        Self { shared, task }
+    }
+
+    /// Base url of `api`, used in place of the public endpoint.
+    pub fn url(&self, api: Api) -> String {
+        format!("{}{}", self.shared.base_url, api.prefix())
+    }
+
+    pub fn options(&self, api: Api) -> HttpOptions {
+        HttpOptions::new().base_url(self.url(api))
+    }
+
+    /// Answers the next unscripted request of `api` with `fault`.
+    pub fn fail(&self, api: Api, fault: Fault) {
+        let mut script = self.shared.script.lock().unwrap();
+        script.faults.entry(api).or_default().push_back(fault);
+    }
+
+    /// Adds a line to the dictionary of all apis.
+    pub fn translation(&self, text: impl Into<String>, translated: impl Into<String>) {
+        let mut script = self.shared.script.lock().unwrap();
+        script.dictionary.insert(text.into(), translated.into());
+    }
+
+    /// Requests of `api` in the order they arrived, including the failed ones.
+    pub fn requests(&self, api: Api) -> Vec<Recorded> {
+        let script = self.shared.script.lock().unwrap();
+        script.requests.get(&api).cloned().unwrap_or_default()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Records the request and applies the next scripted fault of its api.
+async fn script(State(shared): State<Arc<Shared>>, req: Request, next: Next) -> Response {
+    let path = req.uri().path().to_owned();
+    let Some(api) = Api::ALL.into_iter().find(|v| path.starts_with(v.prefix())) else {
+        return next.run(req).await;
+    };
+    let (parts, body) = req.into_parts();
+    let body = to_bytes(body, usize::MAX).await.unwrap_or_default();
+    let fault = {
+        let mut script = shared.script.lock().unwrap();
+        script.requests.entry(api).or_default().push(Recorded {
+            method: parts.method.to_string(),
+            path: path[api.prefix().len()..].to_owned(),
+            query: parts.uri.query().map(|v| v.to_owned()),
+            body: String::from_utf8_lossy(&body).into_owned(),
+        });
+        script.faults.get_mut(&api).and_then(|v| v.pop_front())
+    };
+    match fault {
+        Some(Fault::Status(status)) => return status_code(status).into_response(),
+        Some(Fault::Json(status, body)) => {
+            return (status_code(status), Json(body)).into_response();
+        }
+        Some(Fault::Delay(delay)) => tokio::time::sleep(delay).await,
+        None => {}
+    }
+    next.run(Request::from_parts(parts, Body::from(body))).await
+}
+
+fn status_code(status: u16) -> StatusCode {
+    StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn faults_are_applied_in_order() {
+        let server = MockServer::start().await;
+        server.fail(Api::Caiyun, Fault::Status(503));
+        server.fail(
+            Api::Caiyun,
+            Fault::Json(429, json!({ "message": "slow down" })),
+        );
+        let client = reqwest::Client::new();
+        let send = || {
+            client
+                .post(format!("{}/v1/translator", server.url(Api::Caiyun)))
+                .header("x-authorization", format!("token {KEY}"))
+                .json(&json!({ "source": ["Hello World"], "trans_type": "auto2de", "request_id": "demo" }))
+                .send()
+        };
+
+        assert_eq!(send().await.unwrap().status(), 503);
+        let resp = send().await.unwrap();
+        assert_eq!(resp.status(), 429);
+        assert_eq!(resp.json::<Value>().await.unwrap()["message"], "slow down");
+        let resp: Value = send().await.unwrap().json().await.unwrap();
+        assert_eq!(resp["target"], json!(["Hallo Welt"]));
+
+        let requests = server.requests(Api::Caiyun);
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/v1/translator");
+        assert!(requests[2].body.contains("auto2de"));
+        assert!(server.requests(Api::Deepl).is_empty());
+    }
+
+    #[tokio::test]
+    async fn unknown_lines_are_marked() {
+        let server = MockServer::start().await;
+        server.translation("Good morning", "Guten Morgen");
+        assert_eq!(
+            server
+                .shared
+                .translate("Good morning\nHello World\nBye", "de"),
+            "Guten Morgen\nHallo Welt\n[de] Bye"
+        );
+    }
+}
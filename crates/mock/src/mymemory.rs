@@ -0,0 +1,106 @@
+//! MyMemory `/get?q=..&langpair=from|to` and the language select of its website at `/`.
+//!
+//! Like the service, `_._._` separators are kept as they are.
+
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    response::{Html, IntoResponse, Response},
+    routing::get,
+};
+use serde_json::{Value, json};
+
+use crate::Shared;
+
+const LANGUAGES: [(&str, &str); 21] = [
+    ("ar-SA", "Arabic"),
+    ("zh-CN", "Chinese Simplified"),
+    ("zh-TW", "Chinese Traditional"),
+    ("nl-NL", "Dutch"),
+    ("en-GB", "English"),
+    ("fr-FR", "French"),
+    ("de-DE", "German"),
+    ("he-IL", "Hebrew"),
+    ("hi-IN", "Hindi"),
+    ("it-IT", "Italian"),
+    ("ja-JP", "Japanese"),
+    ("ko-KR", "Korean"),
+    ("pl-PL", "Polish"),
+    ("pt-PT", "Portuguese"),
+    ("ru-RU", "Russian"),
+    ("es-ES", "Spanish"),
+    ("sv-SE", "Swedish"),
+    ("th-TH", "Thai"),
+    ("tr-TR", "Turkish"),
+    ("uk-UA", "Ukrainian"),
+    ("vi-VN", "Vietnamese"),
+];
+
+const SEPARATOR: &str = "_._._";
+
+pub(crate) fn router() -> Router<Arc<Shared>> {
+    Router::new()
+        .route("/mymemory/get", get(translate))
+        .route("/mymemory/", get(website))
+}
+
+/// MyMemory answers with status 200 and puts the error into the response.
+fn error(status: u16, message: &str) -> Response {
+    Json(json!({
+        "responseData": { "translatedText": message },
+        "responseStatus": status,
+    }))
+    .into_response()
+}
+
+async fn translate(
+    State(shared): State<Arc<Shared>>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let Some(q) = query.get("q").filter(|v| !v.is_empty()) else {
+        return error(
+            403,
+            "NO QUERY SPECIFIED. EXAMPLE REQUEST: GET?Q=HELLO&LANGPAIR=EN|IT",
+        );
+    };
+    let Some((from, to)) = query.get("langpair").and_then(|v| v.split_once('|')) else {
+        return error(
+            403,
+            "INVALID LANGUAGE PAIR SPECIFIED. EXAMPLE: LANGPAIR=EN|IT USING 2 LETTER ISO OR RFC3066 LIKE ZH-CN. ALMOST ALL LANGUAGES SUPPORTED BUT SOME MAY HAVE NO CONTENT",
+        );
+    };
+    let translated = q
+        .split(SEPARATOR)
+        .map(|v| shared.translate(v, to))
+        .collect::<Vec<_>>()
+        .join(SEPARATOR);
+    let detected = match from {
+        "Autodetect" => json!("en"),
+        _ => Value::Null,
+    };
+    Json(json!({
+        "responseData": {
+            "translatedText": translated,
+            "match": 1,
+            "detectedLanguage": detected,
+        },
+        "quotaFinished": false,
+        "responseStatus": 200,
+        "matches": [],
+    }))
+    .into_response()
+}
+
+async fn website() -> Html<String> {
+    let options = LANGUAGES
+        .iter()
+        .map(|(code, name)| format!("<option value=\"{code}\">{name}</option>"))
+        .collect::<String>();
+    Html(format!(
+        "<html><body><select id=\"select_source_mm\">\
+        <option value=\"Autodetect\">Autodetect</option>\
+        <option value=\"------\">------</option>{options}</select></body></html>"
+    ))
+}
@@ -0,0 +1,115 @@
+//! Papago website: the pages the version key and languages are scraped from, and
+//! `/apis/n2mt/translate` checking the `PPG` authorization signed with [`VERSION`].
+
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    Form, Json, Router,
+    extract::State,
+    http::{HeaderMap, StatusCode, header::AUTHORIZATION},
+    response::{Html, IntoResponse, Response},
+    routing::{get, post},
+};
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use md5::Md5;
+use serde_json::json;
+
+use crate::Shared;
+
+/// Signed by clients, no matter which url they send the request to
+const TRANSLATE_URL: &str = "https://papago.naver.com/apis/n2mt/translate";
+/// Version key embedded in the main script
+const VERSION: &str = "v1.8.7_mock";
+
+const LANGUAGES: [&str; 16] = [
+    "ko", "en", "ja", "zh-CN", "zh-TW", "vi", "th", "id", "fr", "es", "ru", "de", "it", "pt", "hi",
+    "ar",
+];
+
+pub(crate) fn router() -> Router<Arc<Shared>> {
+    Router::new()
+        .route("/papago/", get(index))
+        .route("/papago/main.mock.js", get(main_js))
+        .route("/papago/home.mock.chunk.js", get(home_js))
+        .route("/papago/apis/n2mt/translate", post(translate))
+}
+
+async fn index() -> Html<&'static str> {
+    Html(
+        "<html><head>\
+        <link rel=\"prefetch\" href=\"/home.mock.chunk.js\">\
+        <script src=\"/main.mock.js\"></script>\
+        </head><body></body></html>",
+    )
+}
+
+async fn main_js() -> String {
+    format!("var a=function(e,t){{return[\"PPG \"+e+\":\"+t,\"{VERSION}\"]}};")
+}
+
+async fn home_js() -> String {
+    let languages = LANGUAGES
+        .iter()
+        .map(|v| format!(",\"{}\":\"{v}\"", v.to_uppercase()))
+        .collect::<String>();
+    format!("var n={{ALL:\"auto\"{languages}}};")
+}
+
+/// `PPG {device}:{hmac}` over device, url and timestamp.
+fn signature(device: &str, url: &str, timestamp: &str) -> String {
+    let mut mac = Hmac::<Md5>::new_from_slice(VERSION.as_bytes()).unwrap();
+    mac.update(format!("{device}\n{url}\n{timestamp}").as_bytes());
+    base64::engine::general_purpose::STANDARD.encode(mac.finalize().into_bytes())
+}
+
+fn unauthorized(headers: &HeaderMap) -> Option<Response> {
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+    let valid = match (header(AUTHORIZATION.as_str()), header("Timestamp")) {
+        (Some(auth), Some(timestamp)) => auth
+            .strip_prefix("PPG ")
+            .and_then(|v| v.split_once(':'))
+            .is_some_and(|(device, sign)| sign == signature(device, TRANSLATE_URL, timestamp)),
+        _ => false,
+    };
+    match valid {
+        true => None,
+        false => {
+            let body = json!({ "errorCode": "024", "errorMessage": "Authentication failed." });
+            Some((StatusCode::FORBIDDEN, Json(body)).into_response())
+        }
+    }
+}
+
+fn supported(code: &str) -> bool {
+    LANGUAGES.iter().any(|v| v.eq_ignore_ascii_case(code))
+}
+
+async fn translate(
+    State(shared): State<Arc<Shared>>,
+    headers: HeaderMap,
+    Form(form): Form<HashMap<String, String>>,
+) -> Response {
+    if let Some(e) = unauthorized(&headers) {
+        return e;
+    }
+    let field = |name: &str| form.get(name).map(|v| v.as_str()).unwrap_or_default();
+    let (source, target, text) = (field("source"), field("target"), field("text"));
+    if !(source == "auto" || supported(source)) || !supported(target) {
+        let body = json!({ "errorCode": "N2MT05", "errorMessage": "Unsupported language." });
+        return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+    }
+    let source = match source {
+        "auto" => "en",
+        source => source,
+    };
+    Json(json!({
+        "srcLangType": source,
+        "tarLangType": target,
+        "translatedText": shared.translate(text, target),
+        "langDetection": {
+            "nbests": [{ "lang": source, "prob": 0.98 }, { "lang": "de", "prob": 0.01 }]
+        },
+    }))
+    .into_response()
+}
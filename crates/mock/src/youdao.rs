@@ -0,0 +1,74 @@
+//! Youdao text translation `/api` with v3 signatures,
+//! `sha256(appKey + input + salt + curtime + appSecret)` using [`APP_ID`] and [`SECRET`].
+
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    Form, Json, Router,
+    extract::State,
+    response::{IntoResponse, Response},
+    routing::post,
+};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use crate::{APP_ID, SECRET, Shared};
+
+pub(crate) fn router() -> Router<Arc<Shared>> {
+    Router::new().route("/youdao/api", post(translate))
+}
+
+fn error(code: &str) -> Response {
+    Json(json!({ "errorCode": code })).into_response()
+}
+
+/// `q` itself up to 20 characters, otherwise the first 10, the length and the last 10.
+fn input(q: &str) -> String {
+    let chars = q.chars().collect::<Vec<_>>();
+    match chars.len() {
+        ..=20 => q.to_owned(),
+        len => format!(
+            "{}{len}{}",
+            chars[..10].iter().collect::<String>(),
+            chars[len - 10..].iter().collect::<String>()
+        ),
+    }
+}
+
+async fn translate(
+    State(shared): State<Arc<Shared>>,
+    Form(form): Form<HashMap<String, String>>,
+) -> Response {
+    let field = |name: &str| form.get(name).map(|v| v.as_str()).unwrap_or_default();
+    let (q, from, to) = (field("q"), field("from"), field("to"));
+    let (app_key, salt, curtime, sign) = (
+        field("appKey"),
+        field("salt"),
+        field("curtime"),
+        field("sign"),
+    );
+    if [q, from, to, app_key, salt, sign]
+        .iter()
+        .any(|v| v.is_empty())
+    {
+        return error("101");
+    }
+    if app_key != APP_ID {
+        return error("108");
+    }
+    let expected = Sha256::digest(format!("{app_key}{}{salt}{curtime}{SECRET}", input(q)));
+    if field("signType") != "v3" || sign != hex::encode(expected) {
+        return error("202");
+    }
+    let from = match from {
+        "auto" => "en",
+        from => from,
+    };
+    Json(json!({
+        "errorCode": "0",
+        "query": q,
+        "translation": [shared.translate(q, to)],
+        "l": format!("{from}2{to}"),
+    }))
+    .into_response()
+}
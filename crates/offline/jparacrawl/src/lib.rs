@@ -1,21 +1,31 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
 use aio_translator_interface::{
     BlockingTranslator, Language, Model, Translator, TranslatorMutTrait, TranslatorTrait,
+    ct2::{Ct2Translator, MAX_INPUT_LENGTH, Score, Threads, model_key},
+    decode::{Alternative, DecodeOptions, best},
     error::{self},
+    files::ModelFiles,
+    manager::{Managed, ModelManager, model_size},
+    pool::SharedModel,
     prompt::PromptBuilder,
+    segment::Segmenter,
     tokenizer::SentenceTokenizer,
 };
-use ct2rs::{BatchType, ComputeType, Config, Device, Tokenizer, TranslationOptions};
+use anyhow::Context;
+use ct2rs::{ComputeType, Tokenizer};
 
 use interface_model::{ModelLoad, ModelSource, impl_model_load_helpers};
 use maplit::hashmap;
 
 pub struct JParaCrawlTranslator {
     single_loaded: bool,
-    loaded_models: HashMap<String, ct2rs::Translator<MyTokenizer>>,
+    loaded_models: HashMap<String, Managed<Ct2Translator<MyTokenizer>>>,
     cuda: bool,
     compute_type: ComputeType,
+    options: DecodeOptions,
+    threads: Threads,
+    files: ModelFiles,
     size: Size,
 }
 
@@ -41,6 +51,24 @@ impl MyTokenizer {
             en_ja,
         })
     }
+
+    /// Tokens of a translation, in the language `decode` expects.
+    pub fn encode_target(&self, input: &str) -> anyhow::Result<Vec<String>> {
+        match self.en_ja {
+            false => &self.tokenizer_en,
+            true => &self.tokenizer_ja,
+        }
+        .encode(input)
+    }
+
+    /// Tokens the translation is forced to start with.
+    pub fn encode_prefix(&self, input: &str) -> Vec<String> {
+        match self.en_ja {
+            false => &self.tokenizer_en,
+            true => &self.tokenizer_ja,
+        }
+        .encode_prefix(input)
+    }
 }
 
 impl Tokenizer for MyTokenizer {
@@ -61,12 +89,16 @@ impl Tokenizer for MyTokenizer {
     }
 }
 impl JParaCrawlTranslator {
-    /// single_loaded will only allow one model to be loaded at a time.
+    /// The model of each direction is loaded with its first request. single_loaded will only
+    /// allow one model to be loaded at a time.
     pub fn new(single_loaded: bool, cuda: bool, compute_type: ComputeType, size: Size) -> Self {
         JParaCrawlTranslator {
+            single_loaded,
             compute_type,
+            options: DecodeOptions::default(),
+            threads: Threads::default(),
+            files: ModelFiles::default(),
             cuda,
-            single_loaded,
             size,
             loaded_models: Default::default(),
         }
@@ -106,16 +138,116 @@ impl BlockingTranslator for JParaCrawlTranslator {
         from: Language,
         to: &Language,
     ) -> anyhow::Result<Vec<String>> {
+        let options = self.options;
+        self.translate_vec_with(query, from, to, &options)
+    }
+}
+
+impl JParaCrawlTranslator {
+    /// Threads of every model, applied when it is loaded next.
+    pub fn with_threads(mut self, threads: Threads) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Model files from a local directory, archives or mirrors instead of the default URLs.
+    pub fn with_model_files(mut self, files: ModelFiles) -> Self {
+        self.files = files;
+        self
+    }
+
+    /// Decoding of the calls without own options.
+    pub fn with_decode_options(mut self, options: DecodeOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn set_decode_options(&mut self, options: DecodeOptions) {
+        self.options = options;
+    }
+
+    /// [`BlockingTranslator::translate_vec`] decoding with `options`.
+    pub fn translate_vec_with(
+        &mut self,
+        query: &[String],
+        from: Language,
+        to: &Language,
+        options: &DecodeOptions,
+    ) -> anyhow::Result<Vec<String>> {
+        Ok(best(
+            self.translate_vec_alternatives(query, from, to, options)?,
+        )?)
+    }
+
+    /// Up to `options.num_hypotheses` scored alternatives per query, best first.
+    pub fn translate_vec_alternatives(
+        &mut self,
+        query: &[String],
+        from: Language,
+        to: &Language,
+        options: &DecodeOptions,
+    ) -> anyhow::Result<Vec<Vec<Alternative>>> {
+        let model = self.model(from, to)?;
+        alternatives(&model, query, to, options)
+    }
+
+    /// Translations of every `source` that continue the `prefix` at the same index, like the
+    /// start of a sentence the user already typed. Up to `options.num_hypotheses` per source,
+    /// best first, each text includes its prefix.
+    pub fn complete(
+        &mut self,
+        source: &[String],
+        prefix: &[String],
+        from: Language,
+        to: &Language,
+        options: &DecodeOptions,
+    ) -> anyhow::Result<Vec<Vec<Alternative>>> {
+        let model = self.model(from, to)?;
+        let prefix = prefix
+            .iter()
+            .map(|v| model.tokenizer().encode_prefix(v))
+            .collect::<Vec<_>>();
+        model.translate_batch(source, Some(&prefix), &options.to_translation_options())
+    }
+
+    /// Log-likelihood of every `target` as translation of the `source` at the same index.
+    pub fn score(
+        &mut self,
+        source: &[String],
+        target: &[String],
+        from: Language,
+        to: &Language,
+    ) -> anyhow::Result<Vec<Score>> {
+        let model = self.model(from, to)?;
+        let target = target
+            .iter()
+            .map(|v| model.tokenizer().encode_target(v))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        model.score_batch(source, &target)
+    }
+
+    /// Loads the model translating `from` into `to`, again if the model manager unloaded it.
+    fn model(
+        &mut self,
+        from: Language,
+        to: &Language,
+    ) -> anyhow::Result<Arc<Ct2Translator<MyTokenizer>>> {
+        let (model_name, eng_src) = self.model_name(from, to)?;
+        self.load()?;
+        self.custom_load(&model_name, eng_src)?;
+        self.loaded_models
+            .get(&model_name)
+            .and_then(Managed::get)
+            .context("model was unloaded right after loading")
+    }
+
+    /// Name of the model translating `from` into `to`, and whether it reads English.
+    fn model_name(&self, from: Language, to: &Language) -> Result<(String, bool), error::Error> {
         let eng_src = match (from, to) {
             (Language::English, Language::Japanese) => true,
             (Language::Japanese, Language::English) => false,
-            _ => {
-                Err(error::Error::UnknownLanguageGroup(from, to.clone()))?;
-                false
-            }
+            _ => return Err(error::Error::UnknownLanguageGroup(from, *to)),
         };
-        self.load()?;
-
         let (from, to) = match eng_src {
             true => ("en", "ja"),
             false => ("ja", "en"),
@@ -130,67 +262,101 @@ impl BlockingTranslator for JParaCrawlTranslator {
                 Size::Large => "big",
             }
         );
-        self.custom_load(&model_name, eng_src)?;
-        let trans = self
-            .loaded_models
-            .get_mut(&model_name)
-            .expect("loaded in function")
-            .translate_batch(
-                query,
-                &TranslationOptions {
-                    batch_type: BatchType::Examples,
-                    beam_size: 5,
-                    repetition_penalty: 3.0,
-                    num_hypotheses: 1,
-                    replace_unknowns: true,
-                    disable_unk: true,
-                    return_alternatives: false,
-                    ..Default::default()
-                },
-                None,
-            )?;
-
-        Ok(trans.into_iter().map(|v| v.0).collect())
+        Ok((model_name, eng_src))
+    }
+}
+
+/// Alternatives of every query with the model of its language pair.
+fn alternatives(
+    model: &Ct2Translator<MyTokenizer>,
+    query: &[String],
+    to: &Language,
+    options: &DecodeOptions,
+) -> anyhow::Result<Vec<Vec<Alternative>>> {
+    let segments = Segmenter::new(options.segment_length(MAX_INPUT_LENGTH), |v: &str| {
+        Ok(model.tokenizer().encode(v)?.len())
+    })
+    .target(to)
+    .split(query)?;
+    let trans = model.translate_batch(&segments.texts, None, &options.to_translation_options())?;
+    segments.join_alternatives(trans)
+}
+
+impl SharedModel for JParaCrawlTranslator {
+    fn prepare(&mut self, from: Language, to: &Language) -> anyhow::Result<()> {
+        self.model(from, to)?;
+        Ok(())
+    }
+
+    fn translate_shared(
+        &self,
+        query: &[String],
+        from: Language,
+        to: &Language,
+    ) -> anyhow::Result<Option<Vec<String>>> {
+        let (name, _) = self.model_name(from, to)?;
+        let Some(model) = self.loaded_models.get(&name).and_then(Managed::get) else {
+            return Ok(None);
+        };
+        Ok(Some(best(alternatives(&model, query, to, &self.options)?)?))
     }
 }
 
 impl JParaCrawlTranslator {
     fn custom_load(&mut self, name: &str, en_ja: bool) -> anyhow::Result<()> {
-        if self.loaded_models.contains_key(name) {
+        if self.loaded_models.get(name).is_some_and(Managed::is_loaded) {
             return Ok(());
         }
-        let model = self.download_model(name, &format!("{}/model.bin", name))?;
-        let ja_path = self.download_model("spm.nopretok", "spm.nopretok/spm.ja.nopretok.model")?;
-        let en_path = self.download_model("spm.nopretok", "spm.nopretok/spm.en.nopretok.model")?;
-
+        let model = self.model_file(name, &format!("{}/model.bin", name))?;
         let model = model.parent().map(|v| v.to_path_buf()).unwrap_or(model);
+        let key = model_key(
+            &format!("{}/{name}", Self::name()),
+            &model,
+            self.cuda,
+            self.compute_type,
+            self.threads,
+        );
+        if let Some(model) = ModelManager::global().get(&key) {
+            if self.single_loaded {
+                self.loaded_models.clear();
+            }
+            self.loaded_models.insert(name.to_owned(), model);
+            return Ok(());
+        }
+        // the last handle unloads the other direction, unless another translator shares it
+        if self.single_loaded {
+            self.loaded_models.clear();
+        }
+        let ja_path = self.model_file("spm.nopretok", "spm.nopretok/spm.ja.nopretok.model")?;
+        let en_path = self.model_file("spm.nopretok", "spm.nopretok/spm.en.nopretok.model")?;
+
+        let bytes = model_size(&model);
         let my = MyTokenizer::new(en_ja, ja_path, en_path)?;
 
-        let v = ct2rs::Translator::with_tokenizer(
+        let v = Ct2Translator::new(
             model,
             my,
-            &Config {
-                device: match self.cuda {
-                    true => Device::CUDA,
-                    false => Device::CPU,
-                },
-                compute_type: self.compute_type,
-                ..Default::default()
-            },
+            &self.threads.config(self.cuda, self.compute_type),
         )?;
-        if self.single_loaded {
-            self.loaded_models.drain();
-        }
-        self.loaded_models.insert(name.to_owned(), v);
+        let model = ModelManager::global().insert(key, bytes, v);
+        self.loaded_models.insert(name.to_owned(), model);
         Ok(())
     }
+
+    /// Path of `file` of the model `key`, from the local files or downloaded.
+    fn model_file(&mut self, key: &str, file: &str) -> anyhow::Result<PathBuf> {
+        match self.files.resolve(&self.models(), key, file)? {
+            Some(path) => Ok(path),
+            None => self.download_model(key, file),
+        }
+    }
 }
 
 impl Model for JParaCrawlTranslator {
     impl_model_load_helpers!("translator", "JParaCrawl");
 
     fn models(&self) -> std::collections::HashMap<&'static str, interface_model::ModelSource> {
-        hashmap! {
+        self.files.sources(hashmap! {
             "ja-en-big" => ModelSource {
                 url: "https://github.com/frederik-uni/aiotranslator/releases/download/jparacrawl-3.0/ja-en-big.tar.gz",
                 hash: "188191b34a2002ebc9fba6c8b6e7e803006d65abe583769f20bd50a934a0be33",
@@ -219,7 +385,7 @@ impl Model for JParaCrawlTranslator {
                 url: "https://github.com/frederik-uni/aiotranslator/releases/download/jparacrawl-3.0/spm.nopretok.tar.gz",
                 hash: "ba95a8e1767df22e8d7aecbba76c418a9225ec38705955a736509979f7f5c770"
             }
-        }
+        })
     }
 
     fn unload(&mut self) {
@@ -228,10 +394,10 @@ impl Model for JParaCrawlTranslator {
 }
 
 impl ModelLoad for JParaCrawlTranslator {
-    type T = HashMap<String, ct2rs::Translator<MyTokenizer>>;
+    type T = HashMap<String, Managed<Ct2Translator<MyTokenizer>>>;
 
     fn loaded(&self) -> bool {
-        self.loaded_models.len() > 0
+        self.loaded_models.values().any(Managed::is_loaded)
     }
 
     fn get_model(&mut self) -> Option<&mut Self::T> {
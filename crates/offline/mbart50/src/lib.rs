@@ -1,52 +1,83 @@
-use std::sync::{Arc, Mutex};
+use std::{path::PathBuf, sync::Arc};
 
 use aio_translator_interface::{
     BlockingTranslator, Language, Model, Translator, TranslatorMutTrait, TranslatorTrait,
-    error::Error, prompt::PromptBuilder, tokenizer::SentenceTokenizer,
+    ct2::{Ct2Translator, MAX_INPUT_LENGTH, Score, Threads, model_key},
+    decode::{Alternative, DecodeOptions, best},
+    error::Error,
+    files::ModelFiles,
+    manager::{Managed, ModelManager, model_size},
+    pool::SharedModel,
+    prompt::PromptBuilder,
+    segment::Segmenter,
+    tokenizer::SentenceTokenizer,
 };
-use ct2rs::{BatchType, ComputeType, Config, Device, Tokenizer, TranslationOptions};
+use anyhow::{Context, bail};
+use ct2rs::{ComputeType, Tokenizer};
 
 use interface_model::{ModelLoad, ModelSource, impl_model_load_helpers};
 use maplit::hashmap;
 
 pub struct MyTokenizer {
     tokenizer: SentenceTokenizer,
-    from: Arc<Mutex<String>>,
 }
 
 impl MyTokenizer {
-    pub fn new(tokenizer: SentenceTokenizer, from: Arc<Mutex<String>>) -> Self {
-        Self { tokenizer, from }
+    pub fn new(tokenizer: SentenceTokenizer) -> Self {
+        Self { tokenizer }
     }
-}
 
-impl Tokenizer for MyTokenizer {
-    fn encode(&self, input: &str) -> anyhow::Result<Vec<String>> {
+    /// Tokens of a text in `lang`, which start with its language token.
+    pub fn encode_lang(&self, input: &str, lang: &str) -> anyhow::Result<Vec<String>> {
         let mut encoded = self.tokenizer.encode(input)?;
-        encoded.insert(0, self.from.lock().unwrap().clone());
+        encoded.insert(0, lang.to_owned());
         Ok(encoded)
     }
 
+    /// Length of [`MyTokenizer::encode_lang`] in tokens.
+    pub fn count(&self, input: &str) -> anyhow::Result<usize> {
+        Ok(self.tokenizer.encode(input)?.len() + 1)
+    }
+
+    /// Tokens the translation into `to` is forced to start with.
+    pub fn encode_prefix(&self, input: &str, to: &str) -> Vec<String> {
+        let mut encoded = self.tokenizer.encode_prefix(input);
+        encoded.insert(0, to.to_owned());
+        encoded
+    }
+}
+
+impl Tokenizer for MyTokenizer {
+    /// Fails, mBART-50 needs the language token of [`MyTokenizer::encode_lang`]. Use
+    /// [`Ct2Translator::translate_tokens`] and [`Ct2Translator::score_tokens`] with this
+    /// tokenizer.
+    fn encode(&self, _: &str) -> anyhow::Result<Vec<String>> {
+        bail!("mBART-50 inputs need a language token, encode them with encode_lang")
+    }
+
     fn decode(&self, tokens: Vec<String>) -> anyhow::Result<String> {
         self.tokenizer.decode(tokens)
     }
 }
 
 pub struct MBart50Translator {
-    loaded_models: Option<ct2rs::Translator<MyTokenizer>>,
+    loaded_models: Option<Managed<Ct2Translator<MyTokenizer>>>,
     cuda: bool,
     compute_type: ComputeType,
-    from: Arc<Mutex<String>>,
+    options: DecodeOptions,
+    threads: Threads,
+    files: ModelFiles,
 }
 
 impl MBart50Translator {
-    /// single_loaded will only allow one model to be loaded at a time.
     pub fn new(cuda: bool, compute_type: ComputeType) -> Self {
         MBart50Translator {
             compute_type,
+            options: DecodeOptions::default(),
+            threads: Threads::default(),
+            files: ModelFiles::default(),
             cuda,
             loaded_models: None,
-            from: Arc::default(),
         }
     }
 }
@@ -83,33 +114,205 @@ impl BlockingTranslator for MBart50Translator {
         from: Language,
         to: &Language,
     ) -> anyhow::Result<Vec<String>> {
+        let options = self.options;
+        self.translate_vec_with(query, from, to, &options)
+    }
+}
+
+impl MBart50Translator {
+    /// Threads of the model, applied when it is loaded next.
+    pub fn with_threads(mut self, threads: Threads) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Model files from a local directory, archives or mirrors instead of the default URLs.
+    pub fn with_model_files(mut self, files: ModelFiles) -> Self {
+        self.files = files;
+        self
+    }
+
+    /// Decoding of the calls without own options.
+    pub fn with_decode_options(mut self, options: DecodeOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn set_decode_options(&mut self, options: DecodeOptions) {
+        self.options = options;
+    }
+
+    /// [`BlockingTranslator::translate_vec`] decoding with `options`.
+    pub fn translate_vec_with(
+        &mut self,
+        query: &[String],
+        from: Language,
+        to: &Language,
+        options: &DecodeOptions,
+    ) -> anyhow::Result<Vec<String>> {
+        Ok(best(
+            self.translate_vec_alternatives(query, from, to, options)?,
+        )?)
+    }
+
+    /// Up to `options.num_hypotheses` scored alternatives per query, best first.
+    pub fn translate_vec_alternatives(
+        &mut self,
+        query: &[String],
+        from: Language,
+        to: &Language,
+        options: &DecodeOptions,
+    ) -> anyhow::Result<Vec<Vec<Alternative>>> {
         let from = from.to_mbart_50().ok_or(Error::UnknownLanguage(from))?;
-        let to = to.to_mbart_50().ok_or(Error::UnknownLanguage(to.clone()))?;
-        *self.from.lock().unwrap() = from.to_owned();
-        let model = self.load()?;
-        let trans = model.translate_batch_with_target_prefix(
+        let model = self.model()?;
+        alternatives(&model, query, &vec![from; query.len()], to, options)
+    }
+
+    /// [`Self::translate_vec_alternatives`] of queries with their own source language, in one
+    /// batch.
+    pub fn translate_mixed(
+        &mut self,
+        query: &[(Language, String)],
+        to: &Language,
+        options: &DecodeOptions,
+    ) -> anyhow::Result<Vec<Vec<Alternative>>> {
+        let (from, query): (Vec<_>, Vec<_>) = query
+            .iter()
+            .map(|(from, v)| {
+                let from = from.to_mbart_50().ok_or(Error::UnknownLanguage(*from))?;
+                Ok((from, v.as_str()))
+            })
+            .collect::<Result<Vec<_>, Error>>()?
+            .into_iter()
+            .unzip();
+        let model = self.model()?;
+        alternatives(&model, &query, &from, to, options)
+    }
+
+    /// Translations of every `source` that continue the `prefix` at the same index, like the
+    /// start of a sentence the user already typed. Up to `options.num_hypotheses` per source,
+    /// best first, each text includes its prefix.
+    pub fn complete(
+        &mut self,
+        source: &[String],
+        prefix: &[String],
+        from: Language,
+        to: &Language,
+        options: &DecodeOptions,
+    ) -> anyhow::Result<Vec<Vec<Alternative>>> {
+        let from = from.to_mbart_50().ok_or(Error::UnknownLanguage(from))?;
+        let to = to.to_mbart_50().ok_or(Error::UnknownLanguage(*to))?;
+        let model = self.model()?;
+        let source = source
+            .iter()
+            .map(|v| model.tokenizer().encode_lang(v, from))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let prefix = prefix
+            .iter()
+            .map(|v| model.tokenizer().encode_prefix(v, to))
+            .collect::<Vec<_>>();
+        model.translate_tokens(source, Some(&prefix), &options.to_translation_options())
+    }
+
+    /// Log-likelihood of every `target` as translation of the `source` at the same index.
+    pub fn score(
+        &mut self,
+        source: &[String],
+        target: &[String],
+        from: Language,
+        to: &Language,
+    ) -> anyhow::Result<Vec<Score>> {
+        let from = from.to_mbart_50().ok_or(Error::UnknownLanguage(from))?;
+        let to = to.to_mbart_50().ok_or(Error::UnknownLanguage(*to))?;
+        let model = self.model()?;
+        let source = source
+            .iter()
+            .map(|v| model.tokenizer().encode_lang(v, from))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let target = target
+            .iter()
+            .map(|v| model.tokenizer().encode_lang(v, to))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        model.score_tokens(&source, &target)
+    }
+
+    /// The loaded model, loaded again if the model manager unloaded it.
+    fn model(&mut self) -> anyhow::Result<Arc<Ct2Translator<MyTokenizer>>> {
+        self.load()?
+            .get()
+            .context("model was unloaded right after loading")
+    }
+
+    /// Path of `file` of the model `key`, from the local files or downloaded.
+    fn model_file(&mut self, key: &str, file: &str) -> anyhow::Result<PathBuf> {
+        match self.files.resolve(&self.models(), key, file)? {
+            Some(path) => Ok(path),
+            None => self.download_model(key, file),
+        }
+    }
+}
+
+/// Alternatives of every query, translated from the language token at its index.
+fn alternatives<S: AsRef<str>>(
+    model: &Ct2Translator<MyTokenizer>,
+    query: &[S],
+    from: &[&str],
+    to: &Language,
+    options: &DecodeOptions,
+) -> anyhow::Result<Vec<Vec<Alternative>>> {
+    let token = to.to_mbart_50().ok_or(Error::UnknownLanguage(*to))?;
+    let tokenizer = model.tokenizer();
+    let segments = Segmenter::new(options.segment_length(MAX_INPUT_LENGTH), |v: &str| {
+        tokenizer.count(v)
+    })
+    .target(to)
+    .split(query)?;
+    let tokens = segments
+        .texts
+        .iter()
+        .zip(segments.query_indices())
+        .map(|(v, i)| tokenizer.encode_lang(v, from[i]))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let trans = model.translate_tokens(
+        tokens,
+        Some(&vec![vec![token.to_owned()]; segments.texts.len()]),
+        &options.to_translation_options(),
+    )?;
+    segments.join_alternatives(trans)
+}
+
+impl SharedModel for MBart50Translator {
+    fn prepare(&mut self, _: Language, _: &Language) -> anyhow::Result<()> {
+        self.load()?;
+        Ok(())
+    }
+
+    fn translate_shared(
+        &self,
+        query: &[String],
+        from: Language,
+        to: &Language,
+    ) -> anyhow::Result<Option<Vec<String>>> {
+        let Some(model) = self.loaded_models.as_ref().and_then(Managed::get) else {
+            return Ok(None);
+        };
+        let from = from.to_mbart_50().ok_or(Error::UnknownLanguage(from))?;
+        let from = vec![from; query.len()];
+        Ok(Some(best(alternatives(
+            &model,
             query,
-            &vec![vec![to.to_string()]; query.len()],
-            &TranslationOptions {
-                batch_type: BatchType::Examples,
-                repetition_penalty: 3.0,
-                replace_unknowns: true,
-                disable_unk: true,
-                return_alternatives: false,
-                beam_size: 5,
-                ..Default::default()
-            },
-            None,
-        )?;
-        Ok(trans.into_iter().map(|v| v.0).collect())
+            &from,
+            to,
+            &self.options,
+        )?)?))
     }
 }
 
 impl ModelLoad for MBart50Translator {
-    type T = ct2rs::Translator<MyTokenizer>;
+    type T = Managed<Ct2Translator<MyTokenizer>>;
 
     fn loaded(&self) -> bool {
-        self.loaded_models.is_some()
+        self.loaded_models.as_ref().is_some_and(Managed::is_loaded)
     }
 
     fn get_model(&mut self) -> Option<&mut Self::T> {
@@ -118,25 +321,29 @@ impl ModelLoad for MBart50Translator {
 
     fn reload(&mut self) -> anyhow::Result<&mut Self::T> {
         let model =
-            self.download_model("large-many-to-many-mmt", "large-many-to-many-mmt/model.bin")?;
-        let path = self.download_model("spm", "sentencepiece.bpe.model")?;
-        let tokenizer = MyTokenizer::new(SentenceTokenizer::new(path), self.from.clone());
+            self.model_file("large-many-to-many-mmt", "large-many-to-many-mmt/model.bin")?;
         let model = model.parent().map(|v| v.to_path_buf()).unwrap_or(model);
-        let v = ct2rs::Translator::with_tokenizer(
+        let key = model_key(
+            &format!("{}/large-many-to-many-mmt", Self::name()),
+            &model,
+            self.cuda,
+            self.compute_type,
+            self.threads,
+        );
+        if let Some(model) = ModelManager::global().get(&key) {
+            return Ok(self.loaded_models.insert(model));
+        }
+        let path = self.model_file("spm", "sentencepiece.bpe.model")?;
+        let tokenizer = MyTokenizer::new(SentenceTokenizer::new(path));
+        let bytes = model_size(&model);
+        let v = Ct2Translator::new(
             model,
             tokenizer,
-            &Config {
-                device: match self.cuda {
-                    true => Device::CUDA,
-                    false => Device::CPU,
-                },
-                compute_type: self.compute_type,
-                ..Default::default()
-            },
+            &self.threads.config(self.cuda, self.compute_type),
         )?;
 
-        self.loaded_models = Some(v);
-        Ok(self.loaded_models.as_mut().unwrap())
+        let model = ModelManager::global().insert(key, bytes, v);
+        Ok(self.loaded_models.insert(model))
     }
 }
 
@@ -144,7 +351,7 @@ impl Model for MBart50Translator {
     impl_model_load_helpers!("translator", "mbart50");
 
     fn models(&self) -> std::collections::HashMap<&'static str, interface_model::ModelSource> {
-        hashmap! {
+        self.files.sources(hashmap! {
             "large-many-to-many-mmt" => ModelSource {
                 url: "https://github.com/frederik-uni/aiotranslator/releases/download/mbart-large-50-many-to-many-mmt/large-many-to-many-mmt.tar.gz",
                 hash: "325b0280b362b45e4a24c68fa824549cdf9febacd4b17234cd2a57f4ec56e474",
@@ -154,7 +361,7 @@ impl Model for MBart50Translator {
                 url: "https://github.com/frederik-uni/aiotranslator/releases/download/mbart-large-50-many-to-many-mmt/sentencepiece.bpe.model",
                 hash: "cfc8146abe2a0488e9e2a0c56de7952f7c11ab059eca145a0a727afce0db2865"
             }
-        }
+        })
     }
 
     fn unload(&mut self) {
@@ -0,0 +1,69 @@
+//! Throughput on CPU for 1,000 lines of mixed length, all in one batch in their order and
+//! sorted into batches by token count.
+//!
+//! ```sh
+//! cargo run --release -p aio-translator-nllb --example batching
+//! ```
+
+use std::time::Instant;
+
+use aio_translator_interface::{Language, Model, decode::DecodeOptions};
+use aio_translator_nllb::{NLLBTranslator, Size};
+use ct2rs::ComputeType;
+
+const SENTENCES: [&str; 8] = [
+    "Yes.",
+    "The train was late again.",
+    "Could you send me the report before the meeting tomorrow?",
+    "We walked along the river until the sun went down behind the hills.",
+    "Thank you!",
+    "The new library opens on Monday, and children under twelve can borrow up to ten books at once.",
+    "I don't know.",
+    "After years of planning, the city finally started building the bridge that connects the old town with the harbour.",
+];
+
+/// One to eight sentences per line, the same lines on every run.
+fn lines() -> Vec<String> {
+    let mut state = 42u64;
+    let mut next = move |n: usize| {
+        state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (state >> 33) as usize % n
+    };
+    (0..1000)
+        .map(|_| {
+            let count = 1 + next(8);
+            (0..count)
+                .map(|_| SENTENCES[next(SENTENCES.len())])
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect()
+}
+
+fn main() -> anyhow::Result<()> {
+    let lines = lines();
+    let mut nllb = NLLBTranslator::new(false, ComputeType::DEFAULT, Size::SmallDistilled);
+    nllb.load()?;
+    for (name, options) in [
+        ("one batch", DecodeOptions::default()),
+        (
+            "sorted, 4096 tokens",
+            DecodeOptions::default().batch_tokens(4096),
+        ),
+        (
+            "sorted, 1024 tokens",
+            DecodeOptions::default().batch_tokens(1024),
+        ),
+    ] {
+        let start = Instant::now();
+        nllb.translate_vec_with(&lines, Language::English, &Language::German, &options)?;
+        let elapsed = start.elapsed();
+        println!(
+            "{name}: {:.1} lines/s in {elapsed:.1?}",
+            lines.len() as f64 / elapsed.as_secs_f64()
+        );
+    }
+    Ok(())
+}
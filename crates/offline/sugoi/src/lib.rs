@@ -1,99 +1,68 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, LazyLock},
+};
+
 use aio_translator_interface::{
     BlockingTranslator, Language, Model, Translator, TranslatorMutTrait, TranslatorTrait,
-    error::{self, Error},
+    ct2::{Ct2Translator, MAX_INPUT_LENGTH, Score, Threads, model_key},
+    decode::{Alternative, DecodeOptions, best},
+    error,
+    files::ModelFiles,
+    manager::{Managed, ModelManager, model_size},
+    pool::SharedModel,
     prompt::PromptBuilder,
+    segment::Segmenter,
     tokenizer::SentenceTokenizer,
 };
-use ct2rs::{BatchType, ComputeType, Config, Device, Tokenizer, TranslationOptions};
+use anyhow::Context;
+use ct2rs::{ComputeType, Tokenizer};
 
 use interface_model::{ModelLoad, ModelSource, impl_model_load_helpers};
 use maplit::hashmap;
 use regex::Regex;
 
 pub struct SugoiTranslator {
-    loaded_models: Option<ct2rs::Translator<MyTokenizer>>,
+    loaded_models: Option<Managed<Ct2Translator<MyTokenizer>>>,
     cuda: bool,
     compute_type: ComputeType,
+    options: DecodeOptions,
+    threads: Threads,
+    files: ModelFiles,
 }
 
-fn split_sentences(q: &str, re: &Regex) -> Vec<String> {
-    let mut result = Vec::new();
-    let mut last = 0;
-
-    for mat in re.find_iter(q) {
-        let start = mat.start();
-        let end = mat.end();
-        if last < start {
-            result.push(q[last..start].to_string());
-        }
-        result.push(q[start..end].to_string());
-        last = end;
+/// The model only translates Japanese into English.
+fn check_languages(from: Language, to: &Language) -> Result<(), error::Error> {
+    match (from, to) {
+        (Language::Japanese, Language::English) => Ok(()),
+        _ => Err(error::Error::UnknownLanguageGroup(from, *to)),
     }
+}
 
-    if last < q.len() {
-        result.push(q[last..].to_string());
-    }
+static SENTENCE_END: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[.。]").unwrap());
 
-    result
-}
-fn tokenize(queries: &[String]) -> (Vec<String>, Vec<usize>) {
-    let mut new_queries: Vec<String> = vec![];
-    let mut query_split_sizes: Vec<usize> = vec![];
-    let re2 = Regex::new(r"[.。]").unwrap();
-
-    let re = Regex::new(r"(\w[.‥…!?。・]+)").unwrap();
-
-    for q in queries {
-        let sentences = split_sentences(&q, &re);
-        let mut chunk_queries = vec![];
-        for chunk in sentences.chunks(4) {
-            let s = chunk.concat();
-            let replaced = re2.replace_all(&s, "@").to_string();
-            chunk_queries.push(replaced);
-        }
-        query_split_sizes.push(chunk_queries.len());
-        new_queries.extend(chunk_queries);
-    }
-    (new_queries, query_split_sizes)
+/// Sentence ends as the model writes them.
+fn pre_tokenize(text: &str) -> String {
+    SENTENCE_END.replace_all(text, "@").into_owned()
 }
 
-fn detokenize(queries: Vec<String>, query_split_sizes: Vec<usize>) -> Vec<String> {
-    let mut new_translations = vec![];
-    let mut i = 0;
-    for query_count in query_split_sizes {
-        let sentences = &queries[i..i + query_count].join(" ");
-        i += query_count;
-        let sentences = sentences
-            .replace('@', ".")
-            .replace('▁', " ")
-            .replace("<unk>", "");
-        new_translations.push(sentences);
-    }
-    new_translations
+fn detokenize(text: &str) -> String {
+    text.replace('@', ".")
+        .replace('▁', " ")
+        .replace("<unk>", "")
 }
 
 impl SugoiTranslator {
-    /// single_loaded will only allow one model to be loaded at a time.
     pub fn new(cuda: bool, compute_type: ComputeType) -> Self {
         SugoiTranslator {
             compute_type,
+            options: DecodeOptions::default(),
+            threads: Threads::default(),
+            files: ModelFiles::default(),
             cuda,
             loaded_models: Default::default(),
         }
     }
-
-    fn pre_tokenize(&self, queries: &[String]) -> Result<(Vec<String>, Vec<usize>), Error> {
-        let (queries, query_split_sizes) = tokenize(queries);
-        Ok((queries, query_split_sizes))
-    }
-
-    fn post_detokenize(
-        &self,
-        sentences: Vec<String>,
-        query_split_sizes: Vec<usize>,
-    ) -> anyhow::Result<Vec<String>> {
-        Ok(detokenize(sentences, query_split_sizes))
-    }
 }
 
 impl Translator for SugoiTranslator {
@@ -129,28 +98,145 @@ impl BlockingTranslator for SugoiTranslator {
         from: Language,
         to: &Language,
     ) -> anyhow::Result<Vec<String>> {
-        if let (Language::Japanese, Language::English) = (from, to) {
-        } else {
-            Err(error::Error::UnknownLanguageGroup(from, to.clone()))?;
-        };
+        let options = self.options;
+        self.translate_vec_with(query, from, to, &options)
+    }
+}
 
-        let (query, query_split_sizes) = self.pre_tokenize(query)?;
-        let model = self.load()?;
-        let trans = model.translate_batch(
-            &query,
-            &TranslationOptions {
-                batch_type: BatchType::Examples,
-                beam_size: 5,
-                repetition_penalty: 3.0,
-                num_hypotheses: 1,
-                replace_unknowns: true,
-                disable_unk: true,
-                return_alternatives: false,
-                ..Default::default()
-            },
-            None,
-        )?;
-        self.post_detokenize(trans.into_iter().map(|v| v.0).collect(), query_split_sizes)
+impl SugoiTranslator {
+    /// Threads of the model, applied when it is loaded next.
+    pub fn with_threads(mut self, threads: Threads) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Model files from a local directory, archives or mirrors instead of the default URLs.
+    pub fn with_model_files(mut self, files: ModelFiles) -> Self {
+        self.files = files;
+        self
+    }
+
+    /// Decoding of the calls without own options.
+    pub fn with_decode_options(mut self, options: DecodeOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn set_decode_options(&mut self, options: DecodeOptions) {
+        self.options = options;
+    }
+
+    /// [`BlockingTranslator::translate_vec`] decoding with `options`.
+    pub fn translate_vec_with(
+        &mut self,
+        query: &[String],
+        from: Language,
+        to: &Language,
+        options: &DecodeOptions,
+    ) -> anyhow::Result<Vec<String>> {
+        Ok(best(
+            self.translate_vec_alternatives(query, from, to, options)?,
+        )?)
+    }
+
+    /// Up to `options.num_hypotheses` scored alternatives per query, best first.
+    pub fn translate_vec_alternatives(
+        &mut self,
+        query: &[String],
+        from: Language,
+        to: &Language,
+        options: &DecodeOptions,
+    ) -> anyhow::Result<Vec<Vec<Alternative>>> {
+        check_languages(from, to)?;
+        let model = self.model()?;
+        alternatives(&model, query, to, options)
+    }
+
+    /// Log-likelihood of every `target` as translation of the `source` at the same index.
+    pub fn score(
+        &mut self,
+        source: &[String],
+        target: &[String],
+        from: Language,
+        to: &Language,
+    ) -> anyhow::Result<Vec<Score>> {
+        check_languages(from, to)?;
+
+        // sentence ends are written as @ on both sides, like in translations
+        let source = source.iter().map(|v| pre_tokenize(v)).collect::<Vec<_>>();
+        let model = self.model()?;
+        let target = target
+            .iter()
+            .map(|v| model.tokenizer().encode_target(&pre_tokenize(v)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        model.score_batch(&source, &target)
+    }
+
+    /// The loaded model, loaded again if the model manager unloaded it.
+    fn model(&mut self) -> anyhow::Result<Arc<Ct2Translator<MyTokenizer>>> {
+        self.load()?
+            .get()
+            .context("model was unloaded right after loading")
+    }
+
+    /// Path of `file` of the model `key`, from the local files or downloaded.
+    fn model_file(&mut self, key: &str, file: &str) -> anyhow::Result<PathBuf> {
+        match self.files.resolve(&self.models(), key, file)? {
+            Some(path) => Ok(path),
+            None => self.download_model(key, file),
+        }
+    }
+}
+
+/// Alternatives of every Japanese query in English.
+fn alternatives(
+    model: &Ct2Translator<MyTokenizer>,
+    query: &[String],
+    to: &Language,
+    options: &DecodeOptions,
+) -> anyhow::Result<Vec<Vec<Alternative>>> {
+    let segments = Segmenter::new(options.segment_length(MAX_INPUT_LENGTH), |v: &str| {
+        Ok(model.tokenizer().encode(&pre_tokenize(v))?.len())
+    })
+    .target(to)
+    .split(query)?;
+    let texts = segments
+        .texts
+        .iter()
+        .map(|v| pre_tokenize(v))
+        .collect::<Vec<_>>();
+    let trans = model
+        .translate_batch(&texts, None, &options.to_translation_options())?
+        .into_iter()
+        .map(|v| {
+            v.into_iter()
+                .map(|v| Alternative {
+                    text: detokenize(&v.text),
+                    ..v
+                })
+                .collect()
+        })
+        .collect();
+    segments.join_alternatives(trans)
+}
+
+impl SharedModel for SugoiTranslator {
+    fn prepare(&mut self, _: Language, _: &Language) -> anyhow::Result<()> {
+        self.load()?;
+        Ok(())
+    }
+
+    fn translate_shared(
+        &self,
+        query: &[String],
+        from: Language,
+        to: &Language,
+    ) -> anyhow::Result<Option<Vec<String>>> {
+        check_languages(from, to)?;
+        let Some(model) = self.loaded_models.as_ref().and_then(Managed::get) else {
+            return Ok(None);
+        };
+        Ok(Some(best(alternatives(&model, query, to, &self.options)?)?))
     }
 }
 
@@ -159,6 +245,13 @@ pub struct MyTokenizer {
     en: SentenceTokenizer,
 }
 
+impl MyTokenizer {
+    /// Tokens of an English translation.
+    pub fn encode_target(&self, input: &str) -> anyhow::Result<Vec<String>> {
+        self.en.encode(input)
+    }
+}
+
 impl Tokenizer for MyTokenizer {
     fn encode(&self, input: &str) -> anyhow::Result<Vec<String>> {
         self.ja.encode(input)
@@ -170,10 +263,10 @@ impl Tokenizer for MyTokenizer {
 }
 
 impl ModelLoad for SugoiTranslator {
-    type T = ct2rs::Translator<MyTokenizer>;
+    type T = Managed<Ct2Translator<MyTokenizer>>;
 
     fn loaded(&self) -> bool {
-        self.loaded_models.is_some()
+        self.loaded_models.as_ref().is_some_and(Managed::is_loaded)
     }
 
     fn get_model(&mut self) -> Option<&mut Self::T> {
@@ -181,30 +274,33 @@ impl ModelLoad for SugoiTranslator {
     }
 
     fn reload(&mut self) -> anyhow::Result<&mut Self::T> {
-        let ja_path = self.download_model("spm.ja.nopretok", "spm.ja.nopretok.model")?;
-        let en_path = self.download_model("spm.en.nopretok", "spm.en.nopretok.model")?;
-
-        let model = self.download_model("ja-en", "ja-en/model.bin")?;
-
+        let model = self.model_file("ja-en", "ja-en/model.bin")?;
         let model = model.parent().map(|v| v.to_path_buf()).unwrap_or(model);
+        let key = model_key(
+            &format!("{}/ja-en", Self::name()),
+            &model,
+            self.cuda,
+            self.compute_type,
+            self.threads,
+        );
+        if let Some(model) = ModelManager::global().get(&key) {
+            return Ok(self.loaded_models.insert(model));
+        }
+        let ja_path = self.model_file("spm.ja.nopretok", "spm.ja.nopretok.model")?;
+        let en_path = self.model_file("spm.en.nopretok", "spm.en.nopretok.model")?;
 
-        let v = ct2rs::Translator::with_tokenizer(
+        let bytes = model_size(&model);
+
+        let v = Ct2Translator::new(
             model,
             MyTokenizer {
                 ja: SentenceTokenizer::new(ja_path),
                 en: SentenceTokenizer::new(en_path),
             },
-            &Config {
-                device: match self.cuda {
-                    true => Device::CUDA,
-                    false => Device::CPU,
-                },
-                compute_type: self.compute_type,
-                ..Default::default()
-            },
+            &self.threads.config(self.cuda, self.compute_type),
         )?;
-        self.loaded_models = Some(v);
-        Ok(self.loaded_models.as_mut().unwrap())
+        let model = ModelManager::global().insert(key, bytes, v);
+        Ok(self.loaded_models.insert(model))
     }
 }
 
@@ -212,7 +308,7 @@ impl Model for SugoiTranslator {
     impl_model_load_helpers!("translator", "sugoi");
 
     fn models(&self) -> std::collections::HashMap<&'static str, interface_model::ModelSource> {
-        hashmap! {
+        self.files.sources(hashmap! {
             "ja-en" => ModelSource {
                 url: "https://github.com/frederik-uni/aiotranslator/releases/download/sugoi/ja-en.tar.gz",
                 hash: "1bb89212e1024e6ad649ed212a4201a524231c46b565819c3112e4c46b38b7ad",
@@ -225,7 +321,7 @@ impl Model for SugoiTranslator {
                 url: "https://github.com/frederik-uni/aiotranslator/releases/download/sugoi/spm.ja.nopretok.model",
                 hash: "1bff3529a8e0bd898f00707a4e36dc16540d84112cc8a4a14462c0099e4aab9d"
             }
-        }
+        })
     }
 
     fn unload(&mut self) {
@@ -239,6 +335,12 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn sentence_ends() {
+        assert_eq!(pre_tokenize("雨。Yes."), "雨@Yes@");
+        assert_eq!(detokenize("▁It@<unk>"), " It.");
+    }
+
     #[test]
     fn test_load() {
         let mut sugoi = SugoiTranslator::new(false, ComputeType::DEFAULT);
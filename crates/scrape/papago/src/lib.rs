@@ -1,6 +1,7 @@
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use aio_translator_interface::error::Error;
+use aio_translator_interface::http::{Http, HttpOptions};
 use aio_translator_interface::prompt::PromptBuilder;
 use aio_translator_interface::{
     AsyncTranslator, Language, TranslationListOutput, TranslationOutput, Translator,
@@ -10,23 +11,31 @@ use base64::Engine;
 use hmac::{Hmac, Mac};
 use md5::Md5;
 use regex::Regex;
-use reqwest::Client;
 use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
 use serde::{Deserialize, Serialize};
 
+pub const BASE_URL: &str = "https://papago.naver.com";
+
 pub struct PapagoTranslator {
-    client: Client,
+    http: Http,
     ver: String,
     honorific: bool,
 }
 
 impl PapagoTranslator {
     pub async fn new(honorific: bool) -> Result<PapagoTranslator, Error> {
-        let client = Client::new();
-        let ver = version_key(&client).await?;
+        Self::with_options(honorific, HttpOptions::new()).await
+    }
+
+    pub async fn with_options(
+        honorific: bool,
+        options: HttpOptions,
+    ) -> Result<PapagoTranslator, Error> {
+        let http = options.build(BASE_URL)?;
+        let ver = version_key(&http).await?;
 
         Ok(PapagoTranslator {
-            client,
+            http,
             ver,
             honorific,
         })
@@ -60,16 +69,22 @@ impl AsyncTranslator for PapagoTranslator {
             .map(|v| v.to_papago().ok_or(Error::UnknownLanguage(v)))
             .unwrap_or(Ok("auto"))?;
         let to = to.to_papago().ok_or(Error::UnknownLanguage(*to))?;
-        let url = "https://papago.naver.com/apis/n2mt/translate";
+        let path = "/apis/n2mt/translate";
 
         let ts = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_millis() as u64;
-        let ppg = get_auth_ppg(url, &self.ver, &uuid::Uuid::new_v4().to_string(), ts)?;
+        // signed with the public url, also when requests go through another base url
+        let ppg = get_auth_ppg(
+            &format!("{BASE_URL}{path}"),
+            &self.ver,
+            &uuid::Uuid::new_v4().to_string(),
+            ts,
+        )?;
         let content: Root1 = self
-            .client
-            .post(url)
+            .http
+            .post(path)
             .header(AUTHORIZATION, ppg)
             .header(
                 CONTENT_TYPE,
@@ -144,20 +159,15 @@ fn get_auth_ppg(
         base64::engine::general_purpose::STANDARD.encode(result)
     ))
 }
-async fn version_key(client: &Client) -> Result<String, Error> {
-    let script = client
-        .get("https://papago.naver.com")
-        .send()
-        .await?
-        .text()
-        .await?;
+async fn version_key(http: &Http) -> Result<String, Error> {
+    let script = http.get("/").send().await?.text().await?;
     let main_js = Regex::new(r"\/(main.*\.js)")
         .expect("Regex")
         .captures(&script)
         .unwrap()[1]
         .to_string();
-    let ver_data = client
-        .get(format!("https://papago.naver.com/{}", main_js))
+    let ver_data = http
+        .get(&format!("/{}", main_js))
         .send()
         .await?
         .text()
@@ -169,19 +179,14 @@ async fn version_key(client: &Client) -> Result<String, Error> {
         .to_string())
 }
 
-async fn get_languages() -> Result<Vec<String>, Error> {
-    let host = "https://papago.naver.com";
-    let client = Client::new();
-
-    let data = client.get(host).send().await?.text().await?;
+async fn get_languages(http: &Http) -> Result<Vec<String>, Error> {
+    let data = http.get("/").send().await?.text().await?;
     let url_path = Regex::new(r"/home\.(.*?)\.chunk\.js")
         .expect("Regex")
         .captures(&data)
         .unwrap()[0]
         .to_string();
-    let lang_detect_url = format!("{}{}", host, url_path);
-
-    let lang_html = client.get(lang_detect_url).send().await?.text().await?;
+    let lang_html = http.get(&url_path).send().await?.text().await?;
     let lang_re = Regex::new(r#"=\{ALL:(.*?)}"#).expect("Regex");
     let lang_str = lang_re.captures(&lang_html).unwrap()[0]
         .to_owned()
@@ -220,12 +225,23 @@ struct Root1 {
 #[cfg(test)]
 mod tests {
     use aio_translator_interface::{Language, Translator as _};
+    use aio_translator_mock::{Api, Fixture, MockServer};
 
-    use crate::{PapagoTranslator, get_languages};
+    use crate::{BASE_URL, PapagoTranslator, get_languages};
+
+    async fn translator(server: &MockServer) -> PapagoTranslator {
+        PapagoTranslator::with_options(false, server.options(Api::Papago))
+            .await
+            .expect("Failed to create translator")
+    }
 
     #[tokio::test]
     async fn all_langauges_available() {
-        let langs = get_languages().await.expect("Failed to fetch languages");
+        let server = MockServer::start().await;
+        let http = server.options(Api::Papago).build(BASE_URL).unwrap();
+        let langs = get_languages(&http)
+            .await
+            .expect("Failed to fetch languages");
         assert!(langs.len() > 0);
         for lang in langs {
             Language::from_papago(&lang).expect(&lang);
@@ -234,9 +250,8 @@ mod tests {
 
     #[tokio::test]
     async fn translate_unknown() {
-        let trans = PapagoTranslator::new(false)
-            .await
-            .expect("Failed to create translator");
+        let server = MockServer::start().await;
+        let trans = translator(&server).await;
         let trans = trans.translator();
         let trans = trans.as_async().expect("Failed to create async translator");
         let trans = trans
@@ -256,9 +271,8 @@ mod tests {
 
     #[tokio::test]
     async fn translate_known() {
-        let trans = PapagoTranslator::new(false)
-            .await
-            .expect("Failed to create translator");
+        let server = MockServer::start().await;
+        let trans = translator(&server).await;
         let trans = trans.translator();
         let trans = trans.as_async().expect("Failed to create async translator");
         let trans = trans
@@ -274,4 +288,43 @@ mod tests {
         assert_eq!(trans.lang, Some(Language::English));
         assert_eq!(trans.text, "Hallo Welt");
     }
+
+    #[tokio::test]
+    async fn signature_is_checked() {
+        let server = MockServer::start().await;
+        let mut trans = translator(&server).await;
+        trans.ver = "v0.0.0".to_owned();
+        let trans = trans.translator();
+        let trans = trans.as_async().unwrap();
+        let out = trans
+            .translate("Hello World", None, None, &Language::German)
+            .await;
+        assert!(out.is_err());
+    }
+
+    #[tokio::test]
+    async fn replay_translate_vec() {
+        let fixture = Fixture::start(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/translate.json"),
+            BASE_URL,
+        )
+        .await;
+        let trans = PapagoTranslator::with_options(false, fixture.options())
+            .await
+            .expect("Failed to create translator");
+        let trans = trans.translator();
+        let trans = trans.as_async().unwrap();
+        let trans = trans
+            .translate_vec(
+                &["Hello World".to_owned(), "This is a test".to_owned()],
+                None,
+                Some(Language::English),
+                &Language::German,
+            )
+            .await
+            .expect("Failed to translate");
+
+        assert_eq!(trans.lang, Some(Language::English));
+        assert_eq!(trans.text, ["Hallo Welt", "Das ist ein Test."]);
+    }
 }
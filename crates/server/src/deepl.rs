@@ -0,0 +1,334 @@
+//! DeepL v2 compatible `/v2/translate`, `/v2/languages` and `/v2/usage`.
+//!
+//! Authentication keys are accepted but not checked.
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+};
+
+use aio_translator::Language;
+use axum::{
+    Form, Json, Router,
+    extract::{FromRequest, Query, Request, State},
+    http::header::CONTENT_TYPE,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::{ApiError, AppState};
+
+/// Reported as limit by `/v2/usage`, there is none.
+const CHARACTER_LIMIT: u64 = 1_000_000_000_000;
+
+#[derive(Clone)]
+struct DeeplState {
+    app: AppState,
+    characters: Arc<AtomicU64>,
+}
+
+/// DeepL puts the message of an error into `message`.
+struct DeeplError(ApiError);
+
+impl From<ApiError> for DeeplError {
+    fn from(value: ApiError) -> Self {
+        Self(value)
+    }
+}
+
+impl From<anyhow::Error> for DeeplError {
+    fn from(value: anyhow::Error) -> Self {
+        Self(value.into())
+    }
+}
+
+impl IntoResponse for DeeplError {
+    fn into_response(self) -> Response {
+        (self.0.0, Json(json!({ "message": self.0.1 }))).into_response()
+    }
+}
+
+#[derive(Deserialize, Default)]
+struct TranslateRequest {
+    text: Vec<String>,
+    source_lang: Option<String>,
+    target_lang: String,
+    tag_handling: Option<String>,
+}
+
+impl TranslateRequest {
+    /// Form bodies repeat `text` for every entry.
+    fn from_pairs(pairs: Vec<(String, String)>) -> Self {
+        let mut request = Self::default();
+        for (key, value) in pairs {
+            match key.as_str() {
+                "text" => request.text.push(value),
+                "source_lang" => request.source_lang = Some(value),
+                "target_lang" => request.target_lang = value,
+                "tag_handling" => request.tag_handling = Some(value),
+                _ => {}
+            }
+        }
+        request
+    }
+}
+
+impl<S: Send + Sync> FromRequest<S> for TranslateRequest {
+    type Rejection = DeeplError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let json = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("application/json"));
+        match json {
+            true => Json::from_request(req, state)
+                .await
+                .map(|v| v.0)
+                .map_err(|e| ApiError::bad_request(e.body_text()).into()),
+            false => Form::from_request(req, state)
+                .await
+                .map(|v| Self::from_pairs(v.0))
+                .map_err(|e| ApiError::bad_request(e.body_text()).into()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct LanguagesQuery {
+    #[serde(rename = "type")]
+    kind: Option<String>,
+}
+
+/// Accepts regional variants like `EN-US` and `ZH-HANT`.
+fn language(code: &str, parameter: &str) -> Result<Language, ApiError> {
+    let code = code.to_uppercase();
+    if code == "ZH-HANT" {
+        return Ok(Language::ChineseTraditional);
+    }
+    let primary = code.split('-').next().unwrap_or_default();
+    Language::from_deepl(primary).ok_or_else(|| {
+        ApiError::bad_request(format!("Value for '{parameter}' not supported: {code}"))
+    })
+}
+
+fn code(language: &Language) -> String {
+    match language.to_deepl() {
+        Some(code) => code.to_owned(),
+        None => language.to_tag().unwrap_or_default().to_uppercase(),
+    }
+}
+
+pub fn router(state: AppState) -> Router {
+    let state = DeeplState {
+        app: state,
+        characters: Default::default(),
+    };
+    Router::new()
+        .route("/v2/translate", get(translate).post(translate))
+        .route("/v2/languages", get(languages).post(languages))
+        .route("/v2/usage", get(usage).post(usage))
+        .with_state(state)
+}
+
+async fn translate(
+    State(state): State<DeeplState>,
+    request: TranslateRequest,
+) -> Result<Json<Value>, DeeplError> {
+    if request.text.is_empty() {
+        Err(ApiError::bad_request("Parameter 'text' not specified."))?;
+    }
+    let to = language(&request.target_lang, "target_lang")?;
+    let from = match request.source_lang.as_deref() {
+        Some(code) if !code.is_empty() => Some(language(code, "source_lang")?),
+        _ => None,
+    };
+    let detected = request
+        .text
+        .iter()
+        .map(|v| from.or_else(|| state.app.detector.detect_language(v)))
+        .collect::<Vec<_>>();
+    state.characters.fetch_add(
+        request.text.iter().map(|v| v.chars().count() as u64).sum(),
+        Ordering::Relaxed,
+    );
+
+    let translated = match request.tag_handling.as_deref() {
+        None | Some("") => {
+            let items = request.text.into_iter().zip(detected.clone()).collect();
+            state.app.pool.translate(items, to).await?
+        }
+        Some("html") => {
+            let mut translated = vec![];
+            for (text, from) in request.text.into_iter().zip(&detected) {
+                translated.push(state.app.pool.translate_html(text, *from, to).await?);
+            }
+            translated
+        }
+        Some(other) => Err(ApiError::bad_request(format!(
+            "Value for 'tag_handling' not supported: {other}"
+        )))?,
+    };
+    let translations = translated
+        .into_iter()
+        .zip(detected)
+        .map(|(text, from)| {
+            json!({
+                "detected_source_language": from.map(|v| code(&v)).unwrap_or_default(),
+                "text": text,
+            })
+        })
+        .collect::<Vec<_>>();
+    Ok(Json(json!({ "translations": translations })))
+}
+
+async fn languages(
+    State(state): State<DeeplState>,
+    Query(query): Query<LanguagesQuery>,
+) -> Json<Value> {
+    let target = query.kind.as_deref() == Some("target");
+    Json(Value::Array(
+        state
+            .app
+            .languages
+            .iter()
+            .filter(|v| v.to_deepl().is_some())
+            .map(|language| {
+                let mut entry = json!({
+                    "language": code(language),
+                    "name": language.to_name().unwrap_or_default(),
+                });
+                if target {
+                    entry["supports_formality"] = json!(false);
+                }
+                entry
+            })
+            .collect(),
+    ))
+}
+
+async fn usage(State(state): State<DeeplState>) -> Json<Value> {
+    Json(json!({
+        "character_count": state.characters.load(Ordering::Relaxed),
+        "character_limit": CHARACTER_LIMIT,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use aio_translator::{Detector, OriginalTranslator};
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::{BatchOptions, TranslatorPool};
+
+    struct AsciiDetector;
+
+    impl Detector for AsciiDetector {
+        fn detect_language(&self, text: &str) -> Option<Language> {
+            text.is_ascii().then_some(Language::English)
+        }
+    }
+
+    async fn start() -> String {
+        let pool = TranslatorPool::new(
+            vec![Box::new(OriginalTranslator::new())],
+            BatchOptions::default(),
+        );
+        let state = AppState::new(
+            pool,
+            AsciiDetector,
+            vec![Language::English, Language::Japanese, Language::Abkhazian],
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(crate::serve(listener, router(state)));
+        format!("http://{address}")
+    }
+
+    #[tokio::test]
+    async fn translate() {
+        let url = start().await;
+        let client = reqwest::Client::new();
+
+        let response: Value = client
+            .post(format!("{url}/v2/translate"))
+            .header("Authorization", "DeepL-Auth-Key anything")
+            .form(&[
+                ("text", "Hello"),
+                ("text", "World"),
+                ("target_lang", "EN-US"),
+            ])
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(
+            response,
+            json!({ "translations": [
+                { "detected_source_language": "EN", "text": "Hello" },
+                { "detected_source_language": "EN", "text": "World" },
+            ] })
+        );
+
+        let response: Value = client
+            .post(format!("{url}/v2/translate"))
+            .json(&json!({ "text": ["<p>こんにちは</p>"], "source_lang": "JA", "target_lang": "EN", "tag_handling": "html" }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(
+            response,
+            json!({ "translations": [
+                { "detected_source_language": "JA", "text": "<p>こんにちは</p>" },
+            ] })
+        );
+
+        let response = client
+            .post(format!("{url}/v2/translate"))
+            .form(&[("text", "Hello"), ("target_lang", "XX")])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        let response: Value = response.json().await.unwrap();
+        assert_eq!(
+            response,
+            json!({ "message": "Value for 'target_lang' not supported: XX" })
+        );
+
+        let response: Value = reqwest::get(format!("{url}/v2/usage"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(response["character_count"], 10 + 12);
+    }
+
+    #[tokio::test]
+    async fn languages() {
+        let url = start().await;
+        let response: Value = reqwest::get(format!("{url}/v2/languages?type=target"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(
+            response,
+            json!([
+                { "language": "EN", "name": "English", "supports_formality": false },
+                { "language": "JA", "name": "Japanese", "supports_formality": false },
+            ])
+        );
+    }
+}
@@ -0,0 +1,93 @@
+//! HTTP facades over a pool of translators.
+
+pub mod deepl;
+pub mod libretranslate;
+mod pool;
+
+use std::sync::Arc;
+
+use aio_translator::{Detector, Language};
+use axum::{
+    Form, Json, Router,
+    extract::{FromRequest, Request},
+    http::{StatusCode, header::CONTENT_TYPE},
+    response::{IntoResponse, Response},
+};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use tokio::net::TcpListener;
+
+pub use pool::{BatchOptions, BoxedTranslator, TranslatorPool};
+
+#[derive(Clone)]
+pub struct AppState {
+    pub pool: Arc<TranslatorPool>,
+    pub detector: Arc<dyn Detector + Send + Sync>,
+    /// Languages the translators support
+    pub languages: Arc<Vec<Language>>,
+}
+
+impl AppState {
+    pub fn new(
+        pool: TranslatorPool,
+        detector: impl Detector + Send + Sync + 'static,
+        languages: Vec<Language>,
+    ) -> Self {
+        Self {
+            pool: Arc::new(pool),
+            detector: Arc::new(detector),
+            languages: Arc::new(languages),
+        }
+    }
+}
+
+/// Error with the status it is answered with.
+#[derive(Debug)]
+pub struct ApiError(pub StatusCode, pub String);
+
+impl ApiError {
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self(StatusCode::BAD_REQUEST, message.into())
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(value: anyhow::Error) -> Self {
+        Self(StatusCode::INTERNAL_SERVER_ERROR, format!("{value:#}"))
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+/// Request body sent either as JSON or as form.
+pub struct Payload<T>(pub T);
+
+impl<T: DeserializeOwned, S: Send + Sync> FromRequest<S> for Payload<T> {
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let json = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("application/json"));
+        match json {
+            true => Json::from_request(req, state)
+                .await
+                .map(|v| Self(v.0))
+                .map_err(|e| ApiError::bad_request(e.body_text())),
+            false => Form::from_request(req, state)
+                .await
+                .map(|v| Self(v.0))
+                .map_err(|e| ApiError::bad_request(e.body_text())),
+        }
+    }
+}
+
+pub async fn serve(listener: TcpListener, router: Router) -> std::io::Result<()> {
+    axum::serve(listener, router).await
+}
@@ -0,0 +1,363 @@
+//! LibreTranslate compatible `/translate`, `/detect` and `/languages`.
+
+use aio_translator::Language;
+use axum::{
+    Json, Router,
+    extract::State,
+    routing::{get, post},
+};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::{ApiError, AppState, Payload};
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Query {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Format {
+    #[default]
+    Text,
+    Html,
+}
+
+#[derive(Deserialize)]
+struct TranslateRequest {
+    q: Query,
+    #[serde(default = "auto")]
+    source: String,
+    target: String,
+    #[serde(default)]
+    format: Format,
+}
+
+#[derive(Deserialize)]
+struct DetectRequest {
+    q: String,
+}
+
+fn auto() -> String {
+    "auto".to_owned()
+}
+
+/// LibreTranslate calls traditional Chinese `zt`.
+fn code(language: &Language) -> &'static str {
+    match language {
+        Language::ChineseTraditional => "zt",
+        _ => language.to_tag().unwrap_or_default(),
+    }
+}
+
+/// `None` for `auto`.
+fn language(code: &str) -> Result<Option<Language>, ApiError> {
+    match code {
+        "auto" => Ok(None),
+        "zt" => Ok(Some(Language::ChineseTraditional)),
+        _ => Language::from_tag(code)
+            .map(Some)
+            .ok_or_else(|| ApiError::bad_request(format!("{code} is not supported"))),
+    }
+}
+
+/// Detectors don't report a confidence, a detected language counts as certain.
+fn detected(language: Option<Language>) -> Value {
+    match language {
+        Some(language) => json!({ "confidence": 100.0, "language": code(&language) }),
+        None => json!({ "confidence": 0.0, "language": "auto" }),
+    }
+}
+
+pub fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/translate", post(translate))
+        .route("/detect", post(detect))
+        .route("/languages", get(languages).post(languages))
+        .with_state(state)
+}
+
+async fn translate(
+    State(state): State<AppState>,
+    Payload(request): Payload<TranslateRequest>,
+) -> Result<Json<Value>, ApiError> {
+    let from = language(&request.source)?;
+    let to = language(&request.target)?
+        .ok_or_else(|| ApiError::bad_request("target can not be auto"))?;
+    let (texts, many) = match request.q {
+        Query::One(v) => (vec![v], false),
+        Query::Many(v) => (v, true),
+    };
+    let detected_languages = texts
+        .iter()
+        .map(|v| match from {
+            Some(_) => None,
+            None => state.detector.detect_language(v),
+        })
+        .collect::<Vec<_>>();
+
+    let mut translated = vec![];
+    match request.format {
+        Format::Text => {
+            let items = texts
+                .into_iter()
+                .zip(&detected_languages)
+                .map(|(text, detected)| (text, from.or(*detected)))
+                .collect();
+            translated = state.pool.translate(items, to).await?;
+        }
+        Format::Html => {
+            for (text, detected) in texts.into_iter().zip(&detected_languages) {
+                translated.push(
+                    state
+                        .pool
+                        .translate_html(text, from.or(*detected), to)
+                        .await?,
+                );
+            }
+        }
+    }
+
+    let mut response = match many {
+        true => json!({ "translatedText": translated }),
+        false => json!({ "translatedText": translated.remove(0) }),
+    };
+    if from.is_none() {
+        let mut detected_languages = detected_languages.into_iter().map(detected);
+        response["detectedLanguage"] = match many {
+            true => Value::Array(detected_languages.collect()),
+            false => detected_languages.next().unwrap_or_default(),
+        };
+    }
+    Ok(Json(response))
+}
+
+async fn detect(
+    State(state): State<AppState>,
+    Payload(request): Payload<DetectRequest>,
+) -> Json<Value> {
+    let language = state.detector.detect_language(&request.q);
+    Json(Value::Array(
+        language.map(Some).map(detected).into_iter().collect(),
+    ))
+}
+
+async fn languages(State(state): State<AppState>) -> Json<Value> {
+    let codes = state
+        .languages
+        .iter()
+        .map(code)
+        .filter(|v| !v.is_empty())
+        .collect::<Vec<_>>();
+    Json(Value::Array(
+        state
+            .languages
+            .iter()
+            .filter(|v| !code(v).is_empty())
+            .map(|language| {
+                json!({
+                    "code": code(language),
+                    "name": language.to_name().unwrap_or_default(),
+                    "targets": codes.iter().filter(|v| **v != code(language)).collect::<Vec<_>>(),
+                })
+            })
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use aio_translator::{
+        BlockingTranslator, Detector, OriginalTranslator, PromptBuilder, Translator,
+        TranslatorMutTrait, TranslatorTrait,
+    };
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::{BatchOptions, BoxedTranslator, TranslatorPool};
+
+    struct AsciiDetector;
+
+    impl Detector for AsciiDetector {
+        fn detect_language(&self, text: &str) -> Option<Language> {
+            text.is_ascii().then_some(Language::English)
+        }
+    }
+
+    /// Upper-cases and records the size of every batch.
+    struct Recorder(Arc<Mutex<Vec<usize>>>);
+
+    impl Translator for Recorder {
+        fn local(&self) -> bool {
+            true
+        }
+
+        fn translator<'a>(&'a self) -> TranslatorTrait<'a> {
+            TranslatorTrait::Blocking(self)
+        }
+
+        fn translator_mut<'a>(&'a mut self) -> TranslatorMutTrait<'a> {
+            TranslatorMutTrait::Blocking(self)
+        }
+    }
+
+    impl BlockingTranslator for Recorder {
+        fn translate(
+            &mut self,
+            query: &str,
+            _: Option<PromptBuilder>,
+            from: Language,
+            to: &Language,
+        ) -> anyhow::Result<String> {
+            Ok(self
+                .translate_vec(&[query.to_owned()], None, from, to)?
+                .remove(0))
+        }
+
+        fn translate_vec(
+            &mut self,
+            query: &[String],
+            _: Option<PromptBuilder>,
+            _: Language,
+            _: &Language,
+        ) -> anyhow::Result<Vec<String>> {
+            self.0.lock().unwrap().push(query.len());
+            Ok(query.iter().map(|v| v.to_uppercase()).collect())
+        }
+    }
+
+    async fn start(translator: BoxedTranslator, options: BatchOptions) -> String {
+        let pool = TranslatorPool::new(vec![translator], options);
+        let state = AppState::new(
+            pool,
+            AsciiDetector,
+            vec![
+                Language::English,
+                Language::German,
+                Language::ChineseTraditional,
+            ],
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(crate::serve(listener, router(state)));
+        format!("http://{address}")
+    }
+
+    #[tokio::test]
+    async fn translate() {
+        let url = start(Box::new(OriginalTranslator::new()), BatchOptions::default()).await;
+        let client = reqwest::Client::new();
+
+        let response: Value = client
+            .post(format!("{url}/translate"))
+            .json(&json!({ "q": "Hello", "source": "auto", "target": "de" }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(
+            response,
+            json!({
+                "translatedText": "Hello",
+                "detectedLanguage": { "confidence": 100.0, "language": "en" },
+            })
+        );
+
+        let response: Value = client
+            .post(format!("{url}/translate"))
+            .json(&json!({ "q": ["a", "<b>b</b>"], "source": "en", "target": "zt", "format": "html" }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(response, json!({ "translatedText": ["a", "<b>b</b>"] }));
+
+        let response = client
+            .post(format!("{url}/translate"))
+            .form(&[("q", "Hallo"), ("source", "xx"), ("target", "en")])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 400);
+        let response: Value = response.json().await.unwrap();
+        assert_eq!(response, json!({ "error": "xx is not supported" }));
+    }
+
+    #[tokio::test]
+    async fn detect_and_languages() {
+        let url = start(Box::new(OriginalTranslator::new()), BatchOptions::default()).await;
+        let client = reqwest::Client::new();
+
+        let response: Value = client
+            .post(format!("{url}/detect"))
+            .form(&[("q", "Hello")])
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(response, json!([{ "confidence": 100.0, "language": "en" }]));
+
+        let response: Value = reqwest::get(format!("{url}/languages"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(response[0]["code"], "en");
+        assert_eq!(response[0]["name"], "English");
+        assert_eq!(response[0]["targets"], json!(["de", "zt"]));
+    }
+
+    #[tokio::test]
+    async fn micro_batching() {
+        let batches = Arc::new(Mutex::new(vec![]));
+        let options = BatchOptions {
+            max_batch: 64,
+            max_delay: Duration::from_millis(200),
+        };
+        let url = start(Box::new(Recorder(batches.clone())), options).await;
+        let client = reqwest::Client::new();
+
+        let requests = (0..8).map(|i| {
+            client
+                .post(format!("{url}/translate"))
+                .json(&json!({ "q": format!("text {i}"), "source": "en", "target": "de" }))
+                .send()
+        });
+        for (i, response) in spawn_all(requests).await.into_iter().enumerate() {
+            let response: Value = response.unwrap().json().await.unwrap();
+            assert_eq!(response["translatedText"], format!("TEXT {i}"));
+        }
+        let batches = batches.lock().unwrap();
+        assert_eq!(batches.iter().sum::<usize>(), 8);
+        assert!(batches.len() < 8, "{batches:?}");
+    }
+
+    async fn spawn_all<F: Future + Send + 'static>(
+        futures: impl Iterator<Item = F>,
+    ) -> Vec<F::Output>
+    where
+        F::Output: Send,
+    {
+        let handles = futures.map(tokio::spawn).collect::<Vec<_>>();
+        let mut out = vec![];
+        for handle in handles {
+            out.push(handle.await.unwrap());
+        }
+        out
+    }
+}
@@ -0,0 +1,209 @@
+//! Worker threads owning the translators.
+//!
+//! Every worker takes the jobs that arrive within a short window and translates them
+//! with one `translate_vec` call per language pair.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, Receiver, Sender},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use aio_translator::{Language, Translator, html::HtmlDocument, translate_batch};
+use anyhow::anyhow;
+use tokio::sync::oneshot;
+
+/// Translator owned by one worker of a [`TranslatorPool`].
+pub type BoxedTranslator = Box<dyn Translator + Send>;
+
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    /// Texts a worker collects before it stops waiting
+    pub max_batch: usize,
+    /// How long a worker waits for more jobs after the first one
+    pub max_delay: Duration,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            max_batch: 32,
+            max_delay: Duration::from_millis(5),
+        }
+    }
+}
+
+type Reply<T> = oneshot::Sender<anyhow::Result<T>>;
+
+enum Job {
+    Text {
+        items: Vec<(String, Option<Language>)>,
+        to: Language,
+        reply: Reply<Vec<String>>,
+    },
+    Html {
+        html: String,
+        from: Option<Language>,
+        to: Language,
+        reply: Reply<String>,
+    },
+}
+
+impl Job {
+    fn len(&self) -> usize {
+        match self {
+            Job::Text { items, .. } => items.len(),
+            Job::Html { .. } => 1,
+        }
+    }
+}
+
+pub struct TranslatorPool {
+    queue: Sender<Job>,
+}
+
+impl TranslatorPool {
+    /// Starts one worker thread per translator.
+    pub fn new(translators: Vec<BoxedTranslator>, options: BatchOptions) -> Self {
+        let (queue, receiver) = mpsc::channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for translator in translators {
+            let receiver = receiver.clone();
+            let options = options.clone();
+            thread::spawn(move || run(translator, &receiver, &options));
+        }
+        Self { queue }
+    }
+
+    /// Translates every text from its own source language.
+    pub async fn translate(
+        &self,
+        items: Vec<(String, Option<Language>)>,
+        to: Language,
+    ) -> anyhow::Result<Vec<String>> {
+        if items.is_empty() {
+            return Ok(vec![]);
+        }
+        let (reply, result) = oneshot::channel();
+        self.send(Job::Text { items, to, reply })?;
+        result.await?
+    }
+
+    pub async fn translate_html(
+        &self,
+        html: String,
+        from: Option<Language>,
+        to: Language,
+    ) -> anyhow::Result<String> {
+        let (reply, result) = oneshot::channel();
+        self.send(Job::Html {
+            html,
+            from,
+            to,
+            reply,
+        })?;
+        result.await?
+    }
+
+    fn send(&self, job: Job) -> anyhow::Result<()> {
+        self.queue
+            .send(job)
+            .map_err(|_| anyhow!("all translation workers stopped"))
+    }
+}
+
+fn run(mut translator: BoxedTranslator, queue: &Mutex<Receiver<Job>>, options: &BatchOptions) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("worker runtime");
+    loop {
+        let jobs = match queue.lock() {
+            Ok(queue) => collect(&queue, options),
+            Err(_) => return,
+        };
+        if jobs.is_empty() {
+            return;
+        }
+        runtime.block_on(process(translator.as_mut(), jobs));
+    }
+}
+
+/// Waits for a job and everything that arrives shortly after it.
+fn collect(queue: &Receiver<Job>, options: &BatchOptions) -> Vec<Job> {
+    let Ok(first) = queue.recv() else {
+        return vec![];
+    };
+    let deadline = Instant::now() + options.max_delay;
+    let mut size = first.len();
+    let mut jobs = vec![first];
+    while size < options.max_batch {
+        match queue.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(job) => {
+                size += job.len();
+                jobs.push(job);
+            }
+            Err(_) => break,
+        }
+    }
+    jobs
+}
+
+async fn process(translator: &mut (dyn Translator + Send), jobs: Vec<Job>) {
+    let mut texts = vec![];
+    for job in jobs {
+        match job {
+            Job::Text { items, to, reply } => texts.push((items, to, reply)),
+            Job::Html {
+                html,
+                from,
+                to,
+                reply,
+            } => {
+                let mut document = HtmlDocument::parse(&html);
+                let result = document.translate(translator, from, to).await;
+                let _ = reply.send(result.map(|_| document.to_string()));
+            }
+        }
+    }
+
+    // (job, item) of every text, per language pair
+    let mut groups = HashMap::<_, Vec<(usize, usize)>>::new();
+    for (job, (items, to, _)) in texts.iter().enumerate() {
+        for (item, (_, from)) in items.iter().enumerate() {
+            groups.entry((*from, *to)).or_default().push((job, item));
+        }
+    }
+    let mut results = texts
+        .iter()
+        .map(|v| Ok(vec![String::new(); v.0.len()]))
+        .collect::<Vec<anyhow::Result<Vec<String>>>>();
+    for ((from, to), entries) in groups {
+        let query = entries
+            .iter()
+            .map(|(job, item)| texts[*job].0[*item].0.clone())
+            .collect::<Vec<_>>();
+        match translate_batch(translator, &query, from, &to).await {
+            Ok(translated) => {
+                for ((job, item), text) in entries.into_iter().zip(translated) {
+                    if let Ok(out) = &mut results[job] {
+                        out[item] = text;
+                    }
+                }
+            }
+            Err(e) => {
+                let message = format!("{e:#}");
+                for (job, _) in entries {
+                    results[job] = Err(anyhow!("{message}"));
+                }
+            }
+        }
+    }
+    for ((_, _, reply), result) in texts.into_iter().zip(results) {
+        let _ = reply.send(result);
+    }
+}